@@ -0,0 +1,162 @@
+//! Benchmarks for reading entries and building reports from them.
+//!
+//! Each benchmark runs against on-disk databases seeded with 1k, 10k and 100k
+//! entries, or the comma separated counts in `TIMECARD_BENCH_ENTRIES`:
+//!
+//! ```text
+//! TIMECARD_BENCH_ENTRIES=500,5000 cargo bench --bench reads
+//! ```
+//!
+//! To compare a change against the current tree, save a baseline first and
+//! then measure against it; criterion prints the change for each benchmark:
+//!
+//! ```text
+//! cargo bench --bench reads -- --save-baseline before
+//! # make the change
+//! cargo bench --bench reads -- --baseline before
+//! ```
+
+// Std
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Crates
+use chrono::NaiveDate;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use sqlx::sqlite::SqlitePool;
+use tokio::runtime::{Builder, Runtime};
+
+// Modules
+use timecard::report::{self, ReportOptions, WeeklyReport};
+use timecard::{db, Week};
+
+static DEFAULT_ENTRY_COUNTS: &[usize] = &[1_000, 10_000, 100_000];
+
+/// Four two hour entries a day across eight projects, from 2020-01-01 on.
+static SEED_ENTRIES: &str = "
+    WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i + 1 < ?),
+    times(i, start) AS (
+        SELECT i, datetime('2020-01-01 08:00:00', '+' || (i / 4) || ' days', '+' || ((i % 4) * 2) || ' hours')
+        FROM n
+    )
+    INSERT INTO entries (start, stop, week_day, code, memo)
+    SELECT start,
+        datetime(start, '+2 hours'),
+        substr('SunMonTueWedThuFriSat', strftime('%w', start) * 3 + 1, 3),
+        '20-00' || (i % 8),
+        'work, work, work'
+    FROM times";
+
+fn entry_counts() -> Vec<usize> {
+    match env::var("TIMECARD_BENCH_ENTRIES") {
+        Ok(counts) => counts
+            .split(',')
+            .map(|count| count.trim().parse().expect("entry counts must be numbers"))
+            .collect(),
+        Err(_) => DEFAULT_ENTRY_COUNTS.to_vec(),
+    }
+}
+
+/// A database with `count` entries, removed again when dropped.
+struct BenchDb {
+    pool: SqlitePool,
+    path: PathBuf,
+}
+
+impl BenchDb {
+    async fn seed(count: usize) -> anyhow::Result<BenchDb> {
+        let path = env::temp_dir().join(format!("timecard_bench_{}.db", count));
+        let _ = fs::remove_file(&path);
+
+        let pool = SqlitePool::new(&format!("sqlite://{}", path.display())).await?;
+        db::setup_db(&pool).await?;
+        sqlx::query(SEED_ENTRIES)
+            .bind(count as i64)
+            .execute(&pool)
+            .await?;
+
+        Ok(BenchDb { pool, path })
+    }
+}
+
+impl Drop for BenchDb {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn runtime() -> Runtime {
+    Builder::new()
+        .basic_scheduler()
+        .build()
+        .expect("failed to start a runtime")
+}
+
+/// A week in the middle of even the smallest database.
+fn week() -> Week {
+    Week::new(NaiveDate::from_ymd(2020, 3, 4), 0, chrono::Weekday::Mon)
+}
+
+fn bench_reads(c: &mut Criterion) {
+    let mut rt = runtime();
+    let week = week();
+    let (begin, end) = (
+        format!("{} 00:00:00", week.begin()),
+        format!("{} 23:59:59", week.end()),
+    );
+
+    let mut group = c.benchmark_group("reads");
+    for count in entry_counts() {
+        let bench_db = rt.block_on(BenchDb::seed(count)).expect("failed to seed");
+        let pool = &bench_db.pool;
+
+        group.bench_with_input(
+            BenchmarkId::new("read_entries_between", count),
+            &count,
+            |b, _| {
+                b.iter(|| {
+                    rt.block_on(db::read_entries_between(pool, begin.clone(), end.clone()))
+                        .unwrap()
+                })
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("weekly_report", count), &count, |b, _| {
+            b.iter(|| {
+                let entries = rt
+                    .block_on(db::read_entries_between(pool, begin.clone(), end.clone()))
+                    .unwrap();
+                WeeklyReport::build(&entries, &week, ReportOptions::default()).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_reports(c: &mut Criterion) {
+    let mut rt = runtime();
+    let week = week();
+
+    let mut group = c.benchmark_group("reports");
+    for count in entry_counts() {
+        let bench_db = rt.block_on(BenchDb::seed(count)).expect("failed to seed");
+        let entries = rt.block_on(db::read_all_entries(&bench_db.pool)).unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::new("project_minutes", count),
+            &entries,
+            |b, entries| b.iter(|| report::project_minutes(entries).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("weekly_report_build", count),
+            &entries,
+            |b, entries| {
+                b.iter(|| WeeklyReport::build(entries, &week, ReportOptions::default()).unwrap())
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_reads, bench_reports);
+criterion_main!(benches);
@@ -1,299 +1,1034 @@
 // Std
 use std::convert::Infallible;
+use std::fmt;
+use std::sync::Arc;
 
 // Crates
 use anyhow::Result;
-use sqlx::sqlite::SqlitePool;
-use tracing::info;
-use warp::reply::Reply;
+use chrono::{Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, field, info, info_span, instrument, warn, Span};
+use uuid::Uuid;
+use warp::reply::{Reply, Response};
 use warp::{http, Filter};
 
 // Modules
-use crate::db;
-use crate::{Entry, Project};
+use crate::error::{FieldError, TimecardError};
+use crate::ics;
+use crate::report::{self, ReportOptions, WeeklyReport};
+use crate::storage::Storage;
+use crate::{
+    Entry, Export, LatestEntries, NewEntry, Project, ProjectCode, TimestampFormat, Week, Weekday,
+    REQUEST_ID_HEADER,
+};
+
+/// Largest `POST /import` body, which carries a whole database.
+const MAX_IMPORT_BYTES: u64 = 1024 * 1024 * 64;
 
 fn json_body_entry() -> impl Filter<Extract = (Entry,), Error = warp::Rejection> + Clone {
     warp::body::content_length_limit(1024 * 16).and(warp::body::json())
 }
 
+fn json_body_new_entry() -> impl Filter<Extract = (NewEntry,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn json_body_new_entries(
+) -> impl Filter<Extract = (Vec<NewEntry>,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
 fn json_body_project() -> impl Filter<Extract = (Project,), Error = warp::Rejection> + Clone {
     warp::body::content_length_limit(1024 * 16).and(warp::body::json())
 }
 
-fn with_pool(
-    pool: SqlitePool,
-) -> impl Filter<Extract = (SqlitePool,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || pool.clone())
+fn json_body_ids() -> impl Filter<Extract = (Vec<i32>,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn json_body_export() -> impl Filter<Extract = (Export,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(MAX_IMPORT_BYTES).and(warp::body::json())
+}
+
+#[derive(Deserialize)]
+struct FormatQuery {
+    #[serde(default)]
+    format: TimestampFormat,
+}
+
+/// Reads the timestamp format from `?format=rfc3339`, defaulting to the stored format.
+fn timestamp_format() -> impl Filter<Extract = (TimestampFormat,), Error = warp::Rejection> + Clone
+{
+    warp::query::<FormatQuery>().map(|query: FormatQuery| query.format)
+}
+
+#[derive(Deserialize)]
+struct ReportQuery {
+    #[serde(default)]
+    memos: bool,
+    first_day: Option<Weekday>,
+    #[serde(default)]
+    include_archive: bool,
+    #[serde(default)]
+    group: SummaryGroup,
+}
+
+#[derive(Deserialize)]
+struct NewEntryQuery {
+    #[serde(default)]
+    allow_duplicate: bool,
+}
+
+/// Either `n`, for the most recent entries, or `since_id`, for the entries
+/// after a cursor.
+#[derive(Deserialize)]
+struct LatestQuery {
+    n: Option<i32>,
+    since_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct RangeQuery {
+    from: String,
+    to: String,
+}
+
+/// Narrows entries to the projects of one client.
+#[derive(Deserialize)]
+struct ClientQuery {
+    client: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum SummaryGroup {
+    Project,
+    Client,
+}
+
+impl Default for SummaryGroup {
+    fn default() -> Self {
+        SummaryGroup::Project
+    }
+}
+
+#[derive(Deserialize)]
+struct SummaryQuery {
+    #[serde(default)]
+    group: SummaryGroup,
+    #[serde(default)]
+    include_archive: bool,
+}
+
+#[derive(Deserialize)]
+struct ImportQuery {
+    #[serde(default)]
+    merge: bool,
+}
+
+fn with_storage(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = (Arc<dyn Storage>,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || storage.clone())
+}
+
+/// The caller's `X-Request-Id`, or a new one if it didn't send any, recorded
+/// on the request's span.
+fn request_id() -> impl Filter<Extract = (String,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>(REQUEST_ID_HEADER).map(|id: Option<String>| {
+        let id = id
+            .filter(|id| !id.trim().is_empty())
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        Span::current().record("request_id", &id.as_str());
+        id
+    })
 }
 
 // Filters
+/// Every endpoint the server serves. Each request is handled in a `request`
+/// span carrying its request id, which is also echoed in the reply headers,
+/// including on requests no endpoint accepted.
+pub fn routes(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    request_id()
+        .and(endpoints(storage).recover(rejection_reply))
+        .map(|id: String, reply| warp::reply::with_header(reply, REQUEST_ID_HEADER, id))
+        .with(warp::trace(|info| {
+            info_span!(
+                "request",
+                method = %info.method(),
+                path = info.path(),
+                request_id = field::Empty,
+            )
+        }))
+}
+
+fn endpoints(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    post_entry(storage.clone())
+        .or(post_entries(storage.clone()))
+        .or(get_entry(storage.clone()))
+        .or(update_entry(storage.clone()))
+        .or(get_entries_between(storage.clone()))
+        .or(entries_ics(storage.clone()))
+        .or(read_last_entry(storage.clone()))
+        .or(read_last_entries(storage.clone()))
+        .or(read_latest_entries(storage.clone()))
+        .or(delete_entry(storage.clone()))
+        .or(delete_last_entry(storage.clone()))
+        .or(delete_last_entries(storage.clone()))
+        .or(undo(storage.clone()))
+        .or(weekly_report(storage.clone()))
+        .or(integrity(storage.clone()))
+        .or(summary(storage.clone()))
+        .or(clients(storage.clone()))
+        .or(export(storage.clone()))
+        .or(import(storage.clone()))
+        .or(post_project(storage.clone()))
+        .or(get_project(storage.clone()))
+        .or(get_all_projects(storage.clone()))
+        .or(update_project(storage.clone()))
+        .or(delete_project(storage))
+}
+
+/// Stores a new entry, refusing one identical to a stored entry unless
+/// `?allow_duplicate=true` is sent.
 pub fn post_entry(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::path!("entry")
         .and(warp::post())
-        .and(json_body_entry())
-        .and(with_pool(pool))
+        .and(warp::query::<NewEntryQuery>())
+        .and(json_body_new_entry())
+        .and(with_storage(storage))
         .and_then(new_entry)
 }
 
+/// Stores several new entries, such as the halves of a split entry, in one
+/// transaction: either all are stored or none are.
+pub fn post_entries(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("entries")
+        .and(warp::post())
+        .and(warp::query::<NewEntryQuery>())
+        .and(json_body_new_entries())
+        .and(with_storage(storage))
+        .and_then(new_entries)
+}
+
 pub fn get_entry(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::get()
         // .and(warp::path!("entry" / i32))
         .and(warp::path("entry"))
         .and(warp::path::param::<i32>())
-        .and(with_pool(pool))
+        .and(timestamp_format())
+        .and(with_storage(storage))
         .and_then(read_entry)
 }
 
+/// Entries between two timestamps, optionally only those on one client's
+/// projects, e.g. `/entries_between/{start}/{stop}?client=Acme`.
 pub fn get_entries_between(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::get()
         .and(warp::path!("entries_between" / String / String))
-        .and(with_pool(pool))
+        .and(warp::query::<ClientQuery>())
+        .and(timestamp_format())
+        .and(with_storage(storage))
         .and_then(entries_between)
 }
 
+/// Entries starting from one date through another as an iCalendar document,
+/// e.g. `/entries.ics?from=2021-02-01&to=2021-02-07`.
+pub fn entries_ics(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("entries.ics"))
+        .and(warp::query::<RangeQuery>())
+        .and(with_storage(storage))
+        .and_then(entries_ics_handler)
+}
+
 pub fn read_last_entry(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::get()
         .and(warp::path("last_entry"))
-        .and(with_pool(pool))
+        .and(timestamp_format())
+        .and(with_storage(storage))
         .and_then(last_entry)
 }
 
+pub fn read_last_entries(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path("last_entries"))
+        .and(warp::path::param::<i32>())
+        .and(timestamp_format())
+        .and(with_storage(storage))
+        .and_then(last_entries)
+}
+
+/// The `n` most recent entries, or the entries added after `since_id`, oldest
+/// first with the highest id to poll from next, e.g. `/entries/latest?n=5` or
+/// `/entries/latest?since_id=120`.
+pub fn read_latest_entries(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("entries" / "latest"))
+        .and(warp::query::<LatestQuery>())
+        .and(timestamp_format())
+        .and(with_storage(storage))
+        .and_then(latest_entries)
+}
+
 pub fn update_entry(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::post()
         .and(warp::path("update_entry"))
         .and(json_body_entry())
-        .and(with_pool(pool))
+        .and(with_storage(storage))
         .and_then(update_entry_handler)
 }
 
 pub fn delete_entry(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::post()
         .and(warp::path("delete_entry"))
         .and(warp::path::param::<i32>())
-        .and(with_pool(pool))
+        .and(with_storage(storage))
         .and_then(delete_entry_handler)
 }
 
 pub fn delete_last_entry(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::post()
         .and(warp::path("delete_last_entry"))
-        .and(with_pool(pool))
+        .and(with_storage(storage))
         .and_then(delete_last_entry_handler)
 }
 
+pub fn delete_last_entries(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::post()
+        .and(warp::path("delete_last_entries"))
+        .and(json_body_ids())
+        .and(with_storage(storage))
+        .and_then(delete_last_entries_handler)
+}
+
+pub fn undo(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::post()
+        .and(warp::path("undo"))
+        .and(with_storage(storage))
+        .and_then(undo_handler)
+}
+
 pub fn post_project(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::path!("project")
         .and(warp::post())
         .and(json_body_project())
-        .and(with_pool(pool))
+        .and(with_storage(storage))
         .and_then(new_project)
 }
 
 pub fn get_project(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::get()
         .and(warp::path("project"))
         .and(warp::path::param::<i32>())
-        .and(with_pool(pool))
+        .and(with_storage(storage))
         .and_then(read_project)
 }
 
 pub fn get_all_projects(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::get()
         .and(warp::path("all_projects"))
-        .and(with_pool(pool))
+        .and(with_storage(storage))
         .and_then(read_all_projects)
 }
 
 pub fn update_project(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::post()
         .and(warp::path("update_project"))
         .and(json_body_project())
-        .and(with_pool(pool))
+        .and(with_storage(storage))
         .and_then(update_project_handler)
 }
 
 pub fn delete_project(
-    pool: SqlitePool,
+    storage: Arc<dyn Storage>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::post()
         .and(warp::path("delete_project"))
         .and(warp::path::param::<String>())
-        .and(with_pool(pool))
+        .and(with_storage(storage))
         .and_then(delete_project_handler)
 }
 
+/// Weekly report for the week `n` weeks ago, the week containing a date or an
+/// ISO week, e.g. `/weekly_report/0?first_day=mon&memos=true`,
+/// `/weekly_report/2021-02-03` or `/weekly_report/2021-W05`. Weeks start on
+/// Sunday by default; ISO weeks always start on Monday. Archived entries are
+/// left out unless `?include_archive=true` is sent, and `?group=client`
+/// groups the rows by client.
+pub fn weekly_report(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path("weekly_report"))
+        .and(warp::path::param::<String>())
+        .and(warp::query::<ReportQuery>())
+        .and(with_storage(storage))
+        .and_then(weekly_report_handler)
+}
+
+pub fn integrity(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path("integrity"))
+        .and(with_storage(storage))
+        .and_then(integrity_handler)
+}
+
+/// Minutes per project code for entries starting between two dates, or per
+/// client with `?group=client`, e.g. `/summary/2021-01-01/2021-03-31`.
+/// Archived entries are left out unless `?include_archive=true` is sent.
+pub fn summary(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("summary" / String / String))
+        .and(warp::query::<SummaryQuery>())
+        .and(with_storage(storage))
+        .and_then(summary_handler)
+}
+
+/// Every client with its number of projects.
+pub fn clients(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("clients"))
+        .and(with_storage(storage))
+        .and_then(clients_handler)
+}
+
+/// Every project, entry and archived entry, for loading into another server
+/// with `/import`.
+pub fn export(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("export"))
+        .and(with_storage(storage))
+        .and_then(export_handler)
+}
+
+/// Loads an `/export` document into an empty database, or adds it to the
+/// stored data with `?merge=true`.
+pub fn import(
+    storage: Arc<dyn Storage>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("import"))
+        .and(warp::query::<ImportQuery>())
+        .and(json_body_export())
+        .and(with_storage(storage))
+        .and_then(import_handler)
+}
+
 // Handlers
-async fn new_entry(entry: Entry, pool: SqlitePool) -> Result<impl warp::Reply, Infallible> {
+#[instrument(skip(query, entry, storage), fields(code = %entry.code))]
+async fn new_entry(
+    query: NewEntryQuery,
+    entry: NewEntry,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
     info!("Processing new entry");
-    match db::write_entry(&pool, &entry).await {
-        Ok(_) => Ok(http::StatusCode::OK),
-        Err(_) => Ok(http::StatusCode::BAD_REQUEST),
+    if let Err(e) = entry.code.validate() {
+        return Ok(error_reply(&e));
+    }
+    match storage.write_entry(&entry, query.allow_duplicate).await {
+        Ok(id) => {
+            let created = Entry {
+                id: Some(id),
+                ..entry.into()
+            };
+            Ok(warp::reply::json(&created).into_response())
+        }
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[instrument(skip(query, entries, storage), fields(count = entries.len()))]
+async fn new_entries(
+    query: NewEntryQuery,
+    entries: Vec<NewEntry>,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
+    info!("Processing new entries");
+    if let Err(e) = entries.iter().try_for_each(|entry| entry.code.validate()) {
+        return Ok(error_reply(&e));
+    }
+    match storage.write_entries(&entries, query.allow_duplicate).await {
+        Ok(ids) => {
+            let created: Vec<Entry> = ids
+                .into_iter()
+                .zip(entries)
+                .map(|(id, entry)| Entry {
+                    id: Some(id),
+                    ..entry.into()
+                })
+                .collect();
+            Ok(warp::reply::json(&created).into_response())
+        }
+        Err(e) => Ok(error_reply(&e)),
     }
 }
 
-async fn read_entry(id: i32, pool: SqlitePool) -> Result<warp::reply::Response, Infallible> {
+#[instrument(skip(format, storage))]
+async fn read_entry(
+    id: i32,
+    format: TimestampFormat,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
     info!("Reading entry #{}", id);
-    match db::read_entry(&pool, id).await {
-        Ok(entry) => Ok(warp::reply::json(&entry).into_response()),
-        Err(_) => Ok(
-            warp::reply::with_status("Invalid id", http::StatusCode::BAD_REQUEST).into_response(),
-        ),
+    match storage.read_entry(id).await {
+        Ok(entry) => Ok(warp::reply::json(&entry.with_timestamp_format(format)).into_response()),
+        Err(e) => Ok(error_reply(&e)),
     }
 }
 
+#[instrument(skip(query, format, storage), fields(client = ?query.client))]
 async fn entries_between(
     start: String,
     stop: String,
-    pool: SqlitePool,
-) -> Result<impl warp::Reply, Infallible> {
+    query: ClientQuery,
+    format: TimestampFormat,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
     info!("Reading entries between {} and {}", start, stop);
-    match db::read_entries_between(&pool, start, stop).await {
-        Ok(entries) => Ok(warp::reply::json(&entries).into_response()),
-        Err(_) => Ok(
-            warp::reply::with_status("Invalid date range", http::StatusCode::BAD_REQUEST)
-                .into_response(),
-        ),
+    let entries = match query.client {
+        Some(client) => {
+            storage
+                .read_client_entries_between(start, stop, &client)
+                .await
+        }
+        None => storage.read_entries_between(start, stop).await,
+    };
+    match entries {
+        Ok(entries) => Ok(warp::reply::json(&with_format(entries, format)).into_response()),
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[instrument(skip(query, storage), fields(from = %query.from, to = %query.to))]
+async fn entries_ics_handler(
+    query: RangeQuery,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
+    info!("Exporting entries as iCalendar.");
+    let (from, to) = match (parse_date("from", &query.from), parse_date("to", &query.to)) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(e), _) | (_, Err(e)) => return Ok(error_reply(&e)),
+    };
+
+    let entries = match storage
+        .read_entries_between(format!("{} 00:00:00", from), format!("{} 23:59:59", to))
+        .await
+    {
+        Ok(entries) => entries,
+        Err(e) => return Ok(error_reply(&e)),
+    };
+
+    match ics::calendar(&entries, Utc::now().naive_utc()) {
+        Ok(calendar) => {
+            let content_type = "text/calendar; charset=utf-8";
+            Ok(warp::reply::with_header(calendar, "content-type", content_type).into_response())
+        }
+        Err(e) => Ok(internal_error(&e)),
     }
 }
 
-async fn last_entry(pool: SqlitePool) -> Result<impl warp::Reply, Infallible> {
+#[instrument(skip(format, storage))]
+async fn last_entry(
+    format: TimestampFormat,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
     info!("Reading most recent entry.");
-    match db::read_last_entry(&pool).await {
-        Ok(entry) => Ok(warp::reply::json(&entry).into_response()),
-        Err(_) => Ok(warp::reply::with_status(
-            "Failed to read last entry.",
-            http::StatusCode::INTERNAL_SERVER_ERROR,
-        )
-        .into_response()),
+    match storage.read_last_entry().await {
+        Ok(entry) => Ok(warp::reply::json(&entry.with_timestamp_format(format)).into_response()),
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[instrument(skip(format, storage))]
+async fn last_entries(
+    n: i32,
+    format: TimestampFormat,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
+    info!("Reading {} most recent entries.", n);
+    match storage.read_last_n_entries(n).await {
+        Ok(entries) => Ok(warp::reply::json(&with_format(entries, format)).into_response()),
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[instrument(skip(query, format, storage), fields(n = ?query.n, since_id = ?query.since_id))]
+async fn latest_entries(
+    query: LatestQuery,
+    format: TimestampFormat,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
+    info!("Reading latest entries.");
+    let entries = match (query.n, query.since_id) {
+        (Some(n), None) => storage.read_last_n_entries(n).await.map(|mut entries| {
+            entries.reverse();
+            entries
+        }),
+        (None, Some(since_id)) => storage.read_entries_since(since_id).await,
+        _ => Err(TimecardError::invalid(
+            "query",
+            "must have either n or since_id",
+        )),
+    };
+
+    match entries {
+        Ok(entries) => {
+            let max_id = entries.iter().filter_map(|e| e.id).max().or(query.since_id);
+            let latest = LatestEntries {
+                entries: with_format(entries, format),
+                max_id,
+            };
+            Ok(warp::reply::json(&latest).into_response())
+        }
+        Err(e) => Ok(error_reply(&e)),
     }
 }
 
+#[instrument(skip(entry, storage), fields(id = ?entry.id))]
 async fn update_entry_handler(
     entry: Entry,
-    pool: SqlitePool,
-) -> Result<impl warp::Reply, Infallible> {
-    info!("Reading most recent entry.");
-    match db::update_entry(&pool, &entry).await {
-        Ok(_) => Ok(http::StatusCode::OK),
-        Err(_) => Ok(http::StatusCode::BAD_REQUEST),
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
+    info!("Updating entry.");
+    if let Err(e) = entry.code.validate() {
+        return Ok(error_reply(&e));
+    }
+    match storage.update_entry(&entry).await {
+        Ok(_) => Ok(http::StatusCode::OK.into_response()),
+        Err(e) => Ok(error_reply(&e)),
     }
 }
 
-async fn delete_entry_handler(id: i32, pool: SqlitePool) -> Result<impl warp::Reply, Infallible> {
+#[instrument(skip(storage))]
+async fn delete_entry_handler(id: i32, storage: Arc<dyn Storage>) -> Result<Response, Infallible> {
     info!("Deleting entry #{}", id);
-    match db::delete_entry(&pool, id).await {
-        Ok(_) => Ok(http::StatusCode::OK),
-        Err(_) => Ok(http::StatusCode::BAD_REQUEST),
+    match storage.delete_entry(id).await {
+        Ok(_) => Ok(http::StatusCode::OK.into_response()),
+        Err(e) => Ok(error_reply(&e)),
     }
 }
 
-async fn delete_last_entry_handler(pool: SqlitePool) -> Result<impl warp::Reply, Infallible> {
+#[instrument(skip(storage))]
+async fn delete_last_entry_handler(storage: Arc<dyn Storage>) -> Result<Response, Infallible> {
     info!("Deleting most recent entry.");
-    match db::delete_last_entry(&pool).await {
-        Ok(_) => Ok(http::StatusCode::OK),
-        Err(_) => Ok(http::StatusCode::INTERNAL_SERVER_ERROR),
+    match storage.delete_last_entry().await {
+        Ok(_) => Ok(http::StatusCode::OK.into_response()),
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[instrument(skip(storage))]
+async fn delete_last_entries_handler(
+    ids: Vec<i32>,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
+    info!("Deleting entries {:?}", ids);
+    match storage.delete_last_n(&ids).await {
+        Ok(_) => Ok(http::StatusCode::OK.into_response()),
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[instrument(skip(storage))]
+async fn undo_handler(storage: Arc<dyn Storage>) -> Result<Response, Infallible> {
+    info!("Undoing most recent change.");
+    match storage.undo_last_action().await {
+        Ok(Some(record)) => Ok(warp::reply::json(&record).into_response()),
+        Ok(None) => Ok(error_reply(&TimecardError::NotFound(
+            "Change to undo".to_string(),
+        ))),
+        Err(e) => Ok(error_reply(&e)),
     }
 }
 
-async fn new_project(project: Project, pool: SqlitePool) -> Result<impl warp::Reply, Infallible> {
+fn with_format(entries: Vec<Entry>, format: TimestampFormat) -> Vec<Entry> {
+    entries
+        .into_iter()
+        .map(|entry| entry.with_timestamp_format(format))
+        .collect()
+}
+
+#[instrument(skip(project, storage), fields(code = %project.code))]
+async fn new_project(project: Project, storage: Arc<dyn Storage>) -> Result<Response, Infallible> {
     info!("Creating a new project.");
-    match db::write_project(&pool, &project).await {
-        Ok(_) => Ok(http::StatusCode::OK),
-        Err(_) => Ok(http::StatusCode::BAD_REQUEST),
+    if let Err(e) = project.code.validate() {
+        return Ok(error_reply(&e));
+    }
+    match storage.write_project(&project).await {
+        Ok(_) => Ok(http::StatusCode::OK.into_response()),
+        Err(e) => Ok(error_reply(&e)),
     }
 }
 
-async fn read_project(id: i32, pool: SqlitePool) -> Result<warp::reply::Response, Infallible> {
+#[instrument(skip(storage))]
+async fn read_project(id: i32, storage: Arc<dyn Storage>) -> Result<Response, Infallible> {
     info!("Reading project #{}", id);
-    match db::read_project(&pool, id).await {
+    match storage.read_project(id).await {
         Ok(project) => Ok(warp::reply::json(&project).into_response()),
-        Err(_) => Ok(
-            warp::reply::with_status("Invalid id", http::StatusCode::BAD_REQUEST).into_response(),
-        ),
+        Err(e) => Ok(error_reply(&e)),
     }
 }
 
-async fn read_all_projects(pool: SqlitePool) -> Result<warp::reply::Response, Infallible> {
+#[instrument(skip(storage))]
+async fn read_all_projects(storage: Arc<dyn Storage>) -> Result<Response, Infallible> {
     info!("Reading all projects.");
-    match db::read_all_projects(&pool).await {
+    match storage.read_all_projects().await {
         Ok(projects) => Ok(warp::reply::json(&projects).into_response()),
-        Err(_) => Ok(
-            warp::reply::with_status("Invalid id", http::StatusCode::BAD_REQUEST).into_response(),
-        ),
+        Err(e) => Ok(error_reply(&e)),
     }
 }
 
+#[instrument(skip(project, storage), fields(code = %project.code))]
 async fn update_project_handler(
     project: Project,
-    pool: SqlitePool,
-) -> Result<impl warp::Reply, Infallible> {
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
     info!("Updating project.");
-    match db::update_project(&pool, &project).await {
-        Ok(_) => Ok(http::StatusCode::OK),
-        Err(_) => Ok(http::StatusCode::BAD_REQUEST),
+    if let Err(e) = project.code.validate() {
+        return Ok(error_reply(&e));
+    }
+    match storage.update_project(&project).await {
+        Ok(_) => Ok(http::StatusCode::OK.into_response()),
+        Err(e) => Ok(error_reply(&e)),
     }
 }
 
+#[instrument(skip(storage))]
 async fn delete_project_handler(
     code: String,
-    pool: SqlitePool,
-) -> Result<impl warp::Reply, Infallible> {
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
     info!("Deleting project: {}", code);
-    match db::delete_project(&pool, code).await {
-        Ok(_) => Ok(warp::reply::with_status(
-            "Entry deleted.",
-            http::StatusCode::OK,
-        )),
-        Err(_) => Ok(warp::reply::with_status(
-            "Error deleting entry.",
-            http::StatusCode::BAD_REQUEST,
-        )),
+    let code = match code.parse::<ProjectCode>() {
+        Ok(code) => code,
+        Err(e) => return Ok(error_reply(&e)),
+    };
+    match storage.delete_project(&code).await {
+        Ok(_) => {
+            Ok(warp::reply::with_status("Entry deleted.", http::StatusCode::OK).into_response())
+        }
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[instrument(skip(query, storage))]
+async fn weekly_report_handler(
+    week: String,
+    query: ReportQuery,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
+    let first_day = query.first_day.unwrap_or(Weekday::Sun);
+    let week = match Week::resolve(&week, Local::today().naive_local(), first_day.into()) {
+        Ok(week) => week,
+        Err(e) => return Ok(error_reply(&e)),
+    };
+    info!("Building weekly report for {}.", week);
+    let options = ReportOptions { memos: query.memos };
+    let start = format!("{} 00:00:00", week.begin());
+    let end = format!("{} 23:59:59", week.end());
+
+    let mut entries = match storage
+        .read_entries_between(start.clone(), end.clone())
+        .await
+    {
+        Ok(entries) => entries,
+        Err(e) => return Ok(error_reply(&e)),
+    };
+    if query.include_archive {
+        match storage.read_archived_entries_between(start, end).await {
+            Ok(archived) => entries.extend(archived),
+            Err(e) => return Ok(error_reply(&e)),
+        }
+    }
+
+    let weekly = match WeeklyReport::build(&entries, &week, options) {
+        Ok(weekly) => weekly,
+        Err(e) => return Ok(internal_error(&e)),
+    };
+    if query.group == SummaryGroup::Project {
+        return Ok(warp::reply::json(&weekly).into_response());
     }
+
+    match storage.read_all_projects().await {
+        Ok(projects) => {
+            let grouped = report::group_by_client(weekly, &projects);
+            Ok(warp::reply::json(&grouped).into_response())
+        }
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[instrument(skip(storage))]
+async fn integrity_handler(storage: Arc<dyn Storage>) -> Result<Response, Infallible> {
+    info!("Checking stored entries.");
+    match storage.integrity_report().await {
+        Ok(issues) => Ok(warp::reply::json(&issues).into_response()),
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[instrument(skip(query, storage))]
+async fn summary_handler(
+    begin: String,
+    end: String,
+    query: SummaryQuery,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
+    info!("Summarizing projects from {} to {}.", begin, end);
+    let (begin, end) = match (parse_date("begin", &begin), parse_date("end", &end)) {
+        (Ok(begin), Ok(end)) => (begin, end),
+        (Err(e), _) | (_, Err(e)) => return Ok(error_reply(&e)),
+    };
+
+    let totals = match storage
+        .project_minutes_between(begin, end, query.include_archive)
+        .await
+    {
+        Ok(totals) => totals,
+        Err(e) => return Ok(error_reply(&e)),
+    };
+    if query.group == SummaryGroup::Project {
+        return Ok(warp::reply::json(&totals).into_response());
+    }
+
+    match storage.read_all_projects().await {
+        Ok(projects) => {
+            let totals = report::client_minutes(&totals, &projects);
+            Ok(warp::reply::json(&totals).into_response())
+        }
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[instrument(skip(storage))]
+async fn clients_handler(storage: Arc<dyn Storage>) -> Result<Response, Infallible> {
+    info!("Reading clients.");
+    match storage.read_clients().await {
+        Ok(clients) => Ok(warp::reply::json(&clients).into_response()),
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+fn parse_date(field: &str, date: &str) -> Result<NaiveDate, TimecardError> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| TimecardError::invalid(field, format!("'{}' is not a YYYY-MM-DD date", date)))
+}
+
+#[instrument(skip(storage))]
+async fn export_handler(storage: Arc<dyn Storage>) -> Result<Response, Infallible> {
+    info!("Exporting all projects and entries.");
+    match storage.export().await {
+        Ok(export) => Ok(warp::reply::json(&export).into_response()),
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[instrument(skip(query, export, storage), fields(merge = query.merge))]
+async fn import_handler(
+    query: ImportQuery,
+    export: Export,
+    storage: Arc<dyn Storage>,
+) -> Result<Response, Infallible> {
+    info!(
+        "Importing {} projects and {} entries.",
+        export.projects.len(),
+        export.entries.len()
+    );
+    match storage.import(&export, query.merge).await {
+        Ok(summary) => Ok(warp::reply::json(&summary).into_response()),
+        Err(e) => Ok(error_reply(&e)),
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: String,
+    #[serde(skip_serializing_if = "<[FieldError]>::is_empty")]
+    fields: &'a [FieldError],
+    /// The stored entry a new one duplicates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    existing_id: Option<i32>,
+    /// Logged with the error, to find it in the server's logs.
+    correlation_id: String,
+}
+
+/// Maps a library error to the status and JSON body every handler replies with.
+/// The error is logged within the handler's span under a correlation id that's
+/// sent back too. Details of internal failures are only logged.
+pub fn error_reply(err: &TimecardError) -> Response {
+    let (status, fields): (_, &[FieldError]) = match err {
+        TimecardError::NotFound(_) => (http::StatusCode::NOT_FOUND, &[]),
+        TimecardError::Validation(fields) => (http::StatusCode::BAD_REQUEST, fields),
+        TimecardError::Conflict(_) | TimecardError::Duplicate(_) => {
+            (http::StatusCode::CONFLICT, &[])
+        }
+        _ => return internal_error(err),
+    };
+
+    let existing_id = match err {
+        TimecardError::Duplicate(id) => Some(*id),
+        _ => None,
+    };
+    rejected(status, err.to_string(), fields, existing_id)
+}
+
+/// Replies to a request no endpoint accepted, such as one for an unknown path
+/// or with a malformed body, in the same shape as `error_reply`.
+async fn rejection_reply(rejection: warp::Rejection) -> Result<Response, Infallible> {
+    use warp::reject::{
+        InvalidHeader, InvalidQuery, LengthRequired, MethodNotAllowed, MissingHeader,
+        PayloadTooLarge, UnsupportedMediaType,
+    };
+
+    let (status, error) = if rejection.is_not_found() {
+        (
+            http::StatusCode::NOT_FOUND,
+            "Endpoint not found.".to_string(),
+        )
+    } else if let Some(e) = rejection.find::<warp::body::BodyDeserializeError>() {
+        (http::StatusCode::BAD_REQUEST, e.to_string())
+    } else if let Some(e) = rejection.find::<InvalidQuery>() {
+        (http::StatusCode::BAD_REQUEST, e.to_string())
+    } else if let Some(e) = rejection.find::<InvalidHeader>() {
+        (http::StatusCode::BAD_REQUEST, e.to_string())
+    } else if let Some(e) = rejection.find::<MissingHeader>() {
+        (http::StatusCode::BAD_REQUEST, e.to_string())
+    } else if let Some(e) = rejection.find::<LengthRequired>() {
+        (http::StatusCode::LENGTH_REQUIRED, e.to_string())
+    } else if let Some(e) = rejection.find::<PayloadTooLarge>() {
+        (http::StatusCode::PAYLOAD_TOO_LARGE, e.to_string())
+    } else if let Some(e) = rejection.find::<UnsupportedMediaType>() {
+        (http::StatusCode::UNSUPPORTED_MEDIA_TYPE, e.to_string())
+    } else if let Some(e) = rejection.find::<MethodNotAllowed>() {
+        (http::StatusCode::METHOD_NOT_ALLOWED, e.to_string())
+    } else {
+        return Ok(internal_error(&format!("{:?}", rejection)));
+    };
+
+    Ok(rejected(status, error, &[], None))
+}
+
+/// A reply for a request that was refused, logged under a correlation id
+/// that's sent back too.
+fn rejected(
+    status: http::StatusCode,
+    error: String,
+    fields: &[FieldError],
+    existing_id: Option<i32>,
+) -> Response {
+    let correlation_id = Uuid::new_v4().to_string();
+    warn!(%correlation_id, %error, "Request rejected.");
+
+    let body = ErrorBody {
+        error,
+        fields,
+        existing_id,
+        correlation_id,
+    };
+    warp::reply::with_status(warp::reply::json(&body), status).into_response()
+}
+
+/// A 500 reply for `err`, which is logged but not sent to the client.
+fn internal_error(err: &dyn fmt::Display) -> Response {
+    let correlation_id = Uuid::new_v4().to_string();
+    error!(%correlation_id, error = %err, "Request failed.");
+
+    let body = ErrorBody {
+        error: "Internal server error.".to_string(),
+        fields: &[],
+        existing_id: None,
+        correlation_id,
+    };
+    warp::reply::with_status(
+        warp::reply::json(&body),
+        http::StatusCode::INTERNAL_SERVER_ERROR,
+    )
+    .into_response()
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "fake"))]
 mod tests {
     use super::*;
+    use crate::db;
+    use crate::report::GroupedReport;
+    use crate::storage::tests::FailingStorage;
+    use crate::storage::SqliteStorage;
+    #[cfg(feature = "server")]
+    use crate::telemetry::CapturedLogs;
+    use crate::{ImportSummary, UndoRecord, EXPORT_VERSION};
     use bytes::Bytes;
     use fake::{Fake, Faker};
     use serde_json;
+    use sqlx::sqlite::SqlitePool;
+
+    fn storage(pool: &SqlitePool) -> Arc<dyn Storage> {
+        Arc::new(SqliteStorage::new(pool.clone()))
+    }
 
     #[tokio::test]
     async fn test_get_entry() -> Result<()> {
         let pool = db::tests::setup_test_db().await?;
         db::tests::setup_entries_table(&pool).await?;
 
-        let mut exp_entry: db::Entry = Faker.fake();
-        exp_entry.id = Some(1);
-        db::write_entry(&pool, &exp_entry).await?;
+        let new_entry: NewEntry = Faker.fake();
+        let id = db::write_entry(&pool, &new_entry, false).await?;
+        let exp_entry = Entry {
+            id: Some(id),
+            ..new_entry.into()
+        };
 
-        let filter = get_entry(pool);
+        let filter = get_entry(storage(&pool));
 
         let res = warp::test::request()
             .method("GET")
-            .path("/entry/1")
+            .path(&format!("/entry/{}", id))
             .reply(&filter)
             .await;
 
@@ -310,27 +1045,142 @@ mod tests {
         let pool = db::tests::setup_test_db().await?;
         db::tests::setup_entries_table(&pool).await?;
 
-        let mut exp_entry: db::Entry = Faker.fake();
-        exp_entry.id = Some(1);
+        let new_entry: NewEntry = Faker.fake();
 
-        let exp_json = Bytes::from(serde_json::to_string(&exp_entry).unwrap());
+        let filter = post_entry(storage(&pool));
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/entry")
+            .json(&new_entry)
+            .reply(&filter)
+            .await;
+
+        assert_eq!(res.status(), 200);
+
+        let entry = db::read_last_entry(&pool).await?;
+
+        assert_eq!(
+            entry,
+            Entry {
+                id: entry.id,
+                ..new_entry.into()
+            }
+        );
+
+        // The created entry is sent back with its id.
+        let created: Entry = serde_json::from_slice(res.body())?;
+        assert_eq!(created, entry);
+
+        Ok(())
+    }
 
-        // db::write_entry(&pool, &exp_entry).await?;
+    #[tokio::test]
+    async fn test_post_entry_rejects_id() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
 
-        let filter = post_entry(pool.clone());
+        let mut entry: Entry = Faker.fake();
+        entry.id = Some(42);
 
         let res = warp::test::request()
             .method("POST")
             .path("/entry")
-            .body(&exp_json)
-            .reply(&filter)
+            .json(&entry)
+            .reply(&post_entry(storage(&pool)))
+            .await;
+
+        assert_eq!(res.status(), 400);
+        assert!(db::read_all_entries(&pool).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_post_entry_validates_code() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+
+        let mut entry: serde_json::Value = serde_json::to_value(Faker.fake::<NewEntry>())?;
+        entry["code"] = "20 008".into();
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/entry")
+            .json(&entry)
+            .reply(&post_entry(storage(&pool)))
             .await;
 
+        assert_eq!(res.status(), 400);
+        assert!(db::read_all_entries(&pool).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_post_entries() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+
+        let first: NewEntry = Faker.fake();
+        let second = NewEntry {
+            memo: "after the break".to_string(),
+            ..Faker.fake()
+        };
+        let filter = post_entries(storage(&pool));
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/entries")
+            .json(&vec![first.clone(), second.clone()])
+            .reply(&filter)
+            .await;
         assert_eq!(res.status(), 200);
+        let created: Vec<Entry> = serde_json::from_slice(res.body())?;
+        assert_eq!(created.len(), 2);
+        assert_eq!(created[1].memo, "after the break");
 
-        let entry = db::read_entry(&pool, exp_entry.id.unwrap()).await?;
+        // Nothing is stored when any entry is refused.
+        let third: NewEntry = Faker.fake();
+        let res = warp::test::request()
+            .method("POST")
+            .path("/entries")
+            .json(&vec![third, first])
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 409);
+        assert_eq!(db::read_all_entries(&pool).await?.len(), 2);
 
-        assert_eq!(&entry, &exp_entry);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_post_duplicate_entry() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+
+        let new_entry: NewEntry = Faker.fake();
+        let id = db::write_entry(&pool, &new_entry, false).await?;
+        let filter = post_entry(storage(&pool));
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/entry")
+            .json(&new_entry)
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 409);
+        let body: serde_json::Value = serde_json::from_slice(res.body())?;
+        assert_eq!(body["existing_id"], id);
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/entry?allow_duplicate=true")
+            .json(&new_entry)
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(db::read_all_entries(&pool).await?.len(), 2);
 
         Ok(())
     }
@@ -340,18 +1190,19 @@ mod tests {
         let pool = db::tests::setup_test_db().await?;
         db::tests::setup_entries_table(&pool).await?;
 
-        let mut exp_entry: db::Entry = Faker.fake();
-        let id = db::write_entry(&pool, &exp_entry).await?;
+        let new_entry: NewEntry = Faker.fake();
+        let id = db::write_entry(&pool, &new_entry, false).await?;
 
+        let mut exp_entry: Entry = new_entry.into();
         exp_entry.id = Some(id);
         exp_entry.start = String::from("0900");
         exp_entry.stop = String::from("1100");
-        exp_entry.code = String::from("20-008");
+        exp_entry.code = "20-008".parse()?;
         exp_entry.memo = String::from("work, work, work");
 
         let exp_json = Bytes::from(serde_json::to_string(&exp_entry).unwrap());
 
-        let filter = update_entry(pool.clone());
+        let filter = update_entry(storage(&pool));
 
         let res = warp::test::request()
             .method("POST")
@@ -374,24 +1225,830 @@ mod tests {
         let pool = db::tests::setup_test_db().await?;
         db::tests::setup_entries_table(&pool).await?;
 
-        let mut entry: db::Entry = Faker.fake();
-        entry.id = Some(1);
-        db::write_entry(&pool, &entry).await?;
+        let entry: NewEntry = Faker.fake();
+        let id = db::write_entry(&pool, &entry, false).await?;
 
-        let filter = delete_entry(pool.clone());
+        let filter = delete_entry(storage(&pool));
 
         let res = warp::test::request()
             .method("POST")
-            .path("/delete_entry/1")
+            .path(&format!("/delete_entry/{}", id))
             .reply(&filter)
             .await;
 
         assert_eq!(res.status(), 200);
 
         // Entry should not exist.
-        let _ = db::read_entry(&pool, entry.id.unwrap()).await.is_err();
+        assert!(db::read_entry(&pool, id).await.is_err());
 
-        assert_eq!(res.body(), "Entry deleted.");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_last_entries() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+
+        let entry: NewEntry = Faker.fake();
+        let keep_id = db::write_entry(&pool, &entry, false).await?;
+        let delete_id = db::write_entry(&pool, &entry, true).await?;
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/last_entries/1")
+            .reply(&read_last_entries(storage(&pool)))
+            .await;
+
+        assert_eq!(res.status(), 200);
+        let preview: Vec<Entry> = serde_json::from_slice(res.body())?;
+        assert_eq!(preview.len(), 1);
+        assert_eq!(preview[0].id, Some(delete_id));
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/delete_last_entries")
+            .json(&vec![delete_id])
+            .reply(&delete_last_entries(storage(&pool)))
+            .await;
+
+        assert_eq!(res.status(), 200);
+        assert!(db::read_entry(&pool, delete_id).await.is_err());
+        assert!(db::read_entry(&pool, keep_id).await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undo_post_entry() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/undo")
+            .reply(&undo(storage(&pool)))
+            .await;
+        assert_eq!(res.status(), 404);
+
+        let entry: NewEntry = Faker.fake();
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/entry")
+            .json(&entry)
+            .reply(&post_entry(storage(&pool)))
+            .await;
+        assert_eq!(res.status(), 200);
+        let id = db::read_last_entry(&pool).await?.id.unwrap();
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/undo")
+            .reply(&undo(storage(&pool)))
+            .await;
+        assert_eq!(res.status(), 200);
+        assert!(db::read_entry(&pool, id).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_error_reply_status() -> Result<()> {
+        let cases = vec![
+            (TimecardError::NotFound("Entry #1".to_string()), 404),
+            (TimecardError::invalid("count", "must be positive"), 400),
+            (
+                TimecardError::Conflict("UNIQUE constraint failed".to_string()),
+                409,
+            ),
+            (TimecardError::Database(sqlx::Error::PoolClosed), 500),
+            (
+                serde_json::from_str::<UndoRecord>("{").unwrap_err().into(),
+                500,
+            ),
+        ];
+
+        for (err, status) in cases {
+            let res = error_reply(&err);
+            assert_eq!(res.status(), status, "{:?}", err);
+
+            let body = warp::hyper::body::to_bytes(res.into_body()).await?;
+            let body: serde_json::Value = serde_json::from_slice(&body)?;
+            assert!(body["error"].is_string());
+            assert!(body["correlation_id"].is_string());
+        }
+
+        let res = error_reply(&TimecardError::invalid("count", "must be positive"));
+        let body = warp::hyper::body::to_bytes(res.into_body()).await?;
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body["fields"][0]["field"], "count");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn test_error_is_logged() -> Result<()> {
+        // Without an entries table the read fails inside the database.
+        let pool = db::tests::setup_test_db().await?;
+
+        let logs = CapturedLogs::default();
+        let _guard = tracing::subscriber::set_default(logs.subscriber());
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/entry/7")
+            .reply(&get_entry(storage(&pool)))
+            .await;
+        assert_eq!(res.status(), 500);
+
+        let body: serde_json::Value = serde_json::from_slice(res.body())?;
+        assert_eq!(body["error"], "Internal server error.");
+        let correlation_id = body["correlation_id"].as_str().unwrap();
+
+        let logs = logs.contents();
+        let line = logs
+            .lines()
+            .find(|line| line.contains(correlation_id))
+            .expect("no event with the correlation id");
+        assert!(line.contains("ERROR"));
+        assert!(line.contains("read_entry{id=7}"));
+        assert!(line.contains("no such table"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_storage_failures() -> Result<()> {
+        let filter = routes(Arc::new(FailingStorage));
+
+        let requests = [
+            ("GET", "/entry/1"),
+            ("GET", "/last_entry"),
+            ("GET", "/last_entries/3"),
+            ("GET", "/entries/latest?since_id=0"),
+            ("GET", "/entries_between/2021-02-01/2021-02-08"),
+            ("GET", "/weekly_report/0"),
+            ("GET", "/weekly_report/0?include_archive=true"),
+            ("GET", "/integrity"),
+            ("GET", "/summary/2021-02-01/2021-02-28"),
+            ("GET", "/summary/2021-02-01/2021-02-28?group=client"),
+            ("GET", "/clients"),
+            ("GET", "/entries_between/2021-02-01/2021-02-08?client=Acme"),
+            ("GET", "/entries.ics?from=2021-02-01&to=2021-02-07"),
+            ("GET", "/export"),
+            ("POST", "/undo"),
+            ("POST", "/delete_last_entry"),
+            ("GET", "/project/1"),
+            ("GET", "/all_projects"),
+            ("POST", "/delete_project/20-008"),
+        ];
+        for (method, path) in requests.iter() {
+            let res = warp::test::request()
+                .method(method)
+                .path(path)
+                .reply(&filter)
+                .await;
+            assert_eq!(res.status(), 500, "{} {}", method, path);
+
+            let body: serde_json::Value = serde_json::from_slice(res.body())?;
+            assert_eq!(body["error"], "Internal server error.");
+            assert!(body["correlation_id"].is_string());
+        }
+
+        let new_entry: NewEntry = Faker.fake();
+        let res = warp::test::request()
+            .method("POST")
+            .path("/entry")
+            .json(&new_entry)
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 500);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_id() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_projects_table(&pool).await?;
+        let filter = routes(storage(&pool));
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/all_projects")
+            .header("X-Request-Id", "abc-123")
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers()[REQUEST_ID_HEADER], "abc-123");
+
+        // Without one, a new id is made up and still returned.
+        let res = warp::test::request()
+            .method("GET")
+            .path("/all_projects")
+            .reply(&filter)
+            .await;
+        let id = res.headers()[REQUEST_ID_HEADER].to_str()?;
+        assert!(Uuid::parse_str(id).is_ok());
+
+        // Requests no endpoint accepts get it too.
+        for (method, path, status) in &[("GET", "/no_such_endpoint", 404), ("POST", "/entry", 400)]
+        {
+            let res = warp::test::request()
+                .method(method)
+                .path(path)
+                .header("X-Request-Id", "abc-123")
+                .body("{")
+                .reply(&filter)
+                .await;
+            assert_eq!(res.status(), *status, "{} {}", method, path);
+            assert_eq!(res.headers()[REQUEST_ID_HEADER], "abc-123");
+            let body: serde_json::Value = serde_json::from_slice(res.body())?;
+            assert!(body["correlation_id"].is_string());
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn test_request_span() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_projects_table(&pool).await?;
+
+        let logs = CapturedLogs::default();
+        let _guard = tracing::subscriber::set_default(logs.subscriber());
+
+        warp::test::request()
+            .method("GET")
+            .path("/all_projects")
+            .header("X-Request-Id", "abc-123")
+            .reply(&routes(storage(&pool)))
+            .await;
+
+        let logs = logs.contents();
+        let line = logs
+            .lines()
+            .find(|line| line.contains("Reading all projects."))
+            .expect("no event from the handler");
+        assert!(line.contains("request{"));
+        assert!(line.contains("abc-123"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_entry_rfc3339() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+
+        let mut new_entry: NewEntry = Faker.fake();
+        new_entry.start = "2021-02-03 09:00:00".to_string();
+        new_entry.stop = "2021-02-03 10:00:00".to_string();
+        let id = db::write_entry(&pool, &new_entry, false).await?;
+
+        let res = warp::test::request()
+            .method("GET")
+            .path(&format!("/entry/{}?format=rfc3339", id))
+            .reply(&get_entry(storage(&pool)))
+            .await;
+
+        assert_eq!(res.status(), 200);
+        let entry: Entry = serde_json::from_slice(res.body())?;
+        assert_eq!(entry.start, new_entry.start);
+        let body: serde_json::Value = serde_json::from_slice(res.body())?;
+        assert!(body["start"]
+            .as_str()
+            .unwrap()
+            .starts_with("2021-02-03T09:00:00"));
+
+        let res = warp::test::request()
+            .method("GET")
+            .path(&format!("/entry/{}?format=xml", id))
+            .reply(&get_entry(storage(&pool)))
+            .await;
+
+        assert_eq!(res.status(), 400);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_missing_entry() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/entry/1")
+            .reply(&get_entry(storage(&pool)))
+            .await;
+
+        assert_eq!(res.status(), 404);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_weekly_report() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+
+        let today = Local::today().naive_local();
+        let new_entry = NewEntry::builder()
+            .date(today)
+            .start_time(chrono::NaiveTime::from_hms(9, 0, 0))
+            .stop_time(chrono::NaiveTime::from_hms(10, 30, 0))
+            .code("20-008")
+            .memo("work, work, work")
+            .build()?;
+        db::write_entry(&pool, &new_entry, false).await?;
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/weekly_report/0?first_day=mon&memos=true")
+            .reply(&weekly_report(storage(&pool)))
+            .await;
+        assert_eq!(res.status(), 200);
+
+        let report: WeeklyReport = serde_json::from_slice(res.body())?;
+        assert_eq!(report.days[0], Weekday::Mon);
+        assert_eq!(report.total(), 90);
+        assert_eq!(report.rows[0].project, "20-008");
+        assert_eq!(report.rows[0].memos.concat(), vec!["work, work, work"]);
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/weekly_report/2021-W05")
+            .reply(&weekly_report(storage(&pool)))
+            .await;
+        assert_eq!(res.status(), 200);
+        let report: WeeklyReport = serde_json::from_slice(res.body())?;
+        assert_eq!(report.begin, "2021-02-01");
+        assert_eq!(report.days[0], Weekday::Mon);
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/weekly_report/2021-W53")
+            .reply(&weekly_report(storage(&pool)))
+            .await;
+        assert_eq!(res.status(), 400);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_weekly_report_include_archive() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+        db::setup_archive(&pool).await?;
+        for day in &[1, 2] {
+            let entry = NewEntry::builder()
+                .date(NaiveDate::from_ymd(2021, 2, *day))
+                .start_time(chrono::NaiveTime::from_hms(9, 0, 0))
+                .stop_time(chrono::NaiveTime::from_hms(10, 0, 0))
+                .code("20-008")
+                .memo("work, work, work")
+                .build()?;
+            db::write_entry(&pool, &entry, false).await?;
+        }
+        db::archive_entries_before(&pool, NaiveDate::from_ymd(2021, 2, 2), 10).await?;
+
+        let filter = weekly_report(storage(&pool));
+        let res = warp::test::request()
+            .method("GET")
+            .path("/weekly_report/2021-W05")
+            .reply(&filter)
+            .await;
+        let report: WeeklyReport = serde_json::from_slice(res.body())?;
+        assert_eq!(report.total(), 60);
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/weekly_report/2021-W05?include_archive=true")
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 200);
+        let report: WeeklyReport = serde_json::from_slice(res.body())?;
+        assert_eq!(report.total(), 120);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_weekly_report_group_by_client() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+        db::tests::setup_projects_table(&pool).await?;
+        for (day, code) in &[(1, "20-008"), (7, "21-001")] {
+            let entry = NewEntry::builder()
+                .date(NaiveDate::from_ymd(2021, 2, *day))
+                .start_time(chrono::NaiveTime::from_hms(9, 0, 0))
+                .stop_time(chrono::NaiveTime::from_hms(10, 0, 0))
+                .code(*code)
+                .memo("work, work, work")
+                .build()?;
+            db::write_entry(&pool, &entry, false).await?;
+        }
+        let project = Project {
+            id: None,
+            name: "Acme Website".to_string(),
+            code: "20-008".parse()?,
+            client: Some("Acme".to_string()),
+        };
+        db::write_project(&pool, &project).await?;
+
+        let filter = weekly_report(storage(&pool));
+        let res = warp::test::request()
+            .method("GET")
+            .path("/weekly_report/2021-W05?group=client")
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 200);
+        let grouped: GroupedReport = serde_json::from_slice(res.body())?;
+
+        // ISO weeks start on Monday, and so do the grouped columns.
+        assert_eq!(grouped.days[0], Weekday::Mon);
+        let clients: Vec<&str> = grouped.groups.iter().map(|g| g.client.as_str()).collect();
+        assert_eq!(clients, vec!["Acme", report::NO_CLIENT]);
+        assert_eq!(grouped.groups[0].subtotals, [60, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(grouped.groups[1].subtotals, [0, 0, 0, 0, 0, 0, 60]);
+        assert_eq!(grouped.totals, [60, 0, 0, 0, 0, 0, 60]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_integrity() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+
+        let filter = integrity(storage(&pool));
+        let res = warp::test::request()
+            .method("GET")
+            .path("/integrity")
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.body(), "[]");
+
+        sqlx::query(
+            "INSERT INTO entries(start, stop, week_day, code, memo)
+            VALUES('2020-06-17 09:00:00', '2020-06-17 17:00:00', 'blursday', '20-008', '')",
+        )
+        .execute(&pool)
+        .await?;
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/integrity")
+            .reply(&filter)
+            .await;
+        let issues: Vec<db::IntegrityIssue> = serde_json::from_slice(res.body())?;
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "week_day");
+        assert_eq!(issues[0].value, "blursday");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_summary() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+        db::setup_rollups(&pool).await?;
+        for (start, stop) in &[
+            ("2021-02-03 09:00:00", "2021-02-03 10:30:00"),
+            ("2021-03-01 09:00:00", "2021-03-01 10:00:00"),
+        ] {
+            let entry = NewEntry {
+                start: start.to_string(),
+                stop: stop.to_string(),
+                week_day: Weekday::Wed,
+                code: "20-008".parse()?,
+                memo: "work, work, work".to_string(),
+            };
+            db::write_entry(&pool, &entry, false).await?;
+        }
+
+        let filter = summary(storage(&pool));
+        let res = warp::test::request()
+            .method("GET")
+            .path("/summary/2021-02-01/2021-02-28")
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.body(), r#"{"20-008":90}"#);
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/summary/2021-02-03/2021-03-01")
+            .reply(&filter)
+            .await;
+        assert_eq!(res.body(), r#"{"20-008":150}"#);
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/summary/2021-02-30/2021-03-01")
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 400);
+
+        // Archived entries only count when asked for.
+        db::setup_archive(&pool).await?;
+        db::archive_entries_before(&pool, NaiveDate::from_ymd(2021, 3, 1), 10).await?;
+        for (path, body) in &[
+            ("/summary/2021-02-01/2021-02-28", "{}"),
+            (
+                "/summary/2021-02-01/2021-02-28?include_archive=true",
+                r#"{"20-008":90}"#,
+            ),
+            (
+                "/summary/2021-02-03/2021-03-01?include_archive=true",
+                r#"{"20-008":150}"#,
+            ),
+        ] {
+            let res = warp::test::request()
+                .method("GET")
+                .path(path)
+                .reply(&filter)
+                .await;
+            assert_eq!(res.body(), body);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clients() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+        db::tests::setup_projects_table(&pool).await?;
+        db::setup_rollups(&pool).await?;
+        for (code, client) in &[
+            ("20-001", Some("Acme")),
+            ("20-002", Some("Acme")),
+            ("20-003", None),
+        ] {
+            let project = Project {
+                id: None,
+                name: format!("Project {}", code),
+                code: code.parse()?,
+                client: client.map(String::from),
+            };
+            db::write_project(&pool, &project).await?;
+        }
+        for (code, stop) in &[
+            ("20-001", "2021-02-03 10:00:00"),
+            ("20-002", "2021-02-03 09:30:00"),
+            ("20-003", "2021-02-03 09:15:00"),
+        ] {
+            let entry = NewEntry {
+                start: "2021-02-03 09:00:00".to_string(),
+                stop: stop.to_string(),
+                week_day: Weekday::Wed,
+                code: code.parse()?,
+                memo: "work, work, work".to_string(),
+            };
+            db::write_entry(&pool, &entry, false).await?;
+        }
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/clients")
+            .reply(&clients(storage(&pool)))
+            .await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.body(), r#"[{"client":"Acme","projects":2}]"#);
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/summary/2021-02-01/2021-02-28?group=client")
+            .reply(&summary(storage(&pool)))
+            .await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.body(), r#"{"(none)":15,"Acme":90}"#);
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/entries_between/2021-02-03/2021-02-04?client=Acme")
+            .reply(&get_entries_between(storage(&pool)))
+            .await;
+        assert_eq!(res.status(), 200);
+        let entries: Vec<Entry> = serde_json::from_slice(res.body())?;
+        let mut codes: Vec<String> = entries.iter().map(|e| e.code.to_string()).collect();
+        codes.sort();
+        assert_eq!(codes, vec!["20-001", "20-002"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_latest_entries() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+        let filter = read_latest_entries(storage(&pool));
+
+        let latest = |path: String| {
+            let filter = filter.clone();
+            async move {
+                let res = warp::test::request()
+                    .method("GET")
+                    .path(&path)
+                    .reply(&filter)
+                    .await;
+                assert_eq!(res.status(), 200, "{}", path);
+                serde_json::from_slice::<LatestEntries>(res.body()).unwrap()
+            }
+        };
+        let write = |hour: u32| {
+            let pool = pool.clone();
+            async move {
+                let entry = NewEntry {
+                    start: format!("2021-02-03 {:02}:00:00", hour),
+                    stop: format!("2021-02-03 {:02}:30:00", hour),
+                    week_day: Weekday::Wed,
+                    code: "20-008".parse().unwrap(),
+                    memo: "work, work, work".to_string(),
+                };
+                db::write_entry(&pool, &entry, false).await.unwrap()
+            }
+        };
+        let since = |id: i32| format!("/entries/latest?since_id={}", id);
+        let ids = |latest: &LatestEntries| -> Vec<i32> {
+            latest.entries.iter().filter_map(|e| e.id).collect()
+        };
+
+        let polled = latest(since(0)).await;
+        assert!(polled.entries.is_empty());
+        assert_eq!(polled.max_id, Some(0));
+
+        let first = write(9).await;
+        let polled = latest(since(0)).await;
+        assert_eq!(ids(&polled), vec![first]);
+        assert_eq!(polled.max_id, Some(first));
+
+        // Entries written between polls come back in order, and nothing new
+        // keeps the cursor where it was.
+        let second = write(10).await;
+        let third = write(11).await;
+        let polled = latest(since(first)).await;
+        assert_eq!(ids(&polled), vec![second, third]);
+        assert_eq!(polled.max_id, Some(third));
+
+        let polled = latest(since(third)).await;
+        assert!(polled.entries.is_empty());
+        assert_eq!(polled.max_id, Some(third));
+
+        // Deleting the newest entry doesn't free its id for the next one,
+        // which a client already past it would never see.
+        db::delete_entry(&pool, third).await?;
+        let fourth = write(12).await;
+        assert!(fourth > third);
+        let polled = latest(since(third)).await;
+        assert_eq!(ids(&polled), vec![fourth]);
+
+        let recent = latest("/entries/latest?n=2".to_string()).await;
+        assert_eq!(ids(&recent), vec![second, fourth]);
+
+        // Reads are bounded by MAX_SINCE_COUNT, not the delete cap.
+        let recent = latest(format!("/entries/latest?n={}", db::MAX_DELETE_COUNT + 1)).await;
+        assert_eq!(ids(&recent), vec![first, second, fourth]);
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/entries/latest?n=2&since_id=1")
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 400);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_entries_ics() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+        let entry = NewEntry {
+            start: "2021-02-03 09:00:00".to_string(),
+            stop: "2021-02-03 10:30:00".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+        let id = db::write_entry(&pool, &entry, false).await?;
+
+        let filter = entries_ics(storage(&pool));
+        let res = warp::test::request()
+            .method("GET")
+            .path("/entries.ics?from=2021-02-01&to=2021-02-07")
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(
+            res.headers()["content-type"],
+            "text/calendar; charset=utf-8"
+        );
+        let body = String::from_utf8(res.body().to_vec())?;
+        assert!(body.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(body.contains(&format!("UID:entry-{}@timecard\r\n", id)));
+        assert!(body.contains("DTSTART:20210203T090000\r\n"));
+
+        let res = warp::test::request()
+            .method("GET")
+            .path("/entries.ics?from=2021-02-01&to=soon")
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 400);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import() -> Result<()> {
+        let pool = db::tests::setup_test_db().await?;
+        db::tests::setup_entries_table(&pool).await?;
+        db::tests::setup_projects_table(&pool).await?;
+        db::setup_archive(&pool).await?;
+        let filter = import(storage(&pool));
+
+        let mut export = Export {
+            version: EXPORT_VERSION,
+            projects: vec![Faker.fake()],
+            entries: vec![
+                Entry {
+                    id: Some(1),
+                    ..Faker.fake()
+                },
+                Entry {
+                    id: Some(2),
+                    ..Faker.fake()
+                },
+            ],
+            archived: vec![Entry {
+                id: Some(5),
+                ..Faker.fake()
+            }],
+        };
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/import")
+            .json(&export)
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 200);
+        let summary: ImportSummary = serde_json::from_slice(res.body())?;
+        assert_eq!(summary.projects, 1);
+        assert_eq!(summary.entries, 2);
+        assert_eq!(summary.archived, 1);
+        let archived = db::read_all_archived_entries(&pool).await?;
+        assert_eq!(archived, export.archived);
+
+        // Only an empty database can be imported into without merging.
+        let res = warp::test::request()
+            .method("POST")
+            .path("/import")
+            .json(&export)
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 409);
+
+        // Merging skips what's already there.
+        export.entries.push(Faker.fake());
+        export.archived.push(Faker.fake());
+        let res = warp::test::request()
+            .method("POST")
+            .path("/import?merge=true")
+            .json(&export)
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 200);
+        let summary: ImportSummary = serde_json::from_slice(res.body())?;
+        assert_eq!(summary.projects, 0);
+        assert_eq!(summary.entries, 1);
+        assert_eq!(summary.archived, 1);
+        assert_eq!(db::count_entries(&pool).await?, 3);
+
+        // New ids never reuse an archived one.
+        let ids: Vec<i32> = db::read_all_archived_entries(&pool)
+            .await?
+            .iter()
+            .filter_map(|entry| entry.id)
+            .collect();
+        assert!(ids.contains(&5));
+        let new = db::write_entry(&pool, &Faker.fake(), false).await?;
+        assert!(ids.iter().all(|id| new > *id));
+
+        export.version = EXPORT_VERSION + 1;
+        let res = warp::test::request()
+            .method("POST")
+            .path("/import?merge=true")
+            .json(&export)
+            .reply(&filter)
+            .await;
+        assert_eq!(res.status(), 400);
 
         Ok(())
     }
@@ -401,11 +2058,11 @@ mod tests {
         let pool = db::tests::setup_test_db().await?;
         db::tests::setup_projects_table(&pool).await?;
 
-        let mut exp_project: db::Project = Faker.fake();
+        let mut exp_project: Project = Faker.fake();
         exp_project.id = Some(1);
         db::write_project(&pool, &exp_project).await?;
 
-        let filter = get_project(pool);
+        let filter = get_project(storage(&pool));
 
         let res = warp::test::request()
             .method("GET")
@@ -426,12 +2083,12 @@ mod tests {
         let pool = db::tests::setup_test_db().await?;
         db::tests::setup_projects_table(&pool).await?;
 
-        let mut exp_project: db::Project = Faker.fake();
+        let mut exp_project: Project = Faker.fake();
         exp_project.id = Some(1);
 
         let exp_json = Bytes::from(serde_json::to_string(&exp_project).unwrap());
 
-        let filter = post_project(pool.clone());
+        let filter = post_project(storage(&pool));
 
         let res = warp::test::request()
             .method("POST")
@@ -454,16 +2111,16 @@ mod tests {
         let pool = db::tests::setup_test_db().await?;
         db::tests::setup_projects_table(&pool).await?;
 
-        let mut exp_project: db::Project = Faker.fake();
+        let mut exp_project: Project = Faker.fake();
         let id = db::write_project(&pool, &exp_project).await?;
 
         exp_project.id = Some(id);
         exp_project.name = String::from("General Support");
-        exp_project.code = String::from("20-008");
+        exp_project.code = "20-008".parse()?;
 
         let exp_json = Bytes::from(serde_json::to_string(&exp_project).unwrap());
 
-        let filter = update_project(pool.clone());
+        let filter = update_project(storage(&pool));
 
         let res = warp::test::request()
             .method("POST")
@@ -486,12 +2143,12 @@ mod tests {
         let pool = db::tests::setup_test_db().await?;
         db::tests::setup_projects_table(&pool).await?;
 
-        let mut project: db::Project = Faker.fake();
+        let mut project: Project = Faker.fake();
         project.id = Some(1);
         let code = project.code.clone();
         db::write_project(&pool, &project).await?;
 
-        let filter = delete_project(pool.clone());
+        let filter = delete_project(storage(&pool));
 
         let res = warp::test::request()
             .method("POST")
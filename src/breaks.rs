@@ -0,0 +1,241 @@
+// Std
+use std::str::FromStr;
+
+// Crates
+use anyhow::{anyhow, Context, Result};
+use chrono::{Duration, NaiveDateTime};
+
+// Modules
+use crate::time::DATE_FORMAT;
+use crate::NewEntry;
+
+/// How an unlogged break is taken out of an entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakMode {
+    /// Pull the stop time back by the length of the break.
+    Subtract,
+    /// Cut the break out of the middle, leaving two entries.
+    Split,
+}
+
+impl FromStr for BreakMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "subtract" => Ok(BreakMode::Subtract),
+            "split" => Ok(BreakMode::Split),
+            _ => Err(anyhow!(
+                "Invalid break mode '{}': expected 'subtract' or 'split'.",
+                s
+            )),
+        }
+    }
+}
+
+/// Pulls a break out of the entry fields. A break can be appended to the stop
+/// time (`1700-30m`) or given as an extra `break=30` field, but not both.
+/// Returns the bare stop time and the break, if any.
+pub fn extract_break<'a>(
+    stop: &'a str,
+    extra: Option<&str>,
+) -> Result<(&'a str, Option<Duration>)> {
+    let (stop, suffix) = match stop.find('-') {
+        Some(index) => {
+            let minutes = stop[index + 1..]
+                .strip_suffix('m')
+                .context("Break on the stop time must look like '-30m'.")?;
+            (&stop[..index], Some(parse_minutes(minutes)?))
+        }
+        None => (stop, None),
+    };
+
+    let field = match extra {
+        Some(field) => {
+            let minutes = field
+                .trim()
+                .strip_prefix("break=")
+                .context("Extra entry field must look like 'break=30'.")?;
+            let minutes = minutes.strip_suffix('m').unwrap_or(minutes);
+            Some(parse_minutes(minutes)?)
+        }
+        None => None,
+    };
+
+    match (suffix, field) {
+        (Some(_), Some(_)) => Err(anyhow!("Break given twice.")),
+        (brk, None) | (None, brk) => Ok((stop, brk)),
+    }
+}
+
+fn parse_minutes(minutes: &str) -> Result<Duration> {
+    let invalid = || format!("Invalid break length: '{}'", minutes);
+    let minutes: i64 = minutes.parse().with_context(invalid)?;
+    if minutes <= 0 {
+        return Err(anyhow!(invalid()));
+    }
+    // Duration::minutes panics past about 1.5e14 minutes.
+    let millis = minutes.checked_mul(60_000).with_context(invalid)?;
+
+    Ok(Duration::milliseconds(millis))
+}
+
+/// Takes a break out of an entry according to `mode`. Breaks as long as or
+/// longer than the entry itself are rejected.
+pub fn apply_break(entry: NewEntry, brk: Duration, mode: BreakMode) -> Result<Vec<NewEntry>> {
+    let start = NaiveDateTime::parse_from_str(&entry.start, DATE_FORMAT)?;
+    let stop = NaiveDateTime::parse_from_str(&entry.stop, DATE_FORMAT)?;
+    let length = stop - start;
+
+    if brk >= length {
+        return Err(anyhow!(
+            "Break of {} minutes must be shorter than the {} minute entry.",
+            brk.num_minutes(),
+            length.num_minutes()
+        ));
+    }
+
+    match mode {
+        BreakMode::Subtract => {
+            let mut entry = entry;
+            let stop = stop
+                .checked_sub_signed(brk)
+                .context("Break is out of range.")?;
+            entry.stop = stop.format(DATE_FORMAT).to_string();
+
+            Ok(vec![entry])
+        }
+        BreakMode::Split => {
+            // Split on a whole minute, giving any odd minute to the second half.
+            let first_stop = start + Duration::minutes((length - brk).num_minutes() / 2);
+            let second_start = first_stop + brk;
+
+            let mut first = entry.clone();
+            first.stop = first_stop.format(DATE_FORMAT).to_string();
+            let mut second = entry;
+            second.start = second_start.format(DATE_FORMAT).to_string();
+
+            Ok(vec![first, second])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn workday() -> NewEntry {
+        NewEntry {
+            start: "2021-02-03 09:00:00".to_string(),
+            stop: "2021-02-03 17:00:00".to_string(),
+            week_day: crate::Weekday::Wed,
+            code: "20-008".parse().unwrap(),
+            memo: "work, work, work".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_extract_break() -> Result<()> {
+        assert_eq!(extract_break("1700", None)?, ("1700", None));
+        assert_eq!(
+            extract_break("1700-30m", None)?,
+            ("1700", Some(Duration::minutes(30)))
+        );
+        assert_eq!(
+            extract_break("1700", Some("break=45"))?,
+            ("1700", Some(Duration::minutes(45)))
+        );
+
+        assert!(extract_break("1700-30", None).is_err());
+        assert!(extract_break("1700", Some("lunch=30")).is_err());
+        assert!(extract_break("1700-30m", Some("break=30")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subtract_break() -> Result<()> {
+        let entries = apply_break(workday(), Duration::minutes(30), BreakMode::Subtract)?;
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].start, "2021-02-03 09:00:00");
+        assert_eq!(entries[0].stop, "2021-02-03 16:30:00");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_break() -> Result<()> {
+        let entries = apply_break(workday(), Duration::minutes(30), BreakMode::Split)?;
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].start, "2021-02-03 09:00:00");
+        assert_eq!(entries[0].stop, "2021-02-03 12:45:00");
+        assert_eq!(entries[1].start, "2021-02-03 13:15:00");
+        assert_eq!(entries[1].stop, "2021-02-03 17:00:00");
+        assert_eq!(entries[0].code, entries[1].code);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_break_odd_length() -> Result<()> {
+        let mut hour = workday();
+        hour.stop = "2021-02-03 10:00:00".to_string();
+        let entries = apply_break(hour, Duration::minutes(15), BreakMode::Split)?;
+
+        assert_eq!(entries[0].stop, "2021-02-03 09:22:00");
+        assert_eq!(entries[1].start, "2021-02-03 09:37:00");
+        let minutes: i64 = entries
+            .iter()
+            .map(|entry| {
+                let start = NaiveDateTime::parse_from_str(&entry.start, DATE_FORMAT).unwrap();
+                let stop = NaiveDateTime::parse_from_str(&entry.stop, DATE_FORMAT).unwrap();
+                (stop - start).num_minutes()
+            })
+            .sum();
+        assert_eq!(minutes, 45);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_break_longer_than_entry() {
+        for mode in &[BreakMode::Subtract, BreakMode::Split] {
+            assert!(apply_break(workday(), Duration::hours(8), *mode).is_err());
+            assert!(apply_break(workday(), Duration::hours(9), *mode).is_err());
+        }
+    }
+
+    #[test]
+    fn test_huge_break() {
+        // Found by prop_extract_break_never_panics.
+        assert!(extract_break("1700-99999999999999999m", None).is_err());
+        assert!(extract_break("1700", Some("break=9223372036854775807")).is_err());
+    }
+
+    #[test]
+    fn test_break_must_be_positive() {
+        assert!(extract_break("1700", Some("break=-30")).is_err());
+        assert!(extract_break("1700", Some("break=-999999999999")).is_err());
+        assert!(extract_break("1700--30m", None).is_err());
+        assert!(extract_break("1700-0m", None).is_err());
+        assert!(extract_break("1700", Some("break=0")).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_extract_break_never_panics(stop in "\\PC*", extra in proptest::option::of("\\PC*")) {
+            let _ = extract_break(&stop, extra.as_deref());
+        }
+
+        #[test]
+        fn prop_break_on_stop_time(minutes in 1i64..1_000_000) {
+            let stop = format!("1700-{}m", minutes);
+            let (stop, brk) = extract_break(&stop, None).unwrap();
+            prop_assert_eq!(stop, "1700");
+            prop_assert_eq!(brk, Some(Duration::minutes(minutes)));
+        }
+    }
+}
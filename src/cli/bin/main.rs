@@ -2,118 +2,59 @@
 #[macro_use]
 extern crate clap;
 #[macro_use]
-extern crate lazy_static;
-#[macro_use]
 extern crate prettytable;
 #[macro_use]
-extern crate indexmap;
-#[macro_use]
 extern crate anyhow;
 
 // Std
-use std::collections::{HashMap, HashSet};
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, Write};
 use std::str;
+use std::time;
 
 // Crates
 use anyhow::{Context, Result};
-use chrono::offset::TimeZone;
-use chrono::{Datelike, Duration, Local, NaiveDateTime};
-use clap::{App, Arg};
+use chrono::{Duration, Local, NaiveDate, NaiveTime, Weekday};
+use clap::{App, Arg, ArgMatches};
 use dotenv::dotenv;
-use http::StatusCode;
-use indexmap::IndexMap;
 use prettytable::{color, Attr, Cell, Row, Table};
-use reqwest::Client;
+use uuid::Uuid;
 
 // Local
-use timecard::{Entry, Project};
-
-lazy_static! {
-    static ref WEEKDAYS: HashMap<String, i64> = vec![
-        ("Sun".to_owned(), 0),
-        ("Mon".to_owned(), 1),
-        ("Tue".to_owned(), 2),
-        ("Wed".to_owned(), 3),
-        ("Thu".to_owned(), 4),
-        ("Fri".to_owned(), 5),
-        ("Sat".to_owned(), 6),
-    ]
-    .into_iter()
-    .collect();
-}
-
-static DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+use timecard::breaks::{self, BreakMode};
+use timecard::client::TimecardClient;
+use timecard::config::{Config, Template};
+use timecard::db::{UndoRecord, MAX_DELETE_COUNT};
+use timecard::error::TimecardError;
+use timecard::locale::Locale;
+use timecard::remind::{self, DEFAULT_REMIND_AFTER};
+use timecard::report::{self, DurationFormat, GroupedReport, ReportOptions, WeeklyReport};
+use timecard::spec::CliEntrySpec;
+use timecard::{Entry, HumanDuration, NewEntry, Project, ProjectCode, Week};
+
 const MAX_WIDTH: usize = 20;
 
-struct HourRowData {
-    project: String,
-    hours: IndexMap<String, f64>,
-}
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
 
-struct MemoRowData {
-    project: String,
-    memos: IndexMap<String, String>,
-}
+/// Exit code for errors reported by the server or in the command itself.
+const EXIT_FAILURE: i32 = 1;
+/// Exit code when the server couldn't be reached or its reply couldn't be read.
+const EXIT_UNAVAILABLE: i32 = 2;
+/// Exit code from `remind` when nothing was logged today and a reminder was shown.
+const EXIT_REMINDED: i32 = 3;
 
-impl HourRowData {
-    fn new() -> Self {
-        HourRowData {
-            project: String::new(),
-            hours: indexmap! {
-                "Sun".to_string() => 0.0,
-                "Mon".to_string() => 0.0,
-                "Tue".to_string() => 0.0,
-                "Wed".to_string() => 0.0,
-                "Thu".to_string() => 0.0,
-                "Fri".to_string() => 0.0,
-                "Sat".to_string() => 0.0,
-            },
-        }
-    }
+/// How often `remind --daemon` checks for today's entries.
+const REMIND_INTERVAL: time::Duration = time::Duration::from_secs(30 * 60);
 
-    fn convert_to_row(&self, text_color: color::Color) -> Row {
-        let mut cells: Vec<Cell> = Vec::new();
-        cells.push(Cell::new(&self.project).with_style(Attr::ForegroundColor(text_color)));
-        for (_, value) in self.hours.iter() {
-            cells.push(Cell::new(&value.to_string()).with_style(Attr::ForegroundColor(text_color)));
-        }
-        Row::new(cells)
-    }
-}
-
-impl MemoRowData {
-    fn new() -> Self {
-        MemoRowData {
-            project: String::new(),
-            memos: indexmap! {
-                "Sun".to_string() => String::from(""),
-                "Mon".to_string() => String::from(""),
-                "Tue".to_string() => String::from(""),
-                "Wed".to_string() => String::from(""),
-                "Thu".to_string() => String::from(""),
-                "Fri".to_string() => String::from(""),
-                "Sat".to_string() => String::from(""),
-            },
-        }
-    }
-
-    fn convert_to_row(&self, text_color: color::Color) -> Row {
-        let mut cells: Vec<Cell> = Vec::new();
-        cells.push(Cell::new(&self.project).with_style(Attr::ForegroundColor(text_color)));
-        for (_, value) in self.memos.iter() {
-            cells.push(Cell::new(&value).with_style(Attr::ForegroundColor(text_color)));
-        }
-        Row::new(cells)
-    }
-}
+const REMINDER: &str = "Nothing logged today. Don't forget to enter your time!";
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
-    let base_url: String = env::var("BASE_URL").context("BASE_URL env var must be set!")?;
-
-    let client = Client::new();
+    timecard::config::load_into_env()?;
 
     let matches = App::new("timecard")
         .version(crate_version!())
@@ -123,26 +64,47 @@ async fn main() -> Result<()> {
             Arg::with_name("entry")
                 .short('e')
                 .long("entry")
-                .value_names(&["start", "stop", "code", "memo"])
+                .value_name("start|stop|code|memo[|break=minutes]")
                 .about("Add a new time entry.")
                 .takes_value(true)
+                .min_values(4)
+                .max_values(5)
                 .value_delimiter("|"),
         )
         .arg(
             Arg::with_name("backdate")
                 .short('b')
                 .long("backdate")
-                .value_names(&["backdate", "start", "stop", "code", "memo"])
+                .value_name("backdate|start|stop|code|memo[|break=minutes]")
                 .about("Add a backdated entry.")
                 .takes_value(true)
+                .min_values(5)
+                .max_values(6)
                 .value_delimiter("|"),
         )
+        .arg(
+            Arg::with_name("use_template")
+                .short('t')
+                .long("template")
+                .value_name("name [field=value]")
+                .about("Add an entry from a template, overriding any fields given.")
+                .takes_value(true)
+                .min_values(1),
+        )
+        .arg(
+            Arg::with_name("allow_duplicate")
+                .long("allow-duplicate")
+                .about(
+                    "Use with '-e', '-b' or '-t'. Logs an entry even if an identical one exists.",
+                ),
+        )
         .arg(
             Arg::with_name("week")
                 .short('w')
                 .long("week")
+                .value_name("week")
                 .takes_value(true)
-                .about("Print weekly report."),
+                .about("Print weekly report, e.g. '-w 0', '-w 2021-02-03' or '-w 2021-W05'."),
         )
         .arg(
             Arg::with_name("with_memos")
@@ -150,6 +112,32 @@ async fn main() -> Result<()> {
                 .long("with-memos")
                 .about("Use with '-w'. Adds memos to weekly report."),
         )
+        .arg(
+            Arg::with_name("export")
+                .long("export")
+                .value_name("file.ics")
+                .takes_value(true)
+                .about("Use with '-w'. Writes the week's entries to an iCalendar file."),
+        )
+        .arg(
+            Arg::with_name("group_by")
+                .long("group-by")
+                .takes_value(true)
+                .possible_values(&["client"])
+                .about("Use with '-w' or '--summary'. Groups report rows by client."),
+        )
+        .arg(
+            Arg::with_name("summary")
+                .long("summary")
+                .value_names(&["start", "stop"])
+                .about("Print time per project from one YYYY-MM-DD date through another."),
+        )
+        .arg(
+            Arg::with_name("compare")
+                .long("compare")
+                .value_names(&["weekA", "weekB"])
+                .about("Compare project hours between two weeks."),
+        )
         .arg(
             Arg::with_name("last_entry")
                 .long("last")
@@ -159,7 +147,10 @@ async fn main() -> Result<()> {
             Arg::with_name("delete_last_entry")
                 .short('d')
                 .long("delete")
-                .about("Delete the most recent entry."),
+                .value_name("count")
+                .takes_value(true)
+                .min_values(0)
+                .about("Delete the most recent entry, or the most recent <count> entries."),
         )
         .arg(
             Arg::with_name("add_project")
@@ -168,6 +159,12 @@ async fn main() -> Result<()> {
                 .value_names(&["name", "code"])
                 .about("Add a new project to the reference table."),
         )
+        .arg(
+            Arg::with_name("client")
+                .long("client")
+                .takes_value(true)
+                .about("Use with '-a'. Sets the client the new project belongs to."),
+        )
         .arg(
             Arg::with_name("list_projects")
                 .short('p')
@@ -181,63 +178,204 @@ async fn main() -> Result<()> {
                 .value_name("code")
                 .about("Delete a project from the reference table."),
         )
+        .subcommand(App::new("undo").about("Undo the most recent change to entries."))
+        .subcommand(App::new("init").about("Set up timecard for the first time."))
+        .subcommand(
+            App::new("remind")
+                .about("Remind you to log time when nothing has been entered today.")
+                .arg(
+                    Arg::with_name("after")
+                        .long("after")
+                        .value_name("HH:MM")
+                        .default_value(DEFAULT_REMIND_AFTER)
+                        .about("Only remind after this time of day."),
+                )
+                .arg(
+                    Arg::with_name("daemon")
+                        .long("daemon")
+                        .about("Keep checking every half hour instead of exiting."),
+                ),
+        )
+        .subcommand(
+            App::new("template")
+                .about("Manage entry templates.")
+                .subcommand(App::new("list").about("List all templates."))
+                .subcommand(
+                    App::new("add")
+                        .about("Add or replace a template.")
+                        .arg(Arg::with_name("name").required(true))
+                        .arg(
+                            Arg::with_name("fields")
+                                .value_name("start|stop|code|memo")
+                                .required(true)
+                                .min_values(4)
+                                .max_values(4)
+                                .value_delimiter("|"),
+                        ),
+                )
+                .subcommand(
+                    App::new("remove")
+                        .about("Remove a template.")
+                        .arg(Arg::with_name("name").required(true)),
+                ),
+        )
         .get_matches();
 
-    if let Some(values) = matches.values_of("entry") {
-        match process_new_entry(&base_url, client, values.collect()).await {
-            Ok(_) => println!("Entry submitted."),
-            // TODO: Log error
-            Err(e) => eprintln!("Error writing entry: {}", e),
+    if matches.subcommand_matches("init").is_some() {
+        let stdin = io::stdin();
+        timecard::init::run(
+            &mut stdin.lock(),
+            &mut io::stdout(),
+            &Config::path()?,
+            &Config::data_dir()?,
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // Templates live in the config file, so there's no request id to print.
+    if let Some(matches) = matches.subcommand_matches("template") {
+        match manage_templates(matches) {
+            Ok(output) => output.print(),
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                std::process::exit(EXIT_FAILURE);
+            }
         }
-        std::process::exit(1);
+        return Ok(());
     }
 
-    if let Some(values) = matches.values_of("backdate") {
-        match backdated_entry(&base_url, client, values.collect()).await {
-            Ok(_) => println!("Entry submitted."),
-            // TODO: Log error
-            Err(_e) => println!("Error writing entry."),
+    let base_url = env::var("BASE_URL")
+        .context("BASE_URL env var must be set! Run 'timecard init' to create a config.")?;
+    let mut client = TimecardClient::new(base_url);
+    if let Ok(token) = env::var("TIMECARD_TOKEN") {
+        client = client.with_token(token);
+    }
+    // One id per command, sent with every request and printed with any error
+    // so a failure can be found in the server's logs.
+    let request_id = Uuid::new_v4().to_string();
+    let client = client
+        .with_request_id(request_id.as_str())
+        .with_timeout(timeout()?);
+
+    let duration_format = duration_format()?;
+    let locale = locale()?;
+    let first_day = first_day(locale)?;
+
+    if matches.subcommand_matches("undo").is_some() {
+        finish(undo_last_action(&client).await, "Error", &request_id);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("remind") {
+        let after = NaiveTime::parse_from_str(matches.value_of("after").unwrap(), "%H:%M")
+            .context("--after must be a time of day, e.g. 17:00.")?;
+
+        if matches.is_present("daemon") {
+            loop {
+                if let Err(e) = check_reminder(&client, after).await {
+                    print_error("Error", e, &request_id);
+                }
+                tokio::time::delay_for(REMIND_INTERVAL).await;
+            }
+        }
+
+        match check_reminder(&client, after).await {
+            Ok(true) => std::process::exit(EXIT_REMINDED),
+            Ok(false) => std::process::exit(0),
+            Err(e) => {
+                let code = exit_code(&e);
+                print_error("Error", e, &request_id);
+                std::process::exit(code)
+            }
         }
-        std::process::exit(1);
+    }
+
+    let allow_duplicate = matches.is_present("allow_duplicate");
+
+    if let Some(values) = matches.values_of("entry") {
+        let result = process_new_entry(&client, values.collect(), allow_duplicate).await;
+        finish(result, "Error writing entry", &request_id);
+    }
+
+    if let Some(values) = matches.values_of("use_template") {
+        let result = template_entry(&client, values.collect(), allow_duplicate).await;
+        finish(result, "Error writing entry", &request_id);
+    }
+
+    if let Some(values) = matches.values_of("backdate") {
+        let result = backdated_entry(&client, values.collect(), allow_duplicate).await;
+        finish(result, "Error writing entry", &request_id);
     }
 
     if let Some(value) = matches.value_of("week") {
-        let mut memos = false;
-        let num = match value.parse::<i64>() {
-            Ok(n) => n,
-            // TODO: Log error
-            Err(_e) => {
-                eprintln!("Error: week value must be an integer.");
+        let week = match week(value, first_day) {
+            Ok(week) => week,
+            Err(e) => {
+                eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
         };
+        let memos = matches.is_present("with_memos");
+
+        if let Some(path) = matches.value_of("export") {
+            let result = export_calendar(&client, &week, path).await;
+            finish(result, "Error", &request_id);
+        }
 
-        if matches.is_present("with_memos") {
-            memos = true;
+        if matches.value_of("group_by") == Some("client") {
+            let result = create_grouped_report(&client, &week, duration_format, locale).await;
+            finish(result, "Error", &request_id);
         }
 
-        create_weekly_report(&base_url, client, num, memos).await?;
-        std::process::exit(1);
+        let result = create_weekly_report(&client, &week, memos, duration_format, locale).await;
+        finish(result, "Error", &request_id);
     }
 
-    if matches.is_present("last_entry") {
-        match display_last_entry(&base_url, client).await {
-            Ok(table) => table.printstd(),
-            Err(e) => {
-                eprintln!("Error: {:?}", e);
+    if let Some(values) = matches.values_of("summary") {
+        let dates: Vec<&str> = values.collect();
+        let by_client = matches.value_of("group_by") == Some("client");
+        let result = create_summary(&client, dates[0], dates[1], by_client, duration_format).await;
+        finish(result, "Error", &request_id);
+    }
+
+    if let Some(values) = matches.values_of("compare") {
+        let weeks: Vec<&str> = values.collect();
+        let (first, second) = match (week(weeks[0], first_day), week(weeks[1], first_day)) {
+            (Ok(first), Ok(second)) => (first, second),
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
         };
-        std::process::exit(1);
+
+        let result = compare_weeks(&client, &first, &second, duration_format, locale).await;
+        finish(result, "Error", &request_id);
+    }
+
+    if matches.is_present("last_entry") {
+        finish(display_last_entry(&client).await, "Error", &request_id);
     }
 
     if matches.is_present("delete_last_entry") {
-        let url = format!("{}/delete_last_entry", &base_url);
-        let res = client.post(&url).send().await?;
+        let count = match matches.value_of("delete_last_entry") {
+            Some(value) => match value.parse::<i32>() {
+                Ok(n) if (1..=MAX_DELETE_COUNT).contains(&n) => n,
+                _ => {
+                    eprintln!(
+                        "Error: count must be an integer between 1 and {}.",
+                        MAX_DELETE_COUNT
+                    );
+                    std::process::exit(1);
+                }
+            },
+            None => 1,
+        };
 
-        match res.status() {
-            StatusCode::OK => println!("Most recent entry deleted."),
-            _ => println!("Error: {:?}", res.status()),
+        match delete_last_entries(&client, count).await {
+            Ok(0) => println!("Nothing deleted."),
+            Ok(1) => println!("Most recent entry deleted."),
+            Ok(n) => println!("{} most recent entries deleted.", n),
+            Err(e) => print_error("Error", e, &request_id),
         }
     }
 
@@ -246,225 +384,900 @@ async fn main() -> Result<()> {
         let new_project = Project {
             id: None,
             name: values[0].to_string(),
-            code: values[1].to_string(),
+            code: values[1].parse()?,
+            client: matches.value_of("client").map(String::from),
         };
 
-        let url = format!("{}/project", &base_url);
-        let res = client.post(&url).json(&new_project).send().await?;
-
-        if res.status().is_success() {
-            println!("Project saved.");
-        } else {
-            println!("Http error: {}", res.status());
+        match client.create_project(&new_project).await {
+            Ok(_) => println!("Project saved."),
+            Err(e) => print_error("Error", e, &request_id),
         }
     }
 
     if matches.is_present("list_projects") {
-        let url = format!("{}/all_projects", &base_url);
-        let projects = client
-            .get(&url)
-            .send()
-            .await?
-            .json::<Vec<Project>>()
-            .await?;
+        let projects = match client.projects().await {
+            Ok(projects) => projects,
+            Err(e) => {
+                print_error("Error", e, &request_id);
+                std::process::exit(1);
+            }
+        };
 
         let mut table = Table::new();
-        table.add_row(row![Fb => "Name", "Code"]);
+        table.add_row(row![Fb => "Name", "Code", "Client"]);
 
         for project in projects {
-            table.add_row(row![project.name, project.code]);
+            let client = project.client.unwrap_or_default();
+            table.add_row(row![project.name, project.code, client]);
         }
         table.printstd();
     }
 
     if let Some(value) = matches.value_of("delete_project") {
-        let code = value.parse::<String>()?;
-
-        let url = format!("{}/delete_project/{}", &base_url, code);
-        let res = client.post(&url).send().await?;
+        let code = value.parse::<ProjectCode>()?;
 
-        if res.status().is_success() {
-            println!("Project deleted.");
-        } else {
-            println!("Http error: {}", res.status());
+        match client.delete_project(&code).await {
+            Ok(_) => println!("Project deleted."),
+            Err(e) => print_error("Error", e, &request_id),
         }
     }
 
     Ok(())
 }
 
-async fn process_new_entry(base_url: &str, client: Client, values: Vec<&str>) -> Result<()> {
-    let (start_hour, start_minute) = parse_entry_time(values[0].to_owned())?;
-    let (stop_hour, stop_minute) = parse_entry_time(values[1].to_owned())?;
+/// What a command shows when it succeeds.
+#[derive(Debug)]
+enum Output {
+    Message(String),
+    /// A table, under a title if it has one.
+    Table(Option<String>, Table),
+}
 
-    let date = Local::now();
+impl Output {
+    fn print(&self) {
+        match self {
+            Output::Message(message) => println!("{}", message),
+            Output::Table(title, table) => {
+                if let Some(title) = title {
+                    println!("{}", title);
+                }
+                table.printstd();
+            }
+        }
+    }
+}
 
-    let start = entry_time_to_full_date(date, start_hour, start_minute);
-    let stop = entry_time_to_full_date(date, stop_hour, stop_minute);
-    let week_day: String = Local::today().weekday().to_string();
-    let code = values[2].to_owned();
-    let memo = values[3].to_owned();
+/// Prints a command's output, or its error with the request id, and exits
+/// with the matching code.
+fn finish(result: Result<Output>, context: &str, request_id: &str) -> ! {
+    match result {
+        Ok(output) => {
+            output.print();
+            std::process::exit(0)
+        }
+        Err(e) => {
+            let code = exit_code(&e);
+            print_error(context, e, request_id);
+            std::process::exit(code)
+        }
+    }
+}
 
-    let new_entry = Entry {
-        id: None,
-        start,
-        stop,
-        week_day,
-        code,
-        memo,
-    };
+fn exit_code(err: &anyhow::Error) -> i32 {
+    match err.downcast_ref::<TimecardError>() {
+        Some(TimecardError::Request(_))
+        | Some(TimecardError::Timeout)
+        | Some(TimecardError::MalformedReply(_)) => EXIT_UNAVAILABLE,
+        _ => EXIT_FAILURE,
+    }
+}
 
-    let url = format!("{}/entry", base_url);
-    let res = client.post(&url).json(&new_entry).send().await?;
+/// Prints an error from a command that talked to the server.
+fn print_error(context: &str, err: impl Display, request_id: &str) {
+    eprintln!("{}", error_message(context, err, request_id));
+}
 
-    match res.status() {
-        StatusCode::OK => Ok(()),
-        _ => Err(anyhow!("Status code: {}", res.status())),
-    }
+fn error_message(context: &str, err: impl Display, request_id: &str) -> String {
+    format!("{}: {:#} (request id {})", context, err, request_id)
 }
 
-async fn backdated_entry(base_url: &str, client: Client, values: Vec<&str>) -> Result<()> {
-    let date = match values[0] {
-        "today" => Local::today(),
-        "yesterday" => Local::today() - Duration::days(1),
-        "tomorrow" => Local::today() + Duration::days(1),
-        _ => {
-            let date_values: Vec<&str> = values[0].split('-').collect();
-            let year: i32 = date_values[0].parse()?;
-            let month: u32 = date_values[1].parse()?;
-            let day: u32 = date_values[2].parse()?;
+async fn process_new_entry(
+    client: &TimecardClient,
+    values: Vec<&str>,
+    allow_duplicate: bool,
+) -> Result<Output> {
+    let spec = CliEntrySpec::from_fields(&values)?;
+    submit_spec(client, spec, allow_duplicate).await
+}
 
-            Local.ymd(year, month, day)
-        }
-    };
+async fn template_entry(
+    client: &TimecardClient,
+    values: Vec<&str>,
+    allow_duplicate: bool,
+) -> Result<Output> {
+    let config = Config::load(&Config::path()?)?.unwrap_or_default();
+    let fields = config.template(values[0])?.expand(&values[1..])?;
+    let fields: Vec<&str> = fields.iter().map(|f| f.as_str()).collect();
+
+    let spec = CliEntrySpec::from_fields(&fields)?;
+    submit_spec(client, spec, allow_duplicate).await
+}
 
-    let (start_hour, start_minute) = parse_entry_time(values[1].to_owned())?;
-    let (stop_hour, stop_minute) = parse_entry_time(values[2].to_owned())?;
+async fn backdated_entry(
+    client: &TimecardClient,
+    values: Vec<&str>,
+    allow_duplicate: bool,
+) -> Result<Output> {
+    let spec = CliEntrySpec::from_fields(&values)?;
+    if spec.date.is_none() {
+        return Err(anyhow!(
+            "Backdated entries start with a date, e.g. yesterday|0900|1700|20-008|memo."
+        ));
+    }
 
-    let start = entry_time_to_full_date(date, start_hour, start_minute);
-    let stop = entry_time_to_full_date(date, stop_hour, stop_minute);
+    submit_spec(client, spec, allow_duplicate).await
+}
 
-    let week_day: String = date.weekday().to_string();
-    let code = values[3].to_owned();
-    let memo = values[4].to_owned();
+async fn submit_spec(
+    client: &TimecardClient,
+    spec: CliEntrySpec,
+    allow_duplicate: bool,
+) -> Result<Output> {
+    let new_entry = spec.to_new_entry(Local::today().naive_local())?;
+    submit_entry(client, new_entry, spec.brk, allow_duplicate).await?;
 
-    let new_entry = Entry {
-        id: None,
-        start,
-        stop,
-        week_day,
-        code,
-        memo,
+    Ok(Output::Message("Entry submitted.".to_string()))
+}
+
+/// How long to wait for the server: `TIMECARD_TIMEOUT` seconds, 10 by default.
+fn timeout() -> Result<time::Duration> {
+    let secs = match env::var("TIMECARD_TIMEOUT") {
+        Ok(secs) => secs
+            .trim()
+            .parse()
+            .context("TIMECARD_TIMEOUT must be a number of seconds.")?,
+        Err(_) => DEFAULT_TIMEOUT_SECS,
     };
 
-    let url = format!("{}/entry", base_url);
-    let res = client.post(&url).json(&new_entry).send().await?;
+    Ok(time::Duration::from_secs(secs))
+}
 
-    match res.status() {
-        StatusCode::OK => Ok(()),
-        _ => Err(anyhow!("Status code: {}", res.status())),
+fn duration_format() -> Result<DurationFormat> {
+    match env::var("TIMECARD_DURATION_FORMAT") {
+        Ok(format) => format.parse(),
+        Err(_) => Ok(DurationFormat::default()),
     }
 }
 
-fn parse_entry_time(time_str: String) -> Result<(u32, u32)> {
-    let time = time_str.parse::<u32>()?;
-    Ok((time / 100, time % 100))
+fn locale() -> Result<Locale> {
+    match env::var("TIMECARD_LOCALE") {
+        Ok(locale) => locale.parse(),
+        Err(_) => Ok(Locale::default()),
+    }
 }
 
-fn entry_time_to_full_date<T: Datelike>(date: T, hour: u32, minute: u32) -> String {
-    let year = date.year();
-    let month = date.month();
-    let day = date.day();
+/// The day weeks start on: `TIMECARD_WEEK_START` if set, otherwise the locale's.
+fn first_day(locale: Locale) -> Result<Weekday> {
+    match env::var("TIMECARD_WEEK_START") {
+        Ok(day) => Ok(day.parse::<timecard::Weekday>()?.into()),
+        Err(_) => Ok(locale.first_weekday()),
+    }
+}
 
-    return format!(
-        "{}-{:02}-{:02} {:02}:{:02}:{:02}",
-        year, month, day, hour, minute, 0
-    );
+fn break_mode() -> Result<BreakMode> {
+    match env::var("TIMECARD_BREAK_MODE") {
+        Ok(mode) => mode.parse(),
+        Err(_) => Ok(BreakMode::Subtract),
+    }
 }
 
-async fn create_weekly_report(
-    base_url: &str,
-    client: Client,
-    num_weeks: i64,
-    with_memos: bool,
+async fn submit_entry(
+    client: &TimecardClient,
+    entry: NewEntry,
+    brk: Option<Duration>,
+    allow_duplicate: bool,
 ) -> Result<()> {
-    let parse_from_str = NaiveDateTime::parse_from_str;
+    let entries = match brk {
+        Some(brk) => breaks::apply_break(entry, brk, break_mode()?)?,
+        None => vec![entry],
+    };
 
-    let day_of_week: String = Local::today().weekday().to_string();
-    let offset = *WEEKDAYS.get(&day_of_week).expect("Day does not exist!") + (7 * num_weeks);
-    let week_beginning = Local::today() - Duration::days(offset);
-    let week_ending = week_beginning + Duration::days(6);
+    // Sent together so a split entry is never left half written.
+    client
+        .create_entries(&entries, allow_duplicate)
+        .await
+        .map_err(|e| match e {
+            TimecardError::Duplicate(id) => anyhow!(
+                "already logged (id {}). Use '--allow-duplicate' to log it again.",
+                id
+            ),
+            e => e.into(),
+        })?;
 
-    let url = format!(
-        "{}/entries_between/{}/{}",
-        base_url, week_beginning, week_ending
-    );
-    let entries = client.get(&url).send().await?.json::<Vec<Entry>>().await?;
+    Ok(())
+}
 
+/// The week given as a number of weeks ago, a date or an ISO week.
+fn week(spec: &str, first_day: Weekday) -> Result<Week> {
+    Ok(Week::resolve(
+        spec,
+        Local::today().naive_local(),
+        first_day,
+    )?)
+}
+
+async fn fetch_week_entries(client: &TimecardClient, week: &Week) -> Result<Vec<Entry>> {
+    Ok(client.entries_between(week.begin(), week.end()).await?)
+}
+
+/// Writes the week's entries to `path` as an iCalendar file.
+async fn export_calendar(client: &TimecardClient, week: &Week, path: &str) -> Result<Output> {
+    let calendar = client.entries_ics(week.begin(), week.end()).await?;
+    fs::write(path, calendar).with_context(|| format!("Couldn't write {}", path))?;
+
+    Ok(Output::Message(format!("Wrote {}.", path)))
+}
+
+async fn create_weekly_report(
+    client: &TimecardClient,
+    week: &Week,
+    with_memos: bool,
+    format: DurationFormat,
+    locale: Locale,
+) -> Result<Output> {
+    let options = ReportOptions { memos: with_memos };
+    // Sent as the first date so the server's idea of today doesn't matter.
+    let weekly = client
+        .weekly_report(&week.begin().to_string(), week.first_day().into(), options)
+        .await?;
+    let week_beginning = NaiveDate::parse_from_str(&weekly.begin, "%Y-%m-%d")?;
+    let table = weekly_table(&weekly, with_memos, format, locale, week.first_day())?;
+    let title = format!("{} ({})", locale.week_title(week_beginning), weekly.label);
+
+    Ok(Output::Table(Some(title), table))
+}
+
+/// Renders a weekly report whose columns start on `first_day`, with a row of
+/// wrapped memos under each project when `with_memos` is set.
+fn weekly_table(
+    weekly: &WeeklyReport,
+    with_memos: bool,
+    format: DurationFormat,
+    locale: Locale,
+    first_day: Weekday,
+) -> Result<Table> {
     let mut table = Table::new();
-    table.add_row(row![Fb => "Project", "Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"]);
-
-    let mut codes: HashSet<String> = HashSet::new();
-    for entry in &entries {
-        codes.insert(entry.code.clone());
-    }
-
-    for (index, code) in codes.iter().enumerate() {
-        let mut hour_data = HourRowData::new();
-        let mut memo_data = MemoRowData::new();
-        hour_data.project = code.clone();
-        memo_data.project = code.clone();
-
-        let project_entries = entries.iter().filter(|entry| &entry.code == code);
-
-        for entry in project_entries {
-            let start: NaiveDateTime =
-                parse_from_str(&entry.start, DATE_FORMAT).expect("Parsing error!");
-            let stop: NaiveDateTime =
-                parse_from_str(&entry.stop, DATE_FORMAT).expect("Parsing error!");
-            let h = hour_data.hours.entry(entry.week_day.clone()).or_insert(0.0);
-            *h += stop.signed_duration_since(start).num_minutes() as f64 / 60.0;
-
-            let current_memo = memo_data
-                .memos
-                .entry(entry.week_day.clone())
-                .or_insert(String::from(""));
-            // Implement max width
-            for chunk in entry.memo.as_bytes().chunks(MAX_WIDTH) {
-                let chunk_str = str::from_utf8(chunk)?;
-                (*current_memo).push_str(chunk_str);
-                if chunk_str.len() >= MAX_WIDTH {
-                    (*current_memo).push_str("\n");
-                }
-            }
-            (*current_memo).push_str("; ");
-            (*current_memo).push_str("\n");
-        }
+    table.add_row(header_row("Project", locale, first_day));
 
+    for (index, row) in weekly.rows.iter().enumerate() {
         let text_color = if index % 2 == 1 {
             color::MAGENTA
         } else {
             color::WHITE
         };
 
-        table.add_row(hour_data.convert_to_row(text_color));
+        let mut cells = vec![Cell::new(&row.project)];
+        for minutes in &row.minutes {
+            cells.push(Cell::new(&format.display(*minutes).to_string()));
+        }
+        table.add_row(colored_row(cells, text_color));
 
         if with_memos {
-            table.add_row(memo_data.convert_to_row(text_color));
+            let mut cells = vec![Cell::new(&row.project)];
+            for memos in &row.memos {
+                cells.push(Cell::new(&memo_cell(memos)?));
+            }
+            table.add_row(colored_row(cells, text_color));
         }
     }
-    table.printstd();
 
-    Ok(())
+    Ok(table)
 }
 
-async fn display_last_entry(base_url: &str, client: Client) -> Result<Table> {
-    let url = format!("{}/last_entry", base_url);
-    let e = client.get(&url).send().await?.json::<Entry>().await?;
+fn colored_row(cells: Vec<Cell>, text_color: color::Color) -> Row {
+    Row::new(
+        cells
+            .into_iter()
+            .map(|cell| cell.with_style(Attr::ForegroundColor(text_color)))
+            .collect(),
+    )
+}
 
+/// Joins a day's memos, wrapping each at `MAX_WIDTH` bytes.
+fn memo_cell(memos: &[String]) -> Result<String> {
+    let mut cell = String::new();
+    for memo in memos {
+        for chunk in memo.as_bytes().chunks(MAX_WIDTH) {
+            let chunk_str = str::from_utf8(chunk)?;
+            cell.push_str(chunk_str);
+            if chunk_str.len() >= MAX_WIDTH {
+                cell.push('\n');
+            }
+        }
+        cell.push_str("; \n");
+    }
+
+    Ok(cell)
+}
+
+async fn create_grouped_report(
+    client: &TimecardClient,
+    week: &Week,
+    format: DurationFormat,
+    locale: Locale,
+) -> Result<Output> {
+    let grouped = client
+        .client_weekly_report(&week.begin().to_string(), week.first_day().into())
+        .await?;
+    let week_beginning = NaiveDate::parse_from_str(&grouped.begin, "%Y-%m-%d")?;
+
+    let table = grouped_table(&grouped, format, locale, week.first_day());
+    let title = format!("{} ({})", locale.week_title(week_beginning), grouped.label);
+
+    Ok(Output::Table(Some(title), table))
+}
+
+/// Renders per-client subtotals with their projects indented underneath.
+fn grouped_table(
+    grouped: &GroupedReport,
+    format: DurationFormat,
+    locale: Locale,
+    first_day: Weekday,
+) -> Table {
     let mut table = Table::new();
-    table.add_row(row![Fb => "Start Time", "Stop Time", "Week Day", "Code", "Memo"]);
-    table.add_row(row![e.start, e.stop, e.week_day, e.code, e.memo]);
+    table.add_row(header_row("Client / Project", locale, first_day));
 
-    Ok(table)
+    for group in &grouped.groups {
+        table.add_row(minutes_row(&group.client, &group.subtotals, true, format));
+        for project in &group.projects {
+            let label = format!("  {}", project.project);
+            table.add_row(minutes_row(&label, &project.minutes, false, format));
+        }
+    }
+    table.add_row(minutes_row("Total", &grouped.totals, true, format));
+
+    table
+}
+
+/// Totals from the server's summary, per project or per client, for the days
+/// from `start` through `stop`.
+async fn create_summary(
+    client: &TimecardClient,
+    start: &str,
+    stop: &str,
+    by_client: bool,
+    format: DurationFormat,
+) -> Result<Output> {
+    let begin = summary_date(start)?;
+    let end = summary_date(stop)?;
+
+    let (label, totals) = if by_client {
+        ("Client", client.client_summary(begin, end).await?)
+    } else {
+        ("Project", client.summary(begin, end).await?)
+    };
+    let table = summary_table(label, &totals, format);
+    let title = format!("{} to {}", begin, end);
+
+    Ok(Output::Table(Some(title), table))
+}
+
+fn summary_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .with_context(|| format!("'{}' is not a YYYY-MM-DD date", date))
+}
+
+/// Renders a row per total, in key order, followed by the overall total.
+fn summary_table(label: &str, totals: &BTreeMap<String, i64>, format: DurationFormat) -> Table {
+    let mut table = Table::new();
+    table.add_row(row![Fb => label, "Time"]);
+
+    for (key, minutes) in totals {
+        table.add_row(row![key, format.display(*minutes)]);
+    }
+    let total: i64 = totals.values().sum();
+    table.add_row(row![b => "Total", format.display(total)]);
+
+    table
+}
+
+fn header_row(label: &str, locale: Locale, first_day: Weekday) -> Row {
+    let mut cells = vec![Cell::new(label).style_spec("Fb")];
+    for day in report::weekday_headers(locale, first_day) {
+        cells.push(Cell::new(day).style_spec("Fb"));
+    }
+
+    Row::new(cells)
+}
+
+/// Builds a table row from minutes already in the report's day order.
+fn minutes_row(label: &str, minutes: &[i64; 7], bold: bool, format: DurationFormat) -> Row {
+    let mut cells = vec![Cell::new(label)];
+    for m in minutes {
+        cells.push(Cell::new(&format.display(*m).to_string()));
+    }
+
+    if bold {
+        cells = cells
+            .into_iter()
+            .map(|cell| cell.with_style(Attr::Bold))
+            .collect();
+    }
+
+    Row::new(cells)
+}
+
+async fn compare_weeks(
+    client: &TimecardClient,
+    first_week: &Week,
+    second_week: &Week,
+    format: DurationFormat,
+    locale: Locale,
+) -> Result<Output> {
+    let first_entries = fetch_week_entries(client, first_week).await?;
+    let second_entries = fetch_week_entries(client, second_week).await?;
+
+    let rows = report::compare_weeks(&first_entries, &second_entries)?;
+
+    let first_label = locale.week_title(first_week.begin());
+    let second_label = locale.week_title(second_week.begin());
+
+    let mut table = Table::new();
+    table.add_row(row![Fb => "Project", first_label, second_label, "Delta"]);
+
+    for row in rows {
+        let delta = row.delta_minutes();
+        let sign = if delta > 0 { "+" } else { "" };
+        let delta_color = if delta > 0 {
+            color::GREEN
+        } else if delta < 0 {
+            color::RED
+        } else {
+            color::WHITE
+        };
+
+        table.add_row(Row::new(vec![
+            Cell::new(&row.project),
+            Cell::new(&format.display(row.first_minutes).to_string()),
+            Cell::new(&format.display(row.second_minutes).to_string()),
+            Cell::new(&format!("{}{}", sign, format.display(delta)))
+                .with_style(Attr::ForegroundColor(delta_color)),
+        ]));
+    }
+
+    Ok(Output::Table(None, table))
+}
+
+async fn display_last_entry(client: &TimecardClient) -> Result<Output> {
+    let e = client.last_entry().await?;
+
+    Ok(Output::Table(None, entries_table(&[e])))
+}
+
+/// Reminds about today's time if it's past `after` and nothing has been
+/// logged. Returns whether a reminder was shown.
+async fn check_reminder(client: &TimecardClient, after: NaiveTime) -> Result<bool> {
+    let now = Local::now().naive_local();
+    let entries = client.entries_between(now.date(), now.date()).await?;
+
+    let needed = remind::needs_reminder(now, after, &entries)?;
+    if needed {
+        notify(REMINDER);
+    }
+
+    Ok(needed)
+}
+
+/// Shows a desktop notification, or rings the terminal bell and prints the
+/// message when there's no notification service.
+#[cfg(feature = "notify")]
+fn notify(message: &str) {
+    let shown = notify_rust::Notification::new()
+        .summary("timecard")
+        .body(message)
+        .show();
+    if shown.is_err() {
+        bell(message);
+    }
+}
+
+/// Built without desktop notifications, so always rings the terminal bell.
+#[cfg(not(feature = "notify"))]
+fn notify(message: &str) {
+    bell(message);
+}
+
+fn bell(message: &str) {
+    eprintln!("\x07{}", message);
+}
+
+/// Previews the most recent entries, asks for confirmation, and deletes exactly
+/// the previewed entries. Returns how many were deleted.
+async fn delete_last_entries(client: &TimecardClient, count: i32) -> Result<usize> {
+    let entries = client.last_entries(count).await?;
+
+    if entries.is_empty() {
+        return Ok(0);
+    }
+
+    entries_table(&entries).printstd();
+    if !confirm(&format!("Delete these {} entries?", entries.len()))? {
+        return Ok(0);
+    }
+
+    let ids: Vec<i32> = entries.iter().filter_map(|e| e.id).collect();
+    client.delete_last_entries(&ids).await?;
+
+    Ok(ids.len())
+}
+
+/// Asks the server to reverse the most recent change and describes what was
+/// undone.
+async fn undo_last_action(client: &TimecardClient) -> Result<Output> {
+    let message = match client.undo().await? {
+        Some(UndoRecord::Created(entries)) => format!("Removed {} added entries.", entries.len()),
+        Some(UndoRecord::Updated(entries)) => format!("Restored {} edited entries.", entries.len()),
+        Some(UndoRecord::Deleted(entries)) => {
+            format!("Restored {} deleted entries.", entries.len())
+        }
+        None => "Nothing to undo.".to_string(),
+    };
+
+    Ok(Output::Message(message))
+}
+
+fn manage_templates(matches: &ArgMatches) -> Result<Output> {
+    let path = Config::path()?;
+    let mut config = Config::load(&path)?.unwrap_or_default();
+
+    if let Some(matches) = matches.subcommand_matches("add") {
+        let name = matches.value_of("name").unwrap();
+        let fields: Vec<&str> = matches.values_of("fields").unwrap().collect();
+        let template = Template {
+            start: fields[0].to_string(),
+            stop: fields[1].to_string(),
+            code: fields[2].to_string(),
+            memo: fields[3].to_string(),
+        };
+        config.templates.insert(name.to_string(), template);
+        config.save(&path)?;
+
+        Ok(Output::Message(format!("Template '{}' saved.", name)))
+    } else if let Some(matches) = matches.subcommand_matches("remove") {
+        let name = matches.value_of("name").unwrap();
+        config.template(name)?;
+        config.templates.remove(name);
+        config.save(&path)?;
+
+        Ok(Output::Message(format!("Template '{}' removed.", name)))
+    } else {
+        let mut table = Table::new();
+        table.add_row(row![Fb => "Name", "Start Time", "Stop Time", "Code", "Memo"]);
+        for (name, t) in &config.templates {
+            table.add_row(row![name, t.start, t.stop, t.code, t.memo]);
+        }
+
+        Ok(Output::Table(None, table))
+    }
+}
+
+fn entries_table(entries: &[Entry]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row![Fb => "Start Time", "Stop Time", "Duration", "Week Day", "Code", "Memo"]);
+    for e in entries {
+        table.add_row(Row::new(vec![
+            Cell::new(&e.start),
+            Cell::new(&e.stop),
+            duration_cell(e),
+            Cell::new(&e.week_day.to_string()),
+            Cell::new(e.code.as_str()),
+            Cell::new(&e.memo),
+        ]));
+    }
+
+    table
+}
+
+/// An entry's length, in red if it stops before it starts.
+fn duration_cell(entry: &Entry) -> Cell {
+    match entry.interval() {
+        Ok((start, stop)) => {
+            let duration = HumanDuration(stop - start);
+            let cell = Cell::new(&duration.to_string());
+            if duration.is_negative() {
+                cell.with_style(Attr::ForegroundColor(color::RED))
+            } else {
+                cell
+            }
+        }
+        Err(_) => Cell::new("?"),
+    }
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use warp::http::StatusCode;
+    use warp::Filter;
+
+    /// A server answering every request with `status` and `body` after
+    /// `delay`, and a client that waits 200ms for it.
+    fn mock_server(status: u16, body: &str, delay: time::Duration) -> TimecardClient {
+        let body = body.to_string();
+        let reply = warp::any().and_then(move || {
+            let body = body.clone();
+            async move {
+                tokio::time::delay_for(delay).await;
+                let status = StatusCode::from_u16(status).unwrap();
+                Ok::<_, Infallible>(warp::reply::with_status(body, status))
+            }
+        });
+        let (addr, server) = warp::serve(reply).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        TimecardClient::new(format!("http://{}", addr))
+            .with_timeout(time::Duration::from_millis(200))
+    }
+
+    #[tokio::test]
+    async fn test_server_error() {
+        let body = r#"{"error": "Internal server error.", "correlation_id": "abc-123"}"#;
+        let client = mock_server(500, body, time::Duration::from_millis(0));
+
+        let err = process_new_entry(&client, vec!["0900", "1000", "20-008", "memo"], false)
+            .await
+            .unwrap_err();
+        assert_eq!(exit_code(&err), EXIT_FAILURE);
+        assert_eq!(
+            error_message("Error writing entry", err, "req-1"),
+            "Error writing entry: Server replied 500: Internal server error. \
+             (correlation id abc-123) (request id req-1)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let client = mock_server(200, "{}", time::Duration::from_secs(5));
+
+        let err = display_last_entry(&client).await.unwrap_err();
+        assert_eq!(exit_code(&err), EXIT_UNAVAILABLE);
+        assert_eq!(
+            error_message("Error", err, "req-1"),
+            "Error: The server didn't reply in time. (request id req-1)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_reply() {
+        let body = r#"{"id": 1, "start": "2021-02-03 09:00:00"}"#;
+        let client = mock_server(200, body, time::Duration::from_millis(0));
+
+        let err = display_last_entry(&client).await.unwrap_err();
+        assert_eq!(exit_code(&err), EXIT_UNAVAILABLE);
+        let message = error_message("Error", err, "req-1");
+        assert!(message.starts_with("Error: The server's reply couldn't be read: missing field"));
+    }
+
+    #[tokio::test]
+    async fn test_weekly_report_output() -> Result<()> {
+        let week = Week::resolve("2021-W05", Local::today().naive_local(), Weekday::Mon)?;
+        let entry = Entry {
+            id: Some(1),
+            start: "2021-02-03 09:00:00".to_string(),
+            stop: "2021-02-03 10:30:00".to_string(),
+            week_day: timecard::Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+        let report = WeeklyReport::build(&[entry], &week, ReportOptions::default())?;
+        let client = mock_server(
+            200,
+            &serde_json::to_string(&report)?,
+            time::Duration::from_millis(0),
+        );
+
+        let output = create_weekly_report(
+            &client,
+            &week,
+            false,
+            DurationFormat::default(),
+            Locale::default(),
+        )
+        .await?;
+        match output {
+            Output::Table(Some(title), table) => {
+                assert!(title.ends_with("(2021-W05)"), "{}", title);
+                assert!(table.to_string().contains("20-008"));
+            }
+            output => panic!("expected a titled table, got {:?}", output),
+        }
+
+        Ok(())
+    }
+
+    /// Three entries in 2021-W05, two of them on 20-008.
+    fn fixture_entries() -> Vec<Entry> {
+        let entry = |id, start: &str, stop: &str, week_day, code: &str, memo: &str| Entry {
+            id: Some(id),
+            start: start.to_string(),
+            stop: stop.to_string(),
+            week_day,
+            code: code.parse().unwrap(),
+            memo: memo.to_string(),
+        };
+
+        vec![
+            entry(
+                1,
+                "2021-02-01 09:00:00",
+                "2021-02-01 10:30:00",
+                timecard::Weekday::Mon,
+                "20-008",
+                "Standup and planning",
+            ),
+            entry(
+                2,
+                "2021-02-02 08:00:00",
+                "2021-02-02 12:00:00",
+                timecard::Weekday::Tue,
+                "21-001",
+                "Site visit",
+            ),
+            entry(
+                3,
+                "2021-02-03 13:00:00",
+                "2021-02-03 15:15:00",
+                timecard::Weekday::Wed,
+                "20-008",
+                "Reviewed the invoicing changes with the client",
+            ),
+        ]
+    }
+
+    fn fixture_week() -> Week {
+        Week::resolve("2021-W05", Local::today().naive_local(), Weekday::Mon).unwrap()
+    }
+
+    #[test]
+    fn test_weekly_table_snapshot() -> Result<()> {
+        let week = fixture_week();
+        let weekly = WeeklyReport::build(&fixture_entries(), &week, ReportOptions::default())?;
+        let table = weekly_table(
+            &weekly,
+            false,
+            DurationFormat::default(),
+            Locale::En,
+            Weekday::Mon,
+        )?;
+
+        insta::assert_snapshot!(table.to_string(), @r###"
+        +---------+------+------+------+------+------+------+------+
+        | Project | Mon  | Tue  | Wed  | Thu  | Fri  | Sat  | Sun  |
+        +---------+------+------+------+------+------+------+------+
+        | 20-008  | 1.50 | 0.00 | 2.25 | 0.00 | 0.00 | 0.00 | 0.00 |
+        +---------+------+------+------+------+------+------+------+
+        | 21-001  | 0.00 | 4.00 | 0.00 | 0.00 | 0.00 | 0.00 | 0.00 |
+        +---------+------+------+------+------+------+------+------+
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_weekly_table_memos_snapshot() -> Result<()> {
+        let week = fixture_week();
+        let options = ReportOptions { memos: true };
+        let weekly = WeeklyReport::build(&fixture_entries(), &week, options)?;
+        let table = weekly_table(
+            &weekly,
+            true,
+            DurationFormat::default(),
+            Locale::En,
+            Weekday::Mon,
+        )?;
+
+        insta::assert_snapshot!(table.to_string(), @r###"
+        +---------+----------------------+--------------+----------------------+------+------+------+------+
+        | Project | Mon                  | Tue          | Wed                  | Thu  | Fri  | Sat  | Sun  |
+        +---------+----------------------+--------------+----------------------+------+------+------+------+
+        | 20-008  | 1.50                 | 0.00         | 2.25                 | 0.00 | 0.00 | 0.00 | 0.00 |
+        +---------+----------------------+--------------+----------------------+------+------+------+------+
+        | 20-008  | Standup and planning |              | Reviewed the invoici |      |      |      |      |
+        |         | ;                    |              | ng changes with the  |      |      |      |      |
+        |         |                      |              | client;              |      |      |      |      |
+        +---------+----------------------+--------------+----------------------+------+------+------+------+
+        | 21-001  | 0.00                 | 4.00         | 0.00                 | 0.00 | 0.00 | 0.00 | 0.00 |
+        +---------+----------------------+--------------+----------------------+------+------+------+------+
+        | 21-001  |                      | Site visit;  |                      |      |      |      |      |
+        +---------+----------------------+--------------+----------------------+------+------+------+------+
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_grouped_table_snapshot() -> Result<()> {
+        let projects = vec![Project {
+            id: Some(1),
+            name: "Invoicing".to_string(),
+            code: "20-008".parse()?,
+            client: Some("Acme".to_string()),
+        }];
+        let weekly = WeeklyReport::build(
+            &fixture_entries(),
+            &fixture_week(),
+            ReportOptions::default(),
+        )?;
+        let grouped = report::group_by_client(weekly, &projects);
+        let table = grouped_table(
+            &grouped,
+            DurationFormat::default(),
+            Locale::En,
+            Weekday::Mon,
+        );
+
+        insta::assert_snapshot!(table.to_string(), @r###"
+        +------------------+------+------+------+------+------+------+------+
+        | Client / Project | Mon  | Tue  | Wed  | Thu  | Fri  | Sat  | Sun  |
+        +------------------+------+------+------+------+------+------+------+
+        | Acme             | 1.50 | 0.00 | 2.25 | 0.00 | 0.00 | 0.00 | 0.00 |
+        +------------------+------+------+------+------+------+------+------+
+        |   20-008         | 1.50 | 0.00 | 2.25 | 0.00 | 0.00 | 0.00 | 0.00 |
+        +------------------+------+------+------+------+------+------+------+
+        | (none)           | 0.00 | 4.00 | 0.00 | 0.00 | 0.00 | 0.00 | 0.00 |
+        +------------------+------+------+------+------+------+------+------+
+        |   21-001         | 0.00 | 4.00 | 0.00 | 0.00 | 0.00 | 0.00 | 0.00 |
+        +------------------+------+------+------+------+------+------+------+
+        | Total            | 1.50 | 4.00 | 2.25 | 0.00 | 0.00 | 0.00 | 0.00 |
+        +------------------+------+------+------+------+------+------+------+
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_summary_table_snapshot() {
+        let totals: BTreeMap<String, i64> =
+            vec![("(none)".to_string(), 15), ("Acme".to_string(), 90)]
+                .into_iter()
+                .collect();
+        let table = summary_table("Client", &totals, DurationFormat::default());
+
+        insta::assert_snapshot!(table.to_string(), @r###"
+        +--------+------+
+        | Client | Time |
+        +--------+------+
+        | (none) | 0.25 |
+        +--------+------+
+        | Acme   | 1.50 |
+        +--------+------+
+        | Total  | 1.75 |
+        +--------+------+
+        "###);
+    }
+
+    #[test]
+    fn test_last_entry_table_snapshot() {
+        let entries = fixture_entries();
+        let table = entries_table(&entries[2..]);
+
+        insta::assert_snapshot!(table.to_string(), @r###"
+        +---------------------+---------------------+----------+----------+--------+------------------------------------------------+
+        | Start Time          | Stop Time           | Duration | Week Day | Code   | Memo                                           |
+        +---------------------+---------------------+----------+----------+--------+------------------------------------------------+
+        | 2021-02-03 13:00:00 | 2021-02-03 15:15:00 | 2h 15m   | Wed      | 20-008 | Reviewed the invoicing changes with the client |
+        +---------------------+---------------------+----------+----------+--------+------------------------------------------------+
+        "###);
+    }
 }
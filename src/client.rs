@@ -0,0 +1,306 @@
+// Std
+use std::collections::BTreeMap;
+use std::time;
+
+// Crates
+use chrono::{Duration, NaiveDate};
+use reqwest::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+// Modules
+use crate::error::{FieldError, Result, TimecardError};
+use crate::report::{ClientProjects, GroupedReport, ReportOptions, WeeklyReport};
+use crate::{
+    Entry, LatestEntries, NewEntry, Project, ProjectCode, UndoRecord, Weekday, REQUEST_ID_HEADER,
+};
+
+/// Typed access to a timecard server. Error responses come back as the
+/// `TimecardError` the server replied with.
+#[derive(Debug, Clone)]
+pub struct TimecardClient {
+    base_url: String,
+    token: Option<String>,
+    request_id: Option<String>,
+    timeout: Option<time::Duration>,
+    http: Client,
+}
+
+impl TimecardClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        TimecardClient {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+            request_id: None,
+            timeout: None,
+            http: Client::new(),
+        }
+    }
+
+    /// Sends `token` as a bearer token with every request, for servers behind
+    /// an authenticating proxy.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Sends `id` as the `X-Request-Id` of every request, so they can be found
+    /// in the server's logs.
+    pub fn with_request_id(mut self, id: impl Into<String>) -> Self {
+        self.request_id = Some(id.into());
+        self
+    }
+
+    /// Gives up on requests the server hasn't answered within `timeout`.
+    pub fn with_timeout(mut self, timeout: time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Writes a new entry and returns it with its id. An entry identical to a
+    /// stored one fails with `Duplicate` unless `allow_duplicate` is set.
+    pub async fn create_entry(&self, entry: &NewEntry, allow_duplicate: bool) -> Result<Entry> {
+        let req = self
+            .post("/entry")
+            .query(&[("allow_duplicate", allow_duplicate)]);
+        json(req.json(entry)).await
+    }
+
+    /// Writes several new entries, all or none, and returns them with their ids.
+    pub async fn create_entries(
+        &self,
+        entries: &[NewEntry],
+        allow_duplicate: bool,
+    ) -> Result<Vec<Entry>> {
+        let req = self
+            .post("/entries")
+            .query(&[("allow_duplicate", allow_duplicate)]);
+        json(req.json(entries)).await
+    }
+
+    pub async fn last_entry(&self) -> Result<Entry> {
+        json(self.get("/last_entry")).await
+    }
+
+    pub async fn last_entries(&self, n: i32) -> Result<Vec<Entry>> {
+        json(self.get(&format!("/last_entries/{}", n))).await
+    }
+
+    /// Entries added after `since_id`, oldest first, with the id to poll from
+    /// next.
+    pub async fn entries_since(&self, since_id: i32) -> Result<LatestEntries> {
+        let req = self.get("/entries/latest").query(&[("since_id", since_id)]);
+        json(req).await
+    }
+
+    /// Entries starting on any day from `begin` to `end`, inclusive.
+    pub async fn entries_between(&self, begin: NaiveDate, end: NaiveDate) -> Result<Vec<Entry>> {
+        // The server compares timestamps as text, and every time on `end`
+        // sorts before the bare date that follows it.
+        let path = format!("/entries_between/{}/{}", begin, end + Duration::days(1));
+        json(self.get(&path)).await
+    }
+
+    /// Entries starting on any day from `begin` to `end`, inclusive, as an
+    /// iCalendar document.
+    pub async fn entries_ics(&self, begin: NaiveDate, end: NaiveDate) -> Result<String> {
+        let query = [("from", begin.to_string()), ("to", end.to_string())];
+        let res = check(self.get("/entries.ics").query(&query)).await?;
+        res.text().await.map_err(request_error)
+    }
+
+    pub async fn delete_last_entries(&self, ids: &[i32]) -> Result<()> {
+        check(self.post("/delete_last_entries").json(ids)).await?;
+        Ok(())
+    }
+
+    /// Reverses the most recent change, or returns `None` if there is nothing
+    /// to undo.
+    pub async fn undo(&self) -> Result<Option<UndoRecord>> {
+        match json(self.post("/undo")).await {
+            Ok(record) => Ok(Some(record)),
+            Err(TimecardError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Report for `week`, given as a number of weeks ago, a date in the week
+    /// or an ISO week such as `2021-W05`.
+    pub async fn weekly_report(
+        &self,
+        week: &str,
+        first_day: Weekday,
+        options: ReportOptions,
+    ) -> Result<WeeklyReport> {
+        let query = [
+            ("first_day", first_day.to_string()),
+            ("memos", options.memos.to_string()),
+        ];
+        let req = self.get(&format!("/weekly_report/{}", week));
+        json(req.query(&query)).await
+    }
+
+    /// Like `weekly_report`, with the rows grouped by client.
+    pub async fn client_weekly_report(
+        &self,
+        week: &str,
+        first_day: Weekday,
+    ) -> Result<GroupedReport> {
+        let query = [
+            ("first_day", first_day.to_string()),
+            ("group", "client".to_string()),
+        ];
+        let req = self.get(&format!("/weekly_report/{}", week));
+        json(req.query(&query)).await
+    }
+
+    /// Minutes per project code for entries starting on any day from `begin`
+    /// to `end`, inclusive.
+    pub async fn summary(&self, begin: NaiveDate, end: NaiveDate) -> Result<BTreeMap<String, i64>> {
+        json(self.get(&format!("/summary/{}/{}", begin, end))).await
+    }
+
+    /// Like `summary`, with the minutes added up per client.
+    pub async fn client_summary(
+        &self,
+        begin: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<BTreeMap<String, i64>> {
+        let req = self.get(&format!("/summary/{}/{}", begin, end));
+        json(req.query(&[("group", "client")])).await
+    }
+
+    pub async fn clients(&self) -> Result<Vec<ClientProjects>> {
+        json(self.get("/clients")).await
+    }
+
+    pub async fn projects(&self) -> Result<Vec<Project>> {
+        json(self.get("/all_projects")).await
+    }
+
+    pub async fn create_project(&self, project: &Project) -> Result<()> {
+        check(self.post("/project").json(project)).await?;
+        Ok(())
+    }
+
+    pub async fn delete_project(&self, code: &ProjectCode) -> Result<()> {
+        let path = format!("/delete_project/{}", code);
+        check(self.post(&path)).await?;
+        Ok(())
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.add_headers(self.http.get(&format!("{}{}", self.base_url, path)))
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        self.add_headers(self.http.post(&format!("{}{}", self.base_url, path)))
+    }
+
+    fn add_headers(&self, mut req: RequestBuilder) -> RequestBuilder {
+        if let Some(token) = &self.token {
+            req = req.bearer_auth(token);
+        }
+        if let Some(id) = &self.request_id {
+            req = req.header(REQUEST_ID_HEADER, id.as_str());
+        }
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+
+        req
+    }
+}
+
+/// Body of the server's error replies, see `api::error_reply`.
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
+    #[serde(default)]
+    fields: Vec<FieldError>,
+    existing_id: Option<i32>,
+    correlation_id: Option<String>,
+}
+
+/// Sends `req`, passing successful responses through and turning error
+/// replies back into the error the server started from.
+async fn check(req: RequestBuilder) -> Result<Response> {
+    let res = req.send().await.map_err(request_error)?;
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+
+    let text = res.text().await.map_err(request_error)?;
+    let body: ErrorBody = match serde_json::from_str(&text) {
+        Ok(body) => body,
+        // Not one of ours, e.g. a rejection from warp itself.
+        Err(_) => {
+            return Err(TimecardError::Server {
+                status: status.as_u16(),
+                message: if text.is_empty() {
+                    status.to_string()
+                } else {
+                    text
+                },
+            })
+        }
+    };
+
+    Err(match status.as_u16() {
+        404 => TimecardError::NotFound(body.error.trim_end_matches(" not found.").to_string()),
+        400 if !body.fields.is_empty() => TimecardError::Validation(body.fields),
+        409 => match body.existing_id {
+            Some(id) => TimecardError::Duplicate(id),
+            None => {
+                TimecardError::Conflict(body.error.trim_start_matches("Conflict: ").to_string())
+            }
+        },
+        status => TimecardError::Server {
+            status,
+            // Kept so the failure can be looked up in the server's logs.
+            message: match body.correlation_id {
+                Some(id) => format!("{} (correlation id {})", body.error, id),
+                None => body.error,
+            },
+        },
+    })
+}
+
+async fn json<T: DeserializeOwned>(req: RequestBuilder) -> Result<T> {
+    let text = check(req).await?.text().await.map_err(request_error)?;
+    serde_json::from_str(&text).map_err(|e| TimecardError::MalformedReply(e.to_string()))
+}
+
+fn request_error(err: reqwest::Error) -> TimecardError {
+    if err.is_timeout() {
+        TimecardError::Timeout
+    } else {
+        TimecardError::Request(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers() -> anyhow::Result<()> {
+        let client = TimecardClient::new("http://127.0.0.1:3333/")
+            .with_token("secret")
+            .with_request_id("abc-123")
+            .with_timeout(time::Duration::from_secs(5));
+        let req = client.get("/last_entry").build()?;
+
+        assert_eq!(req.url().as_str(), "http://127.0.0.1:3333/last_entry");
+        assert_eq!(req.headers()["authorization"], "Bearer secret");
+        assert_eq!(req.headers()[REQUEST_ID_HEADER], "abc-123");
+        assert_eq!(req.timeout(), Some(&time::Duration::from_secs(5)));
+
+        Ok(())
+    }
+}
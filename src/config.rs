@@ -0,0 +1,286 @@
+// Std
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Crates
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Settings read from the config file. Every field is optional, and anything
+/// already set in the environment (or a `.env` file) takes precedence.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    pub base_url: Option<String>,
+    pub database: Option<String>,
+    pub break_mode: Option<String>,
+    pub duration_format: Option<String>,
+    pub locale: Option<String>,
+    pub week_start: Option<String>,
+    pub code_pattern: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub templates: BTreeMap<String, Template>,
+}
+
+/// A named entry that can be added with `--template <name>` instead of typing
+/// out every field.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    pub start: String,
+    pub stop: String,
+    pub code: String,
+    pub memo: String,
+}
+
+impl Template {
+    /// Entry fields in `-e` order (`start|stop|code|memo[|break=minutes]`), with
+    /// any `field=value` overrides taking precedence over the template.
+    pub fn expand(&self, overrides: &[&str]) -> Result<Vec<String>> {
+        let mut fields = vec![
+            self.start.clone(),
+            self.stop.clone(),
+            self.code.clone(),
+            self.memo.clone(),
+        ];
+        let mut brk = None;
+
+        for pair in overrides {
+            let (field, value) = pair
+                .split_once('=')
+                .with_context(|| format!("Override '{}' must look like field=value.", pair))?;
+            match field.trim() {
+                "start" => fields[0] = value.to_string(),
+                "stop" => fields[1] = value.to_string(),
+                "code" => fields[2] = value.to_string(),
+                "memo" => fields[3] = value.to_string(),
+                "break" => brk = Some(format!("break={}", value)),
+                _ => {
+                    return Err(anyhow!(
+                        "Unknown template field '{}': expected start, stop, code, memo or break.",
+                        field
+                    ))
+                }
+            }
+        }
+        fields.extend(brk);
+
+        Ok(fields)
+    }
+}
+
+impl Config {
+    /// Location of the config file: `TIMECARD_CONFIG` if set, otherwise
+    /// `timecard/config.toml` under `XDG_CONFIG_HOME` or `~/.config`.
+    pub fn path() -> Result<PathBuf> {
+        Self::find_path().context("HOME env var must be set to locate the config file!")
+    }
+
+    /// Like `path`, but `None` when it would be under an unset HOME.
+    fn find_path() -> Option<PathBuf> {
+        locate(
+            env::var_os("TIMECARD_CONFIG"),
+            env::var_os("XDG_CONFIG_HOME"),
+            env::var_os("HOME"),
+        )
+    }
+
+    pub fn path_in_home(home: &Path) -> PathBuf {
+        home.join(".config").join("timecard").join("config.toml")
+    }
+
+    /// Default directory for a local database, under `XDG_DATA_HOME` or `~/.local/share`.
+    pub fn data_dir() -> Result<PathBuf> {
+        if let Ok(dir) = env::var("XDG_DATA_HOME") {
+            return Ok(Path::new(&dir).join("timecard"));
+        }
+
+        Ok(Self::data_dir_in_home(&home_dir()?))
+    }
+
+    pub fn data_dir_in_home(home: &Path) -> PathBuf {
+        home.join(".local").join("share").join("timecard")
+    }
+
+    /// Reads the config file at `path`, or returns `None` if there isn't one.
+    pub fn load(path: &Path) -> Result<Option<Config>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let config = toml::from_str(&contents)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+
+        Ok(Some(config))
+    }
+
+    pub fn template(&self, name: &str) -> Result<&Template> {
+        self.templates
+            .get(name)
+            .ok_or_else(|| anyhow!("No template named '{}'.", name))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+
+        Ok(())
+    }
+
+    /// Exports the settings as the environment variables the rest of the app
+    /// reads, leaving any variable that is already set alone.
+    pub fn apply_to_env(&self) {
+        let vars = [
+            ("BASE_URL", &self.base_url),
+            ("TIMECARD_DB", &self.database),
+            ("TIMECARD_BREAK_MODE", &self.break_mode),
+            ("TIMECARD_DURATION_FORMAT", &self.duration_format),
+            ("TIMECARD_LOCALE", &self.locale),
+            ("TIMECARD_WEEK_START", &self.week_start),
+            ("TIMECARD_CODE_PATTERN", &self.code_pattern),
+        ];
+
+        for (key, value) in vars.iter() {
+            if let (Some(value), None) = (value, env::var_os(key)) {
+                env::set_var(key, value);
+            }
+        }
+    }
+}
+
+/// Loads the config file, if any, into the environment. Called by the binaries
+/// after `dotenv()` so `.env` settings win. Without HOME, or a variable naming
+/// the file's location, there is no config file; services often run that way.
+pub fn load_into_env() -> Result<()> {
+    let path = match Config::find_path() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    if let Some(config) = Config::load(&path)? {
+        config.apply_to_env();
+    }
+
+    Ok(())
+}
+
+/// Where the config file is given `TIMECARD_CONFIG`, `XDG_CONFIG_HOME` and
+/// `HOME`, in that order of precedence.
+fn locate(
+    config: Option<OsString>,
+    config_home: Option<OsString>,
+    home: Option<OsString>,
+) -> Option<PathBuf> {
+    if let Some(path) = config {
+        return Some(PathBuf::from(path));
+    }
+    if let Some(dir) = config_home {
+        return Some(Path::new(&dir).join("timecard").join("config.toml"));
+    }
+
+    home.map(|home| Config::path_in_home(Path::new(&home)))
+}
+
+fn home_dir() -> Result<PathBuf> {
+    env::var("HOME")
+        .map(PathBuf::from)
+        .context("HOME env var must be set to locate the config file!")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_round_trip() -> Result<()> {
+        let path = env::temp_dir()
+            .join(format!("timecard_config_{}", std::process::id()))
+            .join("config.toml");
+
+        assert_eq!(Config::load(&path)?, None);
+
+        let config = Config {
+            base_url: Some("http://localhost:3333".to_string()),
+            locale: Some("fr".to_string()),
+            ..Config::default()
+        };
+        config.save(&path)?;
+
+        assert_eq!(Config::load(&path)?, Some(config));
+
+        fs::remove_dir_all(path.parent().unwrap())?;
+
+        Ok(())
+    }
+
+    fn standup() -> Template {
+        Template {
+            start: "0830".to_string(),
+            stop: "0900".to_string(),
+            code: "20-000".to_string(),
+            memo: "daily standup".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_template_expansion() -> Result<()> {
+        let mut config = Config::default();
+        config.templates.insert("standup".to_string(), standup());
+
+        let fields = config.template("standup")?.expand(&[])?;
+        assert_eq!(fields, vec!["0830", "0900", "20-000", "daily standup"]);
+
+        let toml = toml::to_string(&config)?;
+        assert!(toml.contains("[templates.standup]"));
+        assert_eq!(toml::from_str::<Config>(&toml)?, config);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_template_overrides() -> Result<()> {
+        let fields = standup().expand(&["stop=0915", "memo=standup, ran long", "break=5"])?;
+        assert_eq!(
+            fields,
+            vec!["0830", "0915", "20-000", "standup, ran long", "break=5"]
+        );
+
+        // Later overrides win.
+        let fields = standup().expand(&["stop=0915", "stop=0920"])?;
+        assert_eq!(fields[1], "0920");
+
+        assert!(standup().expand(&["lunch=30"]).is_err());
+        assert!(standup().expand(&["stop"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_locate() {
+        let os = |s: &str| Some(OsString::from(s));
+
+        assert_eq!(locate(None, None, None), None);
+        assert_eq!(
+            locate(None, None, os("/home/ann")),
+            Some(PathBuf::from("/home/ann/.config/timecard/config.toml"))
+        );
+        assert_eq!(
+            locate(None, os("/etc/xdg"), None),
+            Some(PathBuf::from("/etc/xdg/timecard/config.toml"))
+        );
+        assert_eq!(
+            locate(os("/srv/timecard.toml"), os("/etc/xdg"), os("/home/ann")),
+            Some(PathBuf::from("/srv/timecard.toml"))
+        );
+    }
+
+    #[test]
+    fn test_unknown_template() {
+        let err = Config::default().template("standup").unwrap_err();
+        assert_eq!(err.to_string(), "No template named 'standup'.");
+    }
+}
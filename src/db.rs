@@ -1,17 +1,200 @@
 // Std
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::env;
+use std::future::Future;
+use std::time::{Duration, Instant};
 
 // Crates
-use anyhow::{Context, Result};
+use anyhow::Context;
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
 use dotenv::dotenv;
-use sqlx::sqlite::{SqlitePool, SqliteQueryAs};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sqlx::pool::PoolConnection;
+use sqlx::sqlite::{SqliteConnection, SqlitePool, SqliteQueryAs};
+use sqlx::Transaction;
+use tracing::{field, info, info_span, warn, Instrument};
+
+use crate::error::{FieldError, Result, TimecardError};
+use crate::report::ClientProjects;
+use crate::time::DATE_FORMAT;
+use crate::{
+    Entry, Export, ImportSummary, NewEntry, Project, ProjectCode, Weekday, EXPORT_VERSION,
+};
+
+pub use crate::UndoRecord;
+
+type Tx = Transaction<PoolConnection<SqliteConnection>>;
+
+/// Upper bound on how many entries can be deleted in one go.
+pub const MAX_DELETE_COUNT: i32 = 50;
+
+/// How long a write waits for another connection's transaction to finish.
+const BUSY_TIMEOUT_MS: u64 = 5000;
+
+/// Most entries returned by one `read_entries_since` or `read_last_n_entries`;
+/// callers catch up on the rest by asking again from the last id they got.
+pub const MAX_SINCE_COUNT: i32 = 500;
+
+/// Entries moved to `entries_archive` per transaction, so archiving a large
+/// backlog doesn't hold the write lock for long.
+pub const ARCHIVE_BATCH_SIZE: i32 = 500;
+
+/// The `user_version` `setup_db` leaves a database at. Each of its steps sets
+/// the version it brings the schema up to.
+pub const SCHEMA_VERSION: i64 = 6;
+
+/// Queries taking at least this many milliseconds are logged as warnings,
+/// unless `TIMECARD_SLOW_QUERY_MS` says otherwise.
+pub const DEFAULT_SLOW_QUERY_MS: u64 = 100;
+
+/// Keep `monthly_totals` in step with every change to `entries`, within the
+/// statement making the change. An entry counts towards the month it starts
+/// in, and one with an unreadable start or stop counts as 0 minutes.
+const ROLLUP_TRIGGERS: [&str; 3] = [
+    "CREATE TRIGGER IF NOT EXISTS monthly_totals_insert AFTER INSERT ON entries
+    BEGIN
+        INSERT OR IGNORE INTO monthly_totals(code, month, minutes)
+        VALUES(NEW.code, substr(NEW.start, 1, 7), 0);
+        UPDATE monthly_totals
+        SET minutes = minutes
+            + IFNULL((strftime('%s', NEW.stop) - strftime('%s', NEW.start)) / 60, 0)
+        WHERE code = NEW.code AND month = substr(NEW.start, 1, 7);
+    END",
+    "CREATE TRIGGER IF NOT EXISTS monthly_totals_delete AFTER DELETE ON entries
+    BEGIN
+        UPDATE monthly_totals
+        SET minutes = minutes
+            - IFNULL((strftime('%s', OLD.stop) - strftime('%s', OLD.start)) / 60, 0)
+        WHERE code = OLD.code AND month = substr(OLD.start, 1, 7);
+    END",
+    "CREATE TRIGGER IF NOT EXISTS monthly_totals_update AFTER UPDATE ON entries
+    BEGIN
+        UPDATE monthly_totals
+        SET minutes = minutes
+            - IFNULL((strftime('%s', OLD.stop) - strftime('%s', OLD.start)) / 60, 0)
+        WHERE code = OLD.code AND month = substr(OLD.start, 1, 7);
+        INSERT OR IGNORE INTO monthly_totals(code, month, minutes)
+        VALUES(NEW.code, substr(NEW.start, 1, 7), 0);
+        UPDATE monthly_totals
+        SET minutes = minutes
+            + IFNULL((strftime('%s', NEW.stop) - strftime('%s', NEW.start)) / 60, 0)
+        WHERE code = NEW.code AND month = substr(NEW.start, 1, 7);
+    END",
+];
+
+lazy_static! {
+    /// How long a query may take before it's logged as slow, read once by the
+    /// first query; 0 logs every query.
+    static ref SLOW_QUERY_THRESHOLD: Duration = Duration::from_millis(
+        env::var("TIMECARD_SLOW_QUERY_MS")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .unwrap_or(DEFAULT_SLOW_QUERY_MS)
+    );
+}
+
+/// Runs `query` in a `db_query` span recording the operation and how long it
+/// took, with a warning if it was slow.
+pub async fn timed<F: Future>(operation: &'static str, query: F) -> F::Output {
+    timed_with(operation, *SLOW_QUERY_THRESHOLD, query).await
+}
+
+/// `timed` with the slow query threshold given rather than configured.
+async fn timed_with<F: Future>(
+    operation: &'static str,
+    threshold: Duration,
+    query: F,
+) -> F::Output {
+    let span = info_span!("db_query", operation, elapsed_ms = field::Empty);
+    let start = Instant::now();
+    let output = query.instrument(span.clone()).await;
+
+    let elapsed = start.elapsed();
+    let elapsed_ms = elapsed.as_millis() as u64;
+    span.record("elapsed_ms", &elapsed_ms);
+    if elapsed >= threshold {
+        warn!(parent: &span, operation, elapsed_ms, "Slow database query.");
+    }
+
+    output
+}
+
+/// Row shape of the `entries` table. `query_as!` maps columns by their
+/// database type, so rows are read into this and then converted.
+struct EntryRow {
+    id: Option<i32>,
+    start: String,
+    stop: String,
+    week_day: String,
+    code: String,
+    memo: String,
+}
+
+/// Legacy rows may hold any spelling of the week day, or none at all. Known
+/// spellings are normalized; otherwise the day is taken from `start`, and
+/// only if that fails too is the row an error. `integrity_report` lists the
+/// rows that needed help.
+impl TryFrom<EntryRow> for Entry {
+    type Error = TimecardError;
+
+    fn try_from(row: EntryRow) -> Result<Self> {
+        let week_day = match row.week_day.parse::<Weekday>() {
+            Ok(day) => day,
+            Err(e) => match NaiveDateTime::parse_from_str(&row.start, DATE_FORMAT) {
+                Ok(start) => start.weekday().into(),
+                Err(_) => return Err(e),
+            },
+        };
 
-use crate::{Entry, Project};
+        Ok(Entry {
+            id: row.id,
+            start: row.start,
+            stop: row.stop,
+            week_day,
+            code: ProjectCode::from_stored(&row.code),
+            memo: row.memo,
+        })
+    }
+}
+
+fn entries_from_rows(rows: Vec<EntryRow>) -> Result<Vec<Entry>> {
+    rows.into_iter().map(Entry::try_from).collect()
+}
+
+/// A stored value that doesn't fit the type it's read into.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntegrityIssue {
+    pub id: Option<i32>,
+    pub field: String,
+    pub value: String,
+    pub message: String,
+}
+
+/// Row shape of the `projects` table.
+struct ProjectRow {
+    id: Option<i32>,
+    name: String,
+    code: String,
+    client: Option<String>,
+}
+
+impl From<ProjectRow> for Project {
+    fn from(row: ProjectRow) -> Self {
+        Project {
+            id: row.id,
+            name: row.name,
+            code: ProjectCode::from_stored(&row.code),
+            client: row.client,
+        }
+    }
+}
 
 pub async fn setup_db(pool: &SqlitePool) -> Result<()> {
     sqlx::query!(
         "CREATE TABLE IF NOT EXISTS entries (
-        id INTEGER PRIMARY KEY,
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
         start TEXT NOT NULL,
         stop TEXT NOT NULL,
         week_day TEXT NOT NULL,
@@ -25,41 +208,327 @@ pub async fn setup_db(pool: &SqlitePool) -> Result<()> {
         "CREATE TABLE IF NOT EXISTS projects (
         id INTEGER PRIMARY KEY,
         name TEXT NOT NULL,
-        code TEXT NOT NULL)"
+        code TEXT NOT NULL,
+        client TEXT)"
+    )
+    .execute(pool)
+    .await?;
+
+    // Databases created before projects had a client need the column added.
+    let columns: Vec<(String,)> = sqlx::query_as("SELECT name FROM pragma_table_info('projects')")
+        .fetch_all(pool)
+        .await?;
+    if !columns.iter().any(|(name,)| name == "client") {
+        sqlx::query("ALTER TABLE projects ADD COLUMN client TEXT")
+            .execute(pool)
+            .await?;
+    }
+    raise_schema_version(pool, 1).await?;
+
+    sqlx::query!(
+        "CREATE TABLE IF NOT EXISTS journal (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        record TEXT NOT NULL)"
     )
     .execute(pool)
     .await?;
+    raise_schema_version(pool, 2).await?;
+
+    setup_archive(pool).await?;
+    raise_schema_version(pool, 3).await?;
+    migrate_entries_to_autoincrement(pool).await?;
+    raise_schema_version(pool, 4).await?;
+    setup_rollups(pool).await?;
+    raise_schema_version(pool, 5).await?;
+    normalize_codes(pool).await?;
+    raise_schema_version(pool, SCHEMA_VERSION).await?;
 
     Ok(())
 }
 
-pub async fn setup_pool() -> Result<SqlitePool> {
-    dotenv().ok();
-    let db_url = env::var("TIMECARD_DB").context("TIMECARD_DB env var must be set!")?;
+/// Records that the schema has been brought up to `version`, never lowering
+/// a version set by a newer build.
+async fn raise_schema_version(pool: &SqlitePool, version: i64) -> Result<()> {
+    if schema_version(pool).await? < version {
+        // PRAGMA values can't be bound.
+        sqlx::query(&format!("PRAGMA user_version = {}", version))
+            .execute(pool)
+            .await?;
+    }
 
-    Ok(SqlitePool::new(&db_url).await?)
+    Ok(())
 }
 
-pub async fn read_entry(pool: &SqlitePool, id: i32) -> Result<Entry> {
-    Ok(
-        sqlx::query_as!(Entry, "select * from entries where id = ?", id)
+/// Rebuilds an `entries` table created without AUTOINCREMENT, whose ids could
+/// be handed out again once the newest entry was deleted or archived. Ids
+/// polling clients have seen must never come back, so the sequence starts
+/// after the highest id in either table. The rollup triggers go with the old
+/// table and are recreated by `setup_rollups`.
+async fn migrate_entries_to_autoincrement(pool: &SqlitePool) -> Result<()> {
+    let (sql,): (String,) =
+        sqlx::query_as("SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'entries'")
             .fetch_one(pool)
-            .await?,
+            .await?;
+    if sql.to_uppercase().contains("AUTOINCREMENT") {
+        return Ok(());
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "CREATE TABLE entries_autoincrement (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        start TEXT NOT NULL,
+        stop TEXT NOT NULL,
+        week_day TEXT NOT NULL,
+        code TEXT NOT NULL,
+        memo TEXT NOT NULL)",
+    )
+    .execute(&mut tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO entries_autoincrement(id, start, stop, week_day, code, memo)
+        SELECT id, start, stop, week_day, code, memo FROM entries",
     )
+    .execute(&mut tx)
+    .await?;
+    sqlx::query("DROP TABLE entries").execute(&mut tx).await?;
+    sqlx::query("ALTER TABLE entries_autoincrement RENAME TO entries")
+        .execute(&mut tx)
+        .await?;
+    reserve_entry_ids(&mut tx).await?;
+    tx.commit().await?;
+
+    Ok(())
 }
 
-pub async fn read_last_entry(pool: &SqlitePool) -> Result<Entry> {
-    Ok(
-        sqlx::query_as!(Entry, "select * from entries order by id desc limit 1")
-            .fetch_one(pool)
-            .await?,
+/// Moves the `entries` id sequence past every id in `entries` and
+/// `entries_archive`, so none of them is handed out again.
+async fn reserve_entry_ids(tx: &mut Tx) -> Result<()> {
+    let (seq,): (i64,) = sqlx::query_as(
+        "SELECT MAX(
+            IFNULL((SELECT MAX(seq) FROM sqlite_sequence WHERE name = 'entries'), 0),
+            IFNULL((SELECT MAX(id) FROM entries), 0),
+            IFNULL((SELECT MAX(id) FROM entries_archive), 0))",
     )
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM sqlite_sequence WHERE name = 'entries'")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("INSERT INTO sqlite_sequence(name, seq) VALUES('entries', ?)")
+        .bind(seq)
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
+
+/// Rewrites stored project codes in the form `ProjectCode` gives them. Codes
+/// written before it existed may carry whitespace or lowercase letters, and
+/// would otherwise never match a code typed by a user.
+pub async fn normalize_codes(pool: &SqlitePool) -> Result<()> {
+    let tables = [
+        (
+            "SELECT DISTINCT code FROM entries",
+            "UPDATE entries SET code = ? WHERE code = ?",
+        ),
+        (
+            "SELECT DISTINCT code FROM entries_archive",
+            "UPDATE entries_archive SET code = ? WHERE code = ?",
+        ),
+        (
+            "SELECT DISTINCT code FROM projects",
+            "UPDATE projects SET code = ? WHERE code = ?",
+        ),
+    ];
+
+    let mut tx = pool.begin().await?;
+    for (select, update) in tables.iter() {
+        let codes: Vec<(String,)> = sqlx::query_as(select).fetch_all(&mut tx).await?;
+        for (code,) in codes {
+            let normalized = ProjectCode::from_stored(&code);
+            if normalized.as_str() != code {
+                sqlx::query(update)
+                    .bind(normalized.as_str())
+                    .bind(&code)
+                    .execute(&mut tx)
+                    .await?;
+            }
+        }
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Creates `entries_archive`, where old entries are moved by
+/// `archive_entries_before`. Rows keep the id they had in `entries`, which
+/// must be unique so an id is never archived twice.
+pub async fn setup_archive(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS entries_archive (
+        id INTEGER NOT NULL,
+        start TEXT NOT NULL,
+        stop TEXT NOT NULL,
+        week_day TEXT NOT NULL,
+        code TEXT NOT NULL,
+        memo TEXT NOT NULL)",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS entries_archive_id ON entries_archive(id)")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Creates the `monthly_totals` rollup of minutes per project code and month,
+/// and the triggers maintaining it. A new rollup is filled from the entries
+/// already stored.
+pub async fn setup_rollups(pool: &SqlitePool) -> Result<()> {
+    let (existing,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'monthly_totals'",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS monthly_totals (
+        code TEXT NOT NULL,
+        month TEXT NOT NULL,
+        minutes INTEGER NOT NULL,
+        PRIMARY KEY (code, month))",
+    )
+    .execute(pool)
+    .await?;
+    for trigger in ROLLUP_TRIGGERS.iter() {
+        sqlx::query(trigger).execute(pool).await?;
+    }
+
+    if existing == 0 {
+        rebuild_rollups(pool).await?;
+    }
+
+    Ok(())
+}
+
+/// Recomputes `monthly_totals` from the entries, to repair it.
+pub async fn rebuild_rollups(pool: &SqlitePool) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM monthly_totals")
+        .execute(&mut tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO monthly_totals(code, month, minutes)
+        SELECT code, substr(start, 1, 7),
+            SUM(IFNULL((strftime('%s', stop) - strftime('%s', start)) / 60, 0))
+        FROM entries
+        GROUP BY code, substr(start, 1, 7)",
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// The database location from `TIMECARD_DB`, after loading any `.env` file.
+pub fn database_url() -> anyhow::Result<String> {
+    dotenv().ok();
+    env::var("TIMECARD_DB").context("TIMECARD_DB env var must be set!")
+}
+
+/// Opens the database at `database_url` and brings its schema up to date.
+pub async fn setup_pool(database_url: &str) -> anyhow::Result<SqlitePool> {
+    let pool = SqlitePool::new(database_url).await?;
+    setup_db(&pool).await?;
+
+    Ok(pool)
+}
+
+/// The database's `user_version`: `SCHEMA_VERSION` once `setup_db` has run,
+/// 0 for a database it never touched.
+pub async fn schema_version(pool: &SqlitePool) -> Result<i64> {
+    let (version,): (i64,) = sqlx::query_as("PRAGMA user_version")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(version)
+}
+
+pub async fn count_entries(pool: &SqlitePool) -> Result<i64> {
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM entries")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(count)
+}
+
+pub async fn count_projects(pool: &SqlitePool) -> Result<i64> {
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM projects")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(count)
+}
+
+pub async fn read_entry(pool: &SqlitePool, id: i32) -> Result<Entry> {
+    let query = sqlx::query_as!(EntryRow, "select * from entries where id = ?", id);
+    timed("read_entry", query.fetch_optional(pool))
+        .await?
+        .map(Entry::try_from)
+        .transpose()?
+        .ok_or_else(|| TimecardError::NotFound(format!("Entry #{}", id)))
+}
+
+pub async fn read_last_entry(pool: &SqlitePool) -> Result<Entry> {
+    let query = sqlx::query_as!(EntryRow, "select * from entries order by id desc limit 1");
+    timed("read_last_entry", query.fetch_optional(pool))
+        .await?
+        .map(Entry::try_from)
+        .transpose()?
+        .ok_or_else(|| TimecardError::NotFound("Last entry".to_string()))
+}
+
+pub async fn read_last_n_entries(pool: &SqlitePool, n: i32) -> Result<Vec<Entry>> {
+    if !(1..=MAX_SINCE_COUNT).contains(&n) {
+        return Err(TimecardError::invalid(
+            "count",
+            format!("must be between 1 and {}, got {}", MAX_SINCE_COUNT, n),
+        ));
+    }
+
+    let query = sqlx::query_as!(
+        EntryRow,
+        "select * from entries order by id desc limit ?",
+        n
+    );
+    let rows = timed("read_last_n_entries", query.fetch_all(pool)).await?;
+
+    entries_from_rows(rows)
+}
+
+/// Entries with an id above `since_id`, oldest first. Ids are AUTOINCREMENT,
+/// so one is never reused and a cursor never skips an entry written after it.
+/// This is a range scan of the primary key.
+pub async fn read_entries_since(pool: &SqlitePool, since_id: i32) -> Result<Vec<Entry>> {
+    let query = sqlx::query_as!(
+        EntryRow,
+        "select * from entries where id > ? order by id limit ?",
+        since_id,
+        MAX_SINCE_COUNT
+    );
+    let rows = timed("read_entries_since", query.fetch_all(pool)).await?;
+
+    entries_from_rows(rows)
 }
 
 pub async fn read_all_entries(pool: &SqlitePool) -> Result<Vec<Entry>> {
-    Ok(sqlx::query_as!(Entry, "select * from entries")
-        .fetch_all(pool)
-        .await?)
+    let query = sqlx::query_as!(EntryRow, "select * from entries");
+    let rows = timed("read_all_entries", query.fetch_all(pool)).await?;
+
+    entries_from_rows(rows)
 }
 
 pub async fn read_entries_between(
@@ -67,87 +536,578 @@ pub async fn read_entries_between(
     start_date: String,
     end_date: String,
 ) -> Result<Vec<Entry>> {
-    Ok(sqlx::query_as!(
-        Entry,
+    let query = sqlx::query_as!(
+        EntryRow,
         "SELECT * FROM entries WHERE start >= ? AND start <= ?",
         start_date,
         end_date
+    );
+    let rows = timed("read_entries_between", query.fetch_all(pool)).await?;
+
+    entries_from_rows(rows)
+}
+
+/// Archived entries between two timestamps, oldest first.
+pub async fn read_archived_entries_between(
+    pool: &SqlitePool,
+    start_date: String,
+    end_date: String,
+) -> Result<Vec<Entry>> {
+    let query = sqlx::query_as(
+        "SELECT id, start, stop, week_day, code, memo FROM entries_archive
+        WHERE start >= ? AND start <= ?
+        ORDER BY start, id",
     )
-    .fetch_all(pool)
-    .await?)
+    .bind(start_date)
+    .bind(end_date);
+    let rows: Vec<(i32, String, String, String, String, String)> =
+        timed("read_archived_entries_between", query.fetch_all(pool)).await?;
+
+    archived_entries_from_rows(rows)
 }
 
-pub async fn write_entry(pool: &SqlitePool, entry: &Entry) -> Result<i32> {
-    sqlx::query!(
-        "INSERT INTO entries(start, stop, week_day, code, memo) VALUES(?, ?, ?, ?, ?)",
-        entry.start,
-        entry.stop,
-        entry.week_day,
-        entry.code,
-        entry.memo
+/// Every archived entry, oldest first.
+pub async fn read_all_archived_entries(pool: &SqlitePool) -> Result<Vec<Entry>> {
+    let query = sqlx::query_as(
+        "SELECT id, start, stop, week_day, code, memo FROM entries_archive
+        ORDER BY start, id",
+    );
+    let rows: Vec<(i32, String, String, String, String, String)> =
+        timed("read_all_archived_entries", query.fetch_all(pool)).await?;
+
+    archived_entries_from_rows(rows)
+}
+
+fn archived_entries_from_rows(
+    rows: Vec<(i32, String, String, String, String, String)>,
+) -> Result<Vec<Entry>> {
+    let rows = rows
+        .into_iter()
+        .map(|(id, start, stop, week_day, code, memo)| EntryRow {
+            id: Some(id),
+            start,
+            stop,
+            week_day,
+            code,
+            memo,
+        })
+        .collect();
+    entries_from_rows(rows)
+}
+
+/// Moves entries starting before `cutoff` from `entries` to
+/// `entries_archive`, `batch_size` at a time with each batch in its own
+/// transaction. Returns how many were moved. The rollup triggers take the
+/// moved entries out of `monthly_totals`, so summaries only count live ones
+/// unless asked to include the archive. An id already in the archive fails
+/// the batch with a conflict.
+pub async fn archive_entries_before(
+    pool: &SqlitePool,
+    cutoff: NaiveDate,
+    batch_size: i32,
+) -> Result<u64> {
+    if batch_size < 1 {
+        return Err(TimecardError::invalid("batch_size", "must be positive"));
+    }
+    let cutoff = cutoff.to_string();
+    let mut archived = 0;
+
+    loop {
+        let mut tx = pool.begin().await?;
+        // The first write takes the lock, so the delete sees the same batch.
+        let moved = sqlx::query(
+            "INSERT INTO entries_archive(id, start, stop, week_day, code, memo)
+            SELECT id, start, stop, week_day, code, memo FROM entries
+            WHERE start < ? ORDER BY id LIMIT ?",
+        )
+        .bind(&cutoff)
+        .bind(batch_size)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM entries WHERE id IN
+            (SELECT id FROM entries WHERE start < ? ORDER BY id LIMIT ?)",
+        )
+        .bind(&cutoff)
+        .bind(batch_size)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        archived += moved;
+        if moved > 0 {
+            info!(batch = moved, archived, %cutoff, "Archived entries.");
+        }
+        if moved < batch_size as u64 {
+            return Ok(archived);
+        }
+    }
+}
+
+/// Minutes per project code for entries starting from `begin` through `end`,
+/// adding archived entries when `include_archive` is set. Ranges of whole
+/// months are read from the rollup rather than the entries.
+pub async fn project_minutes_between(
+    pool: &SqlitePool,
+    begin: NaiveDate,
+    end: NaiveDate,
+    include_archive: bool,
+) -> Result<BTreeMap<String, i64>> {
+    let whole_months = begin.day() == 1 && end.succ_opt().map_or(true, |next| next.day() == 1);
+    let rows = if whole_months {
+        rollup_minutes(pool, begin, end).await?
+    } else {
+        live_minutes(pool, "entries", begin, end).await?
+    };
+    let mut totals: BTreeMap<String, i64> = rows.into_iter().collect();
+
+    if include_archive {
+        for (code, minutes) in live_minutes(pool, "entries_archive", begin, end).await? {
+            *totals.entry(code).or_insert(0) += minutes;
+        }
+        totals.retain(|_, minutes| *minutes != 0);
+    }
+
+    Ok(totals)
+}
+
+async fn rollup_minutes(
+    pool: &SqlitePool,
+    begin: NaiveDate,
+    end: NaiveDate,
+) -> Result<Vec<(String, i64)>> {
+    let query = sqlx::query_as(
+        "SELECT code, SUM(minutes) FROM monthly_totals
+        WHERE month >= ? AND month <= ?
+        GROUP BY code
+        HAVING SUM(minutes) != 0
+        ORDER BY code",
     )
-    .execute(pool)
+    .bind(begin.format("%Y-%m").to_string())
+    .bind(end.format("%Y-%m").to_string());
+
+    let rows: Vec<(String, i64)> = timed("rollup_minutes", query.fetch_all(pool)).await?;
+
+    Ok(rows)
+}
+
+/// Minutes per project code summed from `table`, either `entries` or
+/// `entries_archive`.
+async fn live_minutes(
+    pool: &SqlitePool,
+    table: &str,
+    begin: NaiveDate,
+    end: NaiveDate,
+) -> Result<Vec<(String, i64)>> {
+    let sql = format!(
+        "SELECT code, SUM(IFNULL((strftime('%s', stop) - strftime('%s', start)) / 60, 0))
+        FROM {}
+        WHERE start >= ? AND start <= ?
+        GROUP BY code
+        HAVING SUM(IFNULL((strftime('%s', stop) - strftime('%s', start)) / 60, 0)) != 0
+        ORDER BY code",
+        table
+    );
+    let query = sqlx::query_as(&sql)
+        .bind(format!("{} 00:00:00", begin))
+        .bind(format!("{} 23:59:59", end));
+
+    let rows: Vec<(String, i64)> = timed("live_minutes", query.fetch_all(pool)).await?;
+
+    Ok(rows)
+}
+
+/// Entries between two timestamps on projects belonging to `client`.
+pub async fn read_client_entries_between(
+    pool: &SqlitePool,
+    start_date: String,
+    end_date: String,
+    client: &str,
+) -> Result<Vec<Entry>> {
+    let query = sqlx::query_as!(
+        EntryRow,
+        "SELECT * FROM entries WHERE start >= ? AND start <= ?
+        AND code IN (SELECT code FROM projects WHERE client = ?)",
+        start_date,
+        end_date,
+        client
+    );
+    let rows = timed("read_client_entries_between", query.fetch_all(pool)).await?;
+
+    entries_from_rows(rows)
+}
+
+/// Stores a new entry and returns its id. An entry with the same start, stop,
+/// code and memo is refused as a `Duplicate` unless `allow_duplicate` is set.
+pub async fn write_entry(
+    pool: &SqlitePool,
+    entry: &NewEntry,
+    allow_duplicate: bool,
+) -> Result<i32> {
+    let ids = write_entries(pool, std::slice::from_ref(entry), allow_duplicate).await?;
+
+    Ok(ids[0])
+}
+
+/// Stores several new entries in one transaction, so either all of them are
+/// written or none are. Returns their ids in order. The batch is journaled as
+/// one change, so a single `undo` removes all of it.
+pub async fn write_entries(
+    pool: &SqlitePool,
+    entries: &[NewEntry],
+    allow_duplicate: bool,
+) -> Result<Vec<i32>> {
+    if entries.is_empty() {
+        return Err(TimecardError::invalid("entries", "must not be empty"));
+    }
+
+    let mut tx = pool.begin().await?;
+    // A concurrent write is waited for rather than failing with SQLITE_BUSY,
+    // so an identical request racing this one ends up a `Duplicate`.
+    sqlx::query(&format!("PRAGMA busy_timeout = {}", BUSY_TIMEOUT_MS))
+        .execute(&mut tx)
+        .await?;
+    let mut ids = Vec::with_capacity(entries.len());
+    let mut created = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let id = insert_entry(&mut tx, entry, allow_duplicate).await?;
+        ids.push(id);
+        created.push(Entry {
+            id: Some(id),
+            ..entry.clone().into()
+        });
+    }
+    journal(&mut tx, &UndoRecord::Created(created)).await?;
+    tx.commit().await?;
+
+    Ok(ids)
+}
+
+async fn insert_entry(tx: &mut Tx, entry: &NewEntry, allow_duplicate: bool) -> Result<i32> {
+    // The duplicate check is part of the insert, so two identical requests
+    // can't both get in between a check and an insert.
+    let week_day = entry.week_day.to_string();
+    let inserted = sqlx::query(
+        "INSERT INTO entries(start, stop, week_day, code, memo)
+        SELECT ?, ?, ?, ?, ?
+        WHERE ? OR NOT EXISTS (
+            SELECT 1 FROM entries WHERE start = ? AND stop = ? AND code = ? AND memo = ?)",
+    )
+    .bind(&entry.start)
+    .bind(&entry.stop)
+    .bind(&week_day)
+    .bind(entry.code.as_str())
+    .bind(&entry.memo)
+    .bind(allow_duplicate)
+    .bind(&entry.start)
+    .bind(&entry.stop)
+    .bind(entry.code.as_str())
+    .bind(&entry.memo)
+    .execute(&mut *tx)
     .await?;
 
+    if inserted == 0 {
+        let (id,): (i32,) = sqlx::query_as(
+            "SELECT id FROM entries WHERE start = ? AND stop = ? AND code = ? AND memo = ?",
+        )
+        .bind(&entry.start)
+        .bind(&entry.stop)
+        .bind(entry.code.as_str())
+        .bind(&entry.memo)
+        .fetch_one(&mut *tx)
+        .await?;
+        return Err(TimecardError::Duplicate(id));
+    }
+
     let rec: (i32,) = sqlx::query_as("SELECT last_insert_rowid()")
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
         .await?;
 
     Ok(rec.0)
 }
 
 pub async fn update_entry(pool: &SqlitePool, entry: &Entry) -> Result<()> {
-    sqlx::query!(
+    let id = entry
+        .id
+        .ok_or_else(|| TimecardError::invalid("id", "is required to update an entry"))?;
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, id).await?;
+    let week_day = entry.week_day.to_string();
+    let updated = sqlx::query!(
         "UPDATE entries SET start=?, stop=?, week_day=?, code=?, memo=?
         WHERE id=?",
         entry.start,
         entry.stop,
-        entry.week_day,
-        entry.code,
+        week_day,
+        entry.code.as_str(),
         entry.memo,
-        entry.id
+        id
     )
-    .execute(pool)
+    .execute(&mut tx)
     .await?;
 
+    if updated == 0 {
+        return Err(TimecardError::NotFound(format!("Entry #{}", id)));
+    }
+    journal(&mut tx, &UndoRecord::Updated(before.into_iter().collect())).await?;
+    tx.commit().await?;
+
     Ok(())
 }
 
 pub async fn delete_entry(pool: &SqlitePool, id: i32) -> Result<()> {
-    sqlx::query!("DELETE FROM entries WHERe id=?", id)
-        .execute(pool)
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, id).await?;
+    let deleted = sqlx::query!("DELETE FROM entries WHERe id=?", id)
+        .execute(&mut tx)
         .await?;
 
+    if deleted == 0 {
+        return Err(TimecardError::NotFound(format!("Entry #{}", id)));
+    }
+    journal(&mut tx, &UndoRecord::Deleted(before.into_iter().collect())).await?;
+    tx.commit().await?;
+
     Ok(())
 }
 
+/// Deletes the newest entry. Does nothing, and leaves the journal alone, when
+/// there are no entries.
 pub async fn delete_last_entry(pool: &SqlitePool) -> Result<()> {
-    sqlx::query!("DELETE FROM entries WHERE id = (SELECT MAX(id) FROM entries LIMIT 1);")
-        .execute(pool)
+    let mut tx = pool.begin().await?;
+    let last: Option<(i32,)> = sqlx::query_as("SELECT id FROM entries ORDER BY id DESC LIMIT 1")
+        .fetch_optional(&mut tx)
+        .await?;
+    let id = match last {
+        Some((id,)) => id,
+        None => return Ok(()),
+    };
+
+    let before = snapshot(&mut tx, id).await?;
+    sqlx::query!("DELETE FROM entries WHERE id = ?", id)
+        .execute(&mut tx)
         .await?;
+    journal(&mut tx, &UndoRecord::Deleted(before.into_iter().collect())).await?;
+    tx.commit().await?;
 
     Ok(())
 }
 
-pub async fn read_project(pool: &SqlitePool, id: i32) -> Result<Project> {
-    Ok(
-        sqlx::query_as!(Project, "select * from projects where id = ?", id)
-            .fetch_one(pool)
-            .await?,
+/// Deletes the entries previously returned by `read_last_n_entries`. Takes the
+/// previewed ids rather than a count so entries written after the preview are
+/// never touched. Only the entries actually deleted are journaled.
+pub async fn delete_last_n(pool: &SqlitePool, ids: &[i32]) -> Result<()> {
+    if ids.len() > MAX_DELETE_COUNT as usize {
+        return Err(TimecardError::invalid(
+            "ids",
+            format!(
+                "cannot delete more than {} entries at once",
+                MAX_DELETE_COUNT
+            ),
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut before = Vec::new();
+    let mut any_deleted = false;
+    for &id in ids {
+        let entry = snapshot(&mut tx, id).await?;
+        let deleted = sqlx::query!("DELETE FROM entries WHERE id = ?", id)
+            .execute(&mut tx)
+            .await?;
+        if deleted > 0 {
+            any_deleted = true;
+            before.extend(entry);
+        }
+    }
+    if any_deleted {
+        journal(&mut tx, &UndoRecord::Deleted(before)).await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Reads an entry inside `tx` so it can be journaled before it changes. A row
+/// that can't be read as an `Entry` couldn't be restored either, so it's left
+/// out rather than failing the change.
+async fn snapshot(tx: &mut Tx, id: i32) -> Result<Option<Entry>> {
+    let row = sqlx::query_as!(EntryRow, "select * from entries where id = ?", id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    Ok(row.and_then(|row| Entry::try_from(row).ok()))
+}
+
+/// Journals a change for `undo` in the transaction making it, replacing the
+/// previous record. A change with nothing restorable clears the journal
+/// instead, so `undo` never reverses an older change out of order.
+async fn journal(tx: &mut Tx, record: &UndoRecord) -> Result<()> {
+    let entries = match record {
+        UndoRecord::Created(entries)
+        | UndoRecord::Updated(entries)
+        | UndoRecord::Deleted(entries) => entries,
+    };
+    if entries.is_empty() {
+        sqlx::query!("DELETE FROM journal")
+            .execute(&mut *tx)
+            .await?;
+        return Ok(());
+    }
+
+    let record = serde_json::to_string(record)?;
+    sqlx::query!(
+        "INSERT OR REPLACE INTO journal(id, record) VALUES(1, ?)",
+        record
     )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Reverses the most recently journaled change and clears the journal.
+/// Returns the record that was undone, or `None` if there was nothing to undo.
+pub async fn undo_last_action(pool: &SqlitePool) -> Result<Option<UndoRecord>> {
+    let mut tx = pool.begin().await?;
+    let row: Option<(String,)> = sqlx::query_as("SELECT record FROM journal WHERE id = 1")
+        .fetch_optional(&mut tx)
+        .await?;
+
+    let record: UndoRecord = match row {
+        Some((record,)) => serde_json::from_str(&record)?,
+        None => return Ok(None),
+    };
+
+    match &record {
+        UndoRecord::Created(entries) => {
+            for entry in entries {
+                sqlx::query!("DELETE FROM entries WHERE id = ?", entry.id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+        }
+        UndoRecord::Updated(entries) => {
+            for entry in entries {
+                let week_day = entry.week_day.to_string();
+                sqlx::query!(
+                    "UPDATE entries SET start=?, stop=?, week_day=?, code=?, memo=?
+                    WHERE id=?",
+                    entry.start,
+                    entry.stop,
+                    week_day,
+                    entry.code.as_str(),
+                    entry.memo,
+                    entry.id
+                )
+                .execute(&mut tx)
+                .await?;
+            }
+        }
+        UndoRecord::Deleted(entries) => {
+            for entry in entries {
+                let week_day = entry.week_day.to_string();
+                sqlx::query!(
+                    "INSERT INTO entries(id, start, stop, week_day, code, memo)
+                    VALUES(?, ?, ?, ?, ?, ?)",
+                    entry.id,
+                    entry.start,
+                    entry.stop,
+                    week_day,
+                    entry.code.as_str(),
+                    entry.memo
+                )
+                .execute(&mut tx)
+                .await?;
+            }
+        }
+    }
+    sqlx::query!("DELETE FROM journal").execute(&mut tx).await?;
+    tx.commit().await?;
+
+    Ok(Some(record))
+}
+
+/// Checks every stored entry for values that can't be read as-is: malformed
+/// timestamps, and week days that are unknown or disagree with the start time.
+pub async fn integrity_report(pool: &SqlitePool) -> Result<Vec<IntegrityIssue>> {
+    let query = sqlx::query_as!(EntryRow, "select * from entries");
+    let rows = timed("integrity_report", query.fetch_all(pool)).await?;
+
+    let mut issues = Vec::new();
+    for row in rows {
+        let mut issue = |field: &str, value: &str, message: String| {
+            issues.push(IntegrityIssue {
+                id: row.id,
+                field: field.to_string(),
+                value: value.to_string(),
+                message,
+            })
+        };
+
+        let start = NaiveDateTime::parse_from_str(&row.start, DATE_FORMAT);
+        if start.is_err() {
+            issue("start", &row.start, "is not a valid timestamp".to_string());
+        }
+        if NaiveDateTime::parse_from_str(&row.stop, DATE_FORMAT).is_err() {
+            issue("stop", &row.stop, "is not a valid timestamp".to_string());
+        }
+
+        match (row.week_day.parse::<Weekday>(), start) {
+            (Err(_), _) => issue(
+                "week_day",
+                &row.week_day,
+                "is not a day of the week".to_string(),
+            ),
+            (Ok(day), Ok(start)) if day != Weekday::from(start.weekday()) => issue(
+                "week_day",
+                &row.week_day,
+                format!("does not match the start time, a {}", start.weekday()),
+            ),
+            _ => {}
+        }
+    }
+
+    Ok(issues)
+}
+
+pub async fn read_project(pool: &SqlitePool, id: i32) -> Result<Project> {
+    let query = sqlx::query_as!(ProjectRow, "select * from projects where id = ?", id);
+    timed("read_project", query.fetch_optional(pool))
+        .await?
+        .map(Project::from)
+        .ok_or_else(|| TimecardError::NotFound(format!("Project #{}", id)))
 }
 
 pub async fn read_all_projects(pool: &SqlitePool) -> Result<Vec<Project>> {
-    Ok(sqlx::query_as!(Project, "select * from projects")
-        .fetch_all(pool)
-        .await?)
+    let query = sqlx::query_as!(ProjectRow, "select * from projects");
+    let rows = timed("read_all_projects", query.fetch_all(pool)).await?;
+
+    Ok(rows.into_iter().map(Project::from).collect())
+}
+
+/// Every client with how many projects it has, ordered by name.
+pub async fn read_clients(pool: &SqlitePool) -> Result<Vec<ClientProjects>> {
+    let query = sqlx::query_as(
+        "SELECT client, COUNT(*) FROM projects
+        WHERE client IS NOT NULL
+        GROUP BY client
+        ORDER BY client",
+    );
+    let rows: Vec<(String, i64)> = timed("read_clients", query.fetch_all(pool)).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(client, projects)| ClientProjects { client, projects })
+        .collect())
 }
 
 pub async fn write_project(pool: &SqlitePool, project: &Project) -> Result<i32> {
     sqlx::query!(
-        "INSERT INTO projects(name, code) VALUES(?, ?)",
+        "INSERT INTO projects(name, code, client) VALUES(?, ?, ?)",
         project.name,
-        project.code,
+        project.code.as_str(),
+        project.client,
     )
     .execute(pool)
     .await?;
@@ -160,30 +1120,238 @@ pub async fn write_project(pool: &SqlitePool, project: &Project) -> Result<i32>
 }
 
 pub async fn update_project(pool: &SqlitePool, project: &Project) -> Result<()> {
-    sqlx::query!(
-        "UPDATE projects SET name=?, code=?
+    let id = project
+        .id
+        .ok_or_else(|| TimecardError::invalid("id", "is required to update a project"))?;
+    let updated = sqlx::query!(
+        "UPDATE projects SET name=?, code=?, client=?
         WHERE id=?",
         project.name,
-        project.code,
-        project.id,
+        project.code.as_str(),
+        project.client,
+        id,
     )
     .execute(pool)
     .await?;
 
+    if updated == 0 {
+        return Err(TimecardError::NotFound(format!("Project #{}", id)));
+    }
+
     Ok(())
 }
 
-pub async fn delete_project(pool: &SqlitePool, code: String) -> Result<()> {
-    sqlx::query!("DELETE FROM projects WHERe code=?", code)
+pub async fn delete_project(pool: &SqlitePool, code: &ProjectCode) -> Result<()> {
+    let deleted = sqlx::query!("DELETE FROM projects WHERe code=?", code.as_str())
         .execute(pool)
         .await?;
 
+    if deleted == 0 {
+        return Err(TimecardError::NotFound(format!("Project {}", code)));
+    }
+
     Ok(())
 }
 
+/// Every project, entry and archived entry, for `import` into another
+/// database.
+pub async fn export(pool: &SqlitePool) -> Result<Export> {
+    Ok(Export {
+        version: EXPORT_VERSION,
+        projects: read_all_projects(pool).await?,
+        entries: read_all_entries(pool).await?,
+        archived: read_all_archived_entries(pool).await?,
+    })
+}
+
+/// Loads an export in one transaction. An empty database gets the rows with
+/// their ids; anything else is a conflict unless `merge` is set, which adds
+/// the rows with new ids, skipping projects whose code is already taken and
+/// entries with the same start, stop, code and memo as a live or archived
+/// one. Archived entries go back to the archive. Rows are checked like those
+/// sent to `POST /entry` and `POST /project`, and nothing is loaded unless
+/// all of them pass.
+pub async fn import(pool: &SqlitePool, export: &Export, merge: bool) -> Result<ImportSummary> {
+    if !(1..=EXPORT_VERSION).contains(&export.version) {
+        return Err(TimecardError::invalid(
+            "version",
+            format!(
+                "must be from 1 to {}, got {}",
+                EXPORT_VERSION, export.version
+            ),
+        ));
+    }
+    validate_export(export)?;
+
+    let mut tx = pool.begin().await?;
+    if !merge {
+        let (rows,): (i64,) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM projects) + (SELECT COUNT(*) FROM entries)
+            + (SELECT COUNT(*) FROM entries_archive)",
+        )
+        .fetch_one(&mut tx)
+        .await?;
+        if rows > 0 {
+            return Err(TimecardError::Conflict(
+                "the database already has data, merge to add to it".to_string(),
+            ));
+        }
+    }
+
+    let mut summary = ImportSummary {
+        projects: 0,
+        entries: 0,
+        archived: 0,
+    };
+
+    for project in &export.projects {
+        if merge {
+            let existing: Option<(i32,)> = sqlx::query_as("SELECT id FROM projects WHERE code = ?")
+                .bind(project.code.as_str())
+                .fetch_optional(&mut tx)
+                .await?;
+            if existing.is_some() {
+                continue;
+            }
+        }
+
+        let id = if merge { None } else { project.id };
+        sqlx::query!(
+            "INSERT INTO projects(id, name, code, client) VALUES(?, ?, ?, ?)",
+            id,
+            project.name,
+            project.code.as_str(),
+            project.client,
+        )
+        .execute(&mut tx)
+        .await?;
+        summary.projects += 1;
+    }
+
+    for entry in &export.entries {
+        if merge && entry_exists(&mut tx, entry).await? {
+            continue;
+        }
+
+        let id = if merge { None } else { entry.id };
+        let week_day = entry.week_day.to_string();
+        sqlx::query!(
+            "INSERT INTO entries(id, start, stop, week_day, code, memo)
+            VALUES(?, ?, ?, ?, ?, ?)",
+            id,
+            entry.start,
+            entry.stop,
+            week_day,
+            entry.code.as_str(),
+            entry.memo
+        )
+        .execute(&mut tx)
+        .await?;
+        summary.entries += 1;
+    }
+
+    for entry in &export.archived {
+        if merge && entry_exists(&mut tx, entry).await? {
+            continue;
+        }
+
+        // The archive has no id sequence of its own; new ids come from the
+        // one `entries` uses, which is then moved past them.
+        let id = if merge { None } else { entry.id };
+        let week_day = entry.week_day.to_string();
+        sqlx::query(
+            "INSERT INTO entries_archive(id, start, stop, week_day, code, memo)
+            SELECT IFNULL(?, MAX(
+                IFNULL((SELECT MAX(seq) FROM sqlite_sequence WHERE name = 'entries'), 0),
+                IFNULL((SELECT MAX(id) FROM entries), 0),
+                IFNULL((SELECT MAX(id) FROM entries_archive), 0)) + 1),
+                ?, ?, ?, ?, ?",
+        )
+        .bind(id)
+        .bind(&entry.start)
+        .bind(&entry.stop)
+        .bind(week_day)
+        .bind(entry.code.as_str())
+        .bind(&entry.memo)
+        .execute(&mut tx)
+        .await?;
+        summary.archived += 1;
+    }
+    reserve_entry_ids(&mut tx).await?;
+    tx.commit().await?;
+
+    Ok(summary)
+}
+
+/// Checks every code against the configured pattern and every timestamp
+/// against `DATE_FORMAT`. Fields are named by their place in the export, e.g.
+/// `entries[3].start`.
+fn validate_export(export: &Export) -> Result<()> {
+    let mut errors = Vec::new();
+    for (i, project) in export.projects.iter().enumerate() {
+        errors.extend(code_errors(&format!("projects[{}].code", i), &project.code));
+    }
+    for (section, entries) in &[("entries", &export.entries), ("archived", &export.archived)] {
+        for (i, entry) in entries.iter().enumerate() {
+            errors.extend(code_errors(
+                &format!("{}[{}].code", section, i),
+                &entry.code,
+            ));
+            for (field, value) in &[("start", &entry.start), ("stop", &entry.stop)] {
+                if NaiveDateTime::parse_from_str(value, DATE_FORMAT).is_err() {
+                    errors.push(FieldError::new(
+                        &format!("{}[{}].{}", section, i, field),
+                        format!("'{}' is not a {} timestamp", value, DATE_FORMAT),
+                    ));
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(TimecardError::Validation(errors))
+    }
+}
+
+fn code_errors(field: &str, code: &ProjectCode) -> Vec<FieldError> {
+    match code.validate() {
+        Err(TimecardError::Validation(errors)) => errors
+            .into_iter()
+            .map(|error| FieldError::new(field, error.message))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Whether a live or archived entry has the same start, stop, code and memo.
+async fn entry_exists(tx: &mut Tx, entry: &Entry) -> Result<bool> {
+    let existing: Option<(i32,)> = sqlx::query_as(
+        "SELECT id FROM entries WHERE start = ? AND stop = ? AND code = ? AND memo = ?
+        UNION ALL
+        SELECT id FROM entries_archive
+        WHERE start = ? AND stop = ? AND code = ? AND memo = ?",
+    )
+    .bind(&entry.start)
+    .bind(&entry.stop)
+    .bind(entry.code.as_str())
+    .bind(&entry.memo)
+    .bind(&entry.start)
+    .bind(&entry.stop)
+    .bind(entry.code.as_str())
+    .bind(&entry.memo)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    Ok(existing.is_some())
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    #[cfg(feature = "server")]
+    use crate::telemetry::CapturedLogs;
     use chrono::{Datelike, Duration, Local, Timelike};
     use rand::distributions::Alphanumeric;
     use rand::{thread_rng, Rng};
@@ -198,7 +1366,7 @@ pub mod tests {
     pub async fn setup_entries_table(pool: &SqlitePool) -> Result<()> {
         sqlx::query!(
             "CREATE TABLE IF NOT EXISTS entries(
-                id INTEGER PRIMARY KEY,
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
                 start TEXT,
                 stop TEXT,
                 week_day TEXT,
@@ -208,6 +1376,19 @@ pub mod tests {
         .execute(pool)
         .await?;
 
+        // Every entry change is journaled, so the two always go together.
+        setup_journal_table(pool).await
+    }
+
+    pub async fn setup_journal_table(pool: &SqlitePool) -> Result<()> {
+        sqlx::query!(
+            "CREATE TABLE IF NOT EXISTS journal(
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                record TEXT)",
+        )
+        .execute(pool)
+        .await?;
+
         Ok(())
     }
 
@@ -216,7 +1397,8 @@ pub mod tests {
             "CREATE TABLE IF NOT EXISTS projects(
                 id INTEGER PRIMARY KEY,
                 name TEXT,
-                code TEXT)",
+                code TEXT,
+                client TEXT)",
         )
         .execute(pool)
         .await?;
@@ -245,20 +1427,117 @@ pub mod tests {
         let pool = setup_test_db().await?;
         setup_entries_table(&pool).await?;
 
-        let mut exp_entry = Entry {
-            id: None,
+        let new_entry = NewEntry {
             start: "0900".to_string(),
             stop: "1000".to_string(),
-            week_day: "WED".to_string(),
-            code: "20-008".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+
+        let id = write_entry(&pool, &new_entry, false).await?;
+        let exp_entry = Entry {
+            id: Some(id),
+            ..new_entry.into()
+        };
+
+        let entry = read_entry(&pool, id).await?;
+        assert_eq!(entry, exp_entry);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_duplicate_entry() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        let entry = NewEntry {
+            start: "2021-02-03 09:00:00".to_string(),
+            stop: "2021-02-03 10:00:00".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+        let id = write_entry(&pool, &entry, false).await?;
+
+        let err = write_entry(&pool, &entry, false).await.unwrap_err();
+        assert!(matches!(err, TimecardError::Duplicate(existing) if existing == id));
+        assert_eq!(read_all_entries(&pool).await?.len(), 1);
+
+        let repeated = write_entry(&pool, &entry, true).await?;
+        assert_ne!(repeated, id);
+
+        // A different memo or stop makes it a different entry.
+        let other_memo = NewEntry {
+            memo: "more work".to_string(),
+            ..entry.clone()
+        };
+        write_entry(&pool, &other_memo, false).await?;
+        let other_stop = NewEntry {
+            stop: "2021-02-03 10:15:00".to_string(),
+            ..entry
+        };
+        write_entry(&pool, &other_stop, false).await?;
+        assert_eq!(read_all_entries(&pool).await?.len(), 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_duplicates() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        let entry = NewEntry {
+            start: "2021-02-03 09:00:00".to_string(),
+            stop: "2021-02-03 10:00:00".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+        let (a, b, c) = tokio::join!(
+            write_entry(&pool, &entry, false),
+            write_entry(&pool, &entry, false),
+            write_entry(&pool, &entry, false),
+        );
+
+        // One gets in; the others see it rather than failing some other way.
+        let results = vec![a, b, c];
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+        for result in results.into_iter().filter(|r| r.is_err()) {
+            assert!(matches!(result, Err(TimecardError::Duplicate(_))));
+        }
+        assert_eq!(read_all_entries(&pool).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_entries_all_or_nothing() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        let entry = |start: &str, stop: &str| NewEntry {
+            start: start.to_string(),
+            stop: stop.to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse().unwrap(),
             memo: "work, work, work".to_string(),
         };
+        let morning = entry("2021-02-03 09:00:00", "2021-02-03 12:00:00");
+        let afternoon = entry("2021-02-03 13:00:00", "2021-02-03 17:00:00");
+        write_entry(&pool, &afternoon, false).await?;
 
-        let id = write_entry(&pool, &exp_entry).await?;
-        exp_entry.id = Some(id);
+        // The second half is a duplicate, so the first isn't kept either.
+        let halves = vec![morning.clone(), afternoon];
+        assert!(write_entries(&pool, &halves, false).await.is_err());
+        assert_eq!(read_all_entries(&pool).await?.len(), 1);
 
-        let entry = read_entry(&pool, id).await?;
-        assert_eq!(entry, exp_entry);
+        let ids = write_entries(&pool, &halves, true).await?;
+        assert_eq!(ids.len(), 2);
+        assert_eq!(read_entry(&pool, ids[0]).await?.start, morning.start);
+        assert!(write_entries(&pool, &[], false).await.is_err());
 
         Ok(())
     }
@@ -268,30 +1547,33 @@ pub mod tests {
         let pool = setup_test_db().await?;
         setup_entries_table(&pool).await?;
 
-        let entry = Entry {
-            id: None,
+        let entry = NewEntry {
             start: "0900".to_string(),
             stop: "1000".to_string(),
-            week_day: "WED".to_string(),
-            code: "20-008".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
             memo: "work, work, work".to_string(),
         };
 
-        let mut last_entry = Entry {
-            id: None,
+        let last_entry = NewEntry {
             start: "1300".to_string(),
             stop: "1530".to_string(),
-            week_day: "FRI".to_string(),
-            code: "20-000-00".to_string(),
+            week_day: Weekday::Fri,
+            code: "20-000-00".parse()?,
             memo: "work, work, work".to_string(),
         };
 
-        write_entry(&pool, &entry).await?;
-        let id = write_entry(&pool, &last_entry).await?;
-        last_entry.id = Some(id);
+        write_entry(&pool, &entry, false).await?;
+        let id = write_entry(&pool, &last_entry, false).await?;
 
         let entry = read_last_entry(&pool).await?;
-        assert_eq!(entry, last_entry);
+        assert_eq!(
+            entry,
+            Entry {
+                id: Some(id),
+                ..last_entry.into()
+            }
+        );
 
         Ok(())
     }
@@ -301,29 +1583,33 @@ pub mod tests {
         let pool = setup_test_db().await?;
         setup_entries_table(&pool).await?;
 
-        let mut exp_entry1 = Entry {
-            id: None,
+        let new_entry1 = NewEntry {
             start: "0900".to_string(),
             stop: "1000".to_string(),
-            week_day: "WED".to_string(),
-            code: "20-008".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
             memo: "work, work, work".to_string(),
         };
 
-        let mut exp_entry2 = Entry {
-            id: None,
+        let new_entry2 = NewEntry {
             start: "1200".to_string(),
             stop: "1430".to_string(),
-            week_day: "FRI".to_string(),
-            code: "20-000".to_string(),
+            week_day: Weekday::Fri,
+            code: "20-000".parse()?,
             memo: "work, work, work".to_string(),
         };
 
-        let id1 = write_entry(&pool, &exp_entry1).await?;
-        let id2 = write_entry(&pool, &exp_entry2).await?;
+        let id1 = write_entry(&pool, &new_entry1, false).await?;
+        let id2 = write_entry(&pool, &new_entry2, false).await?;
 
-        exp_entry1.id = Some(id1);
-        exp_entry2.id = Some(id2);
+        let exp_entry1 = Entry {
+            id: Some(id1),
+            ..new_entry1.into()
+        };
+        let exp_entry2 = Entry {
+            id: Some(id2),
+            ..new_entry2.into()
+        };
 
         let entries = read_all_entries(&pool).await?;
 
@@ -338,33 +1624,32 @@ pub mod tests {
         let pool = setup_test_db().await?;
         setup_entries_table(&pool).await?;
 
-        let code = "20-008".to_string();
+        let code: ProjectCode = "20-008".parse()?;
 
         let start_date = Local::now() - Duration::days(7);
         let end_date = Local::now();
 
         let invalid_date1 = Local::now() - Duration::days(8);
-        let invalid_weekday1 = invalid_date1.weekday().to_string();
+        let invalid_weekday1: Weekday = invalid_date1.weekday().into();
         let invalid_start1 = iso8601_to_db_format(invalid_date1);
         let invalid_stop1 = iso8601_to_db_format(invalid_date1 + Duration::hours(2));
 
         let invalid_date2 = Local::now() - Duration::days(11);
-        let invalid_weekday2 = invalid_date2.weekday().to_string();
+        let invalid_weekday2: Weekday = invalid_date2.weekday().into();
         let invalid_start2 = iso8601_to_db_format(invalid_date2);
         let invalid_stop2 = iso8601_to_db_format(invalid_date2 + Duration::hours(2));
 
         let valid_date1 = start_date + Duration::days(2);
-        let valid_weekday1 = valid_date1.weekday().to_string();
+        let valid_weekday1: Weekday = valid_date1.weekday().into();
         let valid_start1 = iso8601_to_db_format(valid_date1);
         let valid_stop1 = iso8601_to_db_format(valid_date1 + Duration::hours(2));
 
         let valid_date2 = start_date + Duration::days(5);
-        let valid_weekday2 = valid_date2.weekday().to_string();
+        let valid_weekday2: Weekday = valid_date2.weekday().into();
         let valid_start2 = iso8601_to_db_format(valid_date2);
         let valid_stop2 = iso8601_to_db_format(valid_date2 + Duration::hours(2));
 
-        let mut invalid_entry1 = Entry {
-            id: None,
+        let invalid_entry1 = NewEntry {
             start: invalid_start1,
             stop: invalid_stop1,
             week_day: invalid_weekday1,
@@ -372,8 +1657,7 @@ pub mod tests {
             memo: "work, work, work".to_string(),
         };
 
-        let mut invalid_entry2 = Entry {
-            id: None,
+        let invalid_entry2 = NewEntry {
             start: invalid_start2,
             stop: invalid_stop2,
             week_day: invalid_weekday2,
@@ -381,8 +1665,7 @@ pub mod tests {
             memo: "work, work, work".to_string(),
         };
 
-        let mut valid_entry1 = Entry {
-            id: None,
+        let valid_entry1 = NewEntry {
             start: valid_start1,
             stop: valid_stop1,
             week_day: valid_weekday1,
@@ -390,8 +1673,7 @@ pub mod tests {
             memo: "work, work, work".to_string(),
         };
 
-        let mut valid_entry2 = Entry {
-            id: None,
+        let valid_entry2 = NewEntry {
             start: valid_start2,
             stop: valid_stop2,
             week_day: valid_weekday2,
@@ -399,18 +1681,30 @@ pub mod tests {
             memo: "work, work, work".to_string(),
         };
 
-        invalid_entry1.id = Some(write_entry(&pool, &invalid_entry1).await?);
-        invalid_entry2.id = Some(write_entry(&pool, &invalid_entry2).await?);
-        valid_entry1.id = Some(write_entry(&pool, &valid_entry1).await?);
-        valid_entry2.id = Some(write_entry(&pool, &valid_entry2).await?);
+        write_entry(&pool, &invalid_entry1, false).await?;
+        write_entry(&pool, &invalid_entry2, false).await?;
+        let valid_id1 = write_entry(&pool, &valid_entry1, false).await?;
+        let valid_id2 = write_entry(&pool, &valid_entry2, false).await?;
 
         let entries =
             read_entries_between(&pool, start_date.to_string(), end_date.to_string()).await?;
 
         assert!(entries.len() == 2);
 
-        assert_eq!(entries[0], valid_entry1);
-        assert_eq!(entries[1], valid_entry2);
+        assert_eq!(
+            entries[0],
+            Entry {
+                id: Some(valid_id1),
+                ..valid_entry1.into()
+            }
+        );
+        assert_eq!(
+            entries[1],
+            Entry {
+                id: Some(valid_id2),
+                ..valid_entry2.into()
+            }
+        );
 
         Ok(())
     }
@@ -420,22 +1714,24 @@ pub mod tests {
         let pool = setup_test_db().await?;
         setup_entries_table(&pool).await?;
 
-        let mut exp_entry = Entry {
-            id: None,
+        let new_entry = NewEntry {
             start: "0900".to_string(),
             stop: "1000".to_string(),
-            week_day: "WED".to_string(),
-            code: "20-008".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
             memo: "work, work, work".to_string(),
         };
 
-        let id = write_entry(&pool, &exp_entry).await?;
-        exp_entry.id = Some(id);
+        let id = write_entry(&pool, &new_entry, false).await?;
+        let mut exp_entry = Entry {
+            id: Some(id),
+            ..new_entry.into()
+        };
 
         let entry = read_entry(&pool, id).await?;
         assert_eq!(entry.week_day, exp_entry.week_day);
 
-        exp_entry.week_day = "THU".to_string();
+        exp_entry.week_day = Weekday::Thu;
         update_entry(&pool, &exp_entry).await?;
 
         let entry = read_entry(&pool, id).await?;
@@ -444,22 +1740,93 @@ pub mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_missing_entry_is_not_found() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        assert!(matches!(
+            read_entry(&pool, 1).await,
+            Err(TimecardError::NotFound(_))
+        ));
+        assert!(matches!(
+            delete_entry(&pool, 1).await,
+            Err(TimecardError::NotFound(_))
+        ));
+        assert!(matches!(
+            read_last_n_entries(&pool, 0).await,
+            Err(TimecardError::Validation(_))
+        ));
+
+        Ok(())
+    }
+
+    async fn insert_raw_entry(pool: &SqlitePool, start: &str, week_day: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO entries(start, stop, week_day, code, memo)
+            VALUES(?, '2020-06-17 17:00:00', ?, '20-008', 'legacy')",
+        )
+        .bind(start)
+        .bind(week_day)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_legacy_week_days() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        // 2020-06-17 was a Wednesday.
+        insert_raw_entry(&pool, "2020-06-17 09:00:00", "wednesday").await?;
+        insert_raw_entry(&pool, "2020-06-17 10:00:00", "Fri").await?;
+        insert_raw_entry(&pool, "2020-06-17 11:00:00", "blursday").await?;
+        insert_raw_entry(&pool, "yesterday", "someday").await?;
+
+        // Readable rows are kept: known spellings are normalized and unknown
+        // ones fall back to the start time.
+        assert_eq!(read_entry(&pool, 1).await?.week_day, Weekday::Wed);
+        assert_eq!(read_entry(&pool, 2).await?.week_day, Weekday::Fri);
+        assert_eq!(read_entry(&pool, 3).await?.week_day, Weekday::Wed);
+        assert!(matches!(
+            read_entry(&pool, 4).await,
+            Err(TimecardError::Validation(_))
+        ));
+
+        let issues = integrity_report(&pool).await?;
+        let found: Vec<_> = issues
+            .iter()
+            .map(|i| (i.id.unwrap(), i.field.as_str(), i.value.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, "week_day", "Fri"),
+                (3, "week_day", "blursday"),
+                (4, "start", "yesterday"),
+                (4, "week_day", "someday"),
+            ]
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_delete_entry() -> Result<()> {
         let pool = setup_test_db().await?;
         setup_entries_table(&pool).await?;
 
-        let mut exp_entry = Entry {
-            id: None,
+        let new_entry = NewEntry {
             start: "0900".to_string(),
             stop: "1000".to_string(),
-            week_day: "WED".to_string(),
-            code: "20-008".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
             memo: "work, work, work".to_string(),
         };
 
-        let id = write_entry(&pool, &exp_entry).await?;
-        exp_entry.id = Some(id);
+        let id = write_entry(&pool, &new_entry, false).await?;
 
         delete_entry(&pool, id).await?;
         assert!(read_entry(&pool, id).await.is_err());
@@ -472,26 +1839,24 @@ pub mod tests {
         let pool = setup_test_db().await?;
         setup_entries_table(&pool).await?;
 
-        let entry = Entry {
-            id: None,
+        let entry = NewEntry {
             start: "0900".to_string(),
             stop: "1000".to_string(),
-            week_day: "WED".to_string(),
-            code: "20-008".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
             memo: "work, work, work".to_string(),
         };
 
-        let last_entry = Entry {
-            id: None,
+        let last_entry = NewEntry {
             start: "1300".to_string(),
             stop: "1530".to_string(),
-            week_day: "FRI".to_string(),
-            code: "20-000-00".to_string(),
+            week_day: Weekday::Fri,
+            code: "20-000-00".parse()?,
             memo: "work, work, work".to_string(),
         };
 
-        let id1 = write_entry(&pool, &entry).await?;
-        let id2 = write_entry(&pool, &last_entry).await?;
+        let id1 = write_entry(&pool, &entry, false).await?;
+        let id2 = write_entry(&pool, &last_entry, false).await?;
 
         delete_last_entry(&pool).await?;
         assert!(read_entry(&pool, id1).await.is_ok());
@@ -500,6 +1865,410 @@ pub mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_read_last_n_entries() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        let entry = NewEntry {
+            start: "0900".to_string(),
+            stop: "1000".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+
+        let id1 = write_entry(&pool, &entry, true).await?;
+        let id2 = write_entry(&pool, &entry, true).await?;
+        let id3 = write_entry(&pool, &entry, true).await?;
+
+        let entries = read_last_n_entries(&pool, 2).await?;
+        let ids: Vec<Option<i32>> = entries.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![Some(id3), Some(id2)]);
+        assert!(!ids.contains(&Some(id1)));
+
+        assert!(read_last_n_entries(&pool, 0).await.is_err());
+        assert!(read_last_n_entries(&pool, MAX_DELETE_COUNT + 1)
+            .await
+            .is_ok());
+        assert!(read_last_n_entries(&pool, MAX_SINCE_COUNT + 1)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_last_n_pins_previewed_ids() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        let entry = NewEntry {
+            start: "0900".to_string(),
+            stop: "1000".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+
+        let id1 = write_entry(&pool, &entry, true).await?;
+        write_entry(&pool, &entry, true).await?;
+        write_entry(&pool, &entry, true).await?;
+
+        let preview: Vec<i32> = read_last_n_entries(&pool, 2)
+            .await?
+            .iter()
+            .filter_map(|e| e.id)
+            .collect();
+
+        // An entry arriving between the preview and the delete must survive.
+        let late_id = write_entry(&pool, &entry, true).await?;
+
+        delete_last_n(&pool, &preview).await?;
+
+        for id in preview {
+            assert!(read_entry(&pool, id).await.is_err());
+        }
+        assert!(read_entry(&pool, id1).await.is_ok());
+        assert!(read_entry(&pool, late_id).await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_last_n_is_bounded() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        let ids: Vec<i32> = (1..=MAX_DELETE_COUNT + 1).collect();
+        assert!(delete_last_n(&pool, &ids).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undo_nothing_to_undo() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        assert_eq!(undo_last_action(&pool).await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undo_after_add() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        let new_entry = NewEntry {
+            start: "0900".to_string(),
+            stop: "1000".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+
+        let id = write_entry(&pool, &new_entry, false).await?;
+        let entry = Entry {
+            id: Some(id),
+            ..new_entry.into()
+        };
+
+        let undone = undo_last_action(&pool).await?;
+        assert_eq!(undone, Some(UndoRecord::Created(vec![entry])));
+        assert!(read_entry(&pool, id).await.is_err());
+
+        // Only the most recent action can be undone.
+        assert_eq!(undo_last_action(&pool).await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undo_after_edit() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        let new_entry = NewEntry {
+            start: "0900".to_string(),
+            stop: "1000".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+
+        let id = write_entry(&pool, &new_entry, false).await?;
+        let entry = Entry {
+            id: Some(id),
+            ..new_entry.into()
+        };
+
+        let mut edited = entry.clone();
+        edited.memo = "more work".to_string();
+        update_entry(&pool, &edited).await?;
+
+        undo_last_action(&pool).await?;
+        assert_eq!(read_entry(&pool, id).await?, entry);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undo_after_delete() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        let new_entry = NewEntry {
+            start: "0900".to_string(),
+            stop: "1000".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+
+        let id = write_entry(&pool, &new_entry, false).await?;
+        let entry = Entry {
+            id: Some(id),
+            ..new_entry.into()
+        };
+
+        delete_entry(&pool, id).await?;
+
+        undo_last_action(&pool).await?;
+        assert_eq!(read_entry(&pool, id).await?, entry);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undo_after_split_entry() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        let entry = |start: &str, stop: &str| NewEntry {
+            start: start.to_string(),
+            stop: stop.to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse().unwrap(),
+            memo: "work, work, work".to_string(),
+        };
+        let halves = vec![
+            entry("2021-02-03 09:00:00", "2021-02-03 12:00:00"),
+            entry("2021-02-03 13:00:00", "2021-02-03 17:00:00"),
+        ];
+        write_entries(&pool, &halves, false).await?;
+
+        match undo_last_action(&pool).await? {
+            Some(UndoRecord::Created(entries)) => assert_eq!(entries.len(), 2),
+            other => panic!("unexpected undo record: {:?}", other),
+        }
+        assert!(read_all_entries(&pool).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_no_op_delete_keeps_journal() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+
+        let new_entry = NewEntry {
+            start: "0900".to_string(),
+            stop: "1000".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+        let id = write_entry(&pool, &new_entry, false).await?;
+        delete_entry(&pool, id).await?;
+
+        // Nothing left to delete, so the journaled delete is still undoable.
+        delete_last_entry(&pool).await?;
+        delete_last_n(&pool, &[id]).await?;
+        assert!(matches!(
+            undo_last_action(&pool).await?,
+            Some(UndoRecord::Deleted(_))
+        ));
+        assert!(read_entry(&pool, id).await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_archive_entries_before() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+        setup_rollups(&pool).await?;
+        setup_archive(&pool).await?;
+
+        let mut ids = Vec::new();
+        for day in 1..=6 {
+            let entry = NewEntry {
+                start: format!("2021-01-0{} 09:00:00", day),
+                stop: format!("2021-01-0{} 10:00:00", day),
+                week_day: Weekday::Mon,
+                code: "20-008".parse()?,
+                memo: "work, work, work".to_string(),
+            };
+            ids.push(write_entry(&pool, &entry, false).await?);
+        }
+
+        // Five entries in batches of two, so the last batch is partial.
+        let cutoff = NaiveDate::from_ymd(2021, 1, 6);
+        assert_eq!(archive_entries_before(&pool, cutoff, 2).await?, 5);
+        assert_eq!(archive_entries_before(&pool, cutoff, 2).await?, 0);
+
+        let live = read_all_entries(&pool).await?;
+        assert_eq!(live.len(), 1);
+        assert_eq!(live[0].id, Some(ids[5]));
+
+        let start = "2021-01-01 00:00:00".to_string();
+        let end = "2021-01-31 23:59:59".to_string();
+        let archived = read_archived_entries_between(&pool, start, end).await?;
+        let archived_ids: Vec<Option<i32>> = archived.iter().map(|e| e.id).collect();
+        let expected: Vec<Option<i32>> = ids[..5].iter().map(|id| Some(*id)).collect();
+        assert_eq!(archived_ids, expected);
+
+        // Archiving fires the delete trigger, so the rollup only counts live entries.
+        let begin = NaiveDate::from_ymd(2021, 1, 1);
+        let end = NaiveDate::from_ymd(2021, 1, 31);
+        let totals = project_minutes_between(&pool, begin, end, false).await?;
+        assert_eq!(totals["20-008"], 60);
+        let totals = project_minutes_between(&pool, begin, end, true).await?;
+        assert_eq!(totals["20-008"], 360);
+
+        assert!(archive_entries_before(&pool, cutoff, 0).await.is_err());
+
+        // An id already in the archive fails the batch and leaves the entry live.
+        sqlx::query("UPDATE entries SET id = ?, start = '2021-01-05 09:00:00' WHERE id = ?")
+            .bind(ids[0])
+            .bind(ids[5])
+            .execute(&pool)
+            .await?;
+        let result = archive_entries_before(&pool, cutoff, 2).await;
+        assert!(matches!(result, Err(TimecardError::Conflict(_))));
+        assert_eq!(read_entry(&pool, ids[0]).await?.id, Some(ids[0]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_includes_archive() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_db(&pool).await?;
+        for day in 1..=3 {
+            let entry = NewEntry {
+                start: format!("2021-01-0{} 09:00:00", day),
+                stop: format!("2021-01-0{} 10:00:00", day),
+                week_day: Weekday::Mon,
+                code: "20-008".parse()?,
+                memo: "work, work, work".to_string(),
+            };
+            write_entry(&pool, &entry, false).await?;
+        }
+        archive_entries_before(&pool, NaiveDate::from_ymd(2021, 1, 3), 10).await?;
+
+        let exported = export(&pool).await?;
+        assert_eq!(exported.entries.len(), 1);
+        assert_eq!(exported.archived.len(), 2);
+
+        let copy = setup_test_db().await?;
+        setup_db(&copy).await?;
+        let summary = import(&copy, &exported, false).await?;
+        assert_eq!(summary.entries, 1);
+        assert_eq!(summary.archived, 2);
+        assert_eq!(export(&copy).await?, exported);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_checks_rows() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_db(&pool).await?;
+        let entry = |memo: &str| Entry {
+            id: None,
+            start: "2021-02-03 09:00:00".to_string(),
+            stop: "2021-02-03 10:00:00".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse().unwrap(),
+            memo: memo.to_string(),
+        };
+        let mut export = Export {
+            version: EXPORT_VERSION,
+            projects: Vec::new(),
+            entries: vec![entry("work, work, work")],
+            archived: Vec::new(),
+        };
+        import(&pool, &export, false).await?;
+
+        // Only the memo differs, so it's a separate entry.
+        export.entries.push(entry("more work"));
+        let summary = import(&pool, &export, true).await?;
+        assert_eq!(summary.entries, 1);
+        assert_eq!(count_entries(&pool).await?, 2);
+
+        let mut bad = entry("bad rows");
+        bad.stop = "1000".to_string();
+        bad.code = ProjectCode::from_stored("not a code");
+        export.archived.push(bad);
+        match import(&pool, &export, true).await {
+            Err(TimecardError::Validation(fields)) => {
+                let fields: Vec<&str> = fields.iter().map(|f| f.field.as_str()).collect();
+                assert_eq!(fields, vec!["archived[0].code", "archived[0].stop"]);
+            }
+            result => panic!("expected a validation error, got {:?}", result),
+        }
+        assert_eq!(count_entries(&pool).await?, 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rollups_match_live_totals() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+        setup_rollups(&pool).await?;
+
+        let entry = |start: &str, stop: &str, code: &str| NewEntry {
+            start: start.to_string(),
+            stop: stop.to_string(),
+            week_day: Weekday::Mon,
+            code: code.parse().unwrap(),
+            memo: "work, work, work".to_string(),
+        };
+        let first = entry("2021-01-29 09:00:00", "2021-01-29 10:30:00", "20-008");
+        let first = write_entry(&pool, &first, false).await?;
+        let second = entry("2021-02-01 09:00:00", "2021-02-01 12:00:00", "20-008");
+        let second = write_entry(&pool, &second, false).await?;
+        let third = entry("2021-02-03 13:00:00", "2021-02-03 13:45:00", "21-001");
+        write_entry(&pool, &third, false).await?;
+
+        let moved = Entry {
+            id: Some(second),
+            ..entry("2021-02-02 08:00:00", "2021-02-02 09:00:00", "21-001").into()
+        };
+        update_entry(&pool, &moved).await?;
+        delete_entry(&pool, first).await?;
+
+        let begin = NaiveDate::from_ymd(2021, 1, 1);
+        let end = NaiveDate::from_ymd(2021, 2, 28);
+        let live = live_minutes(&pool, "entries", begin, end).await?;
+        assert_eq!(live, vec![("21-001".to_string(), 105)]);
+        assert_eq!(rollup_minutes(&pool, begin, end).await?, live);
+
+        rebuild_rollups(&pool).await?;
+        assert_eq!(rollup_minutes(&pool, begin, end).await?, live);
+
+        // Part of a month is counted from the entries themselves.
+        let day = NaiveDate::from_ymd(2021, 2, 3);
+        let totals = project_minutes_between(&pool, day, day, false).await?;
+        assert_eq!(totals.get("21-001"), Some(&45));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_write_and_read_project() -> Result<()> {
         let pool = setup_test_db().await?;
@@ -508,7 +2277,8 @@ pub mod tests {
         let mut exp_project = Project {
             id: None,
             name: "PPP".to_string(),
-            code: "20-008".to_string(),
+            code: "20-008".parse()?,
+            client: None,
         };
 
         let id = write_project(&pool, &exp_project).await?;
@@ -528,13 +2298,15 @@ pub mod tests {
         let mut exp_project1 = Project {
             id: None,
             name: "PPP".to_string(),
-            code: "20-008".to_string(),
+            code: "20-008".parse()?,
+            client: None,
         };
 
         let mut exp_project2 = Project {
             id: None,
             name: "General".to_string(),
-            code: "20-000-00".to_string(),
+            code: "20-000-00".parse()?,
+            client: None,
         };
 
         let id1 = write_project(&pool, &exp_project1).await?;
@@ -559,7 +2331,8 @@ pub mod tests {
         let mut exp_project = Project {
             id: None,
             name: "PPP".to_string(),
-            code: "20-008".to_string(),
+            code: "20-008".parse()?,
+            client: None,
         };
 
         let id = write_project(&pool, &exp_project).await?;
@@ -583,20 +2356,147 @@ pub mod tests {
         setup_projects_table(&pool).await?;
 
         let name = String::from("PPP");
-        let code = String::from("20-008");
+        let code: ProjectCode = "20-008".parse()?;
 
         let mut exp_project = Project {
             id: None,
             name,
             code: code.clone(),
+            client: None,
         };
 
         let id = write_project(&pool, &exp_project).await?;
         exp_project.id = Some(id);
 
-        delete_project(&pool, code).await?;
+        delete_project(&pool, &code).await?;
         assert!(read_project(&pool, id).await.is_err());
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_schema_version() -> Result<()> {
+        let pool = setup_test_db().await?;
+        assert_eq!(schema_version(&pool).await?, 0);
+
+        setup_db(&pool).await?;
+        assert_eq!(schema_version(&pool).await?, SCHEMA_VERSION);
+
+        // A version set by a newer build is left alone.
+        raise_schema_version(&pool, SCHEMA_VERSION + 1).await?;
+        setup_db(&pool).await?;
+        assert_eq!(schema_version(&pool).await?, SCHEMA_VERSION + 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrate_entries_to_autoincrement() -> Result<()> {
+        let pool = setup_test_db().await?;
+        sqlx::query(
+            "CREATE TABLE entries (
+            id INTEGER PRIMARY KEY,
+            start TEXT NOT NULL,
+            stop TEXT NOT NULL,
+            week_day TEXT NOT NULL,
+            code TEXT NOT NULL,
+            memo TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await?;
+        for hour in &["09", "10"] {
+            insert_raw_entry(&pool, &format!("2020-06-17 {}:00:00", hour), "Wed").await?;
+        }
+
+        setup_db(&pool).await?;
+        setup_db(&pool).await?;
+        assert_eq!(read_all_entries(&pool).await?.len(), 2);
+
+        // The newest id isn't handed out again once it's gone.
+        delete_last_entry(&pool).await?;
+        let new_entry = NewEntry {
+            start: "2020-06-17 11:00:00".to_string(),
+            stop: "2020-06-17 12:00:00".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse()?,
+            memo: "work, work, work".to_string(),
+        };
+        assert_eq!(write_entry(&pool, &new_entry, false).await?, 3);
+
+        // Rollups are still kept up to date.
+        let minutes = rollup_minutes(
+            &pool,
+            NaiveDate::from_ymd(2020, 6, 1),
+            NaiveDate::from_ymd(2020, 6, 30),
+        )
+        .await?;
+        assert_eq!(minutes, vec![("20-008".to_string(), 8 * 60 + 60)]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_normalize_legacy_codes() -> Result<()> {
+        let pool = setup_test_db().await?;
+        setup_db(&pool).await?;
+
+        sqlx::query("INSERT INTO projects(name, code) VALUES('Legacy', ' ab-008 ')")
+            .execute(&pool)
+            .await?;
+        sqlx::query(
+            "INSERT INTO entries(start, stop, week_day, code, memo)
+            VALUES('2021-02-01 09:00:00', '2021-02-01 10:00:00', 'Mon', 'ab-008', '')",
+        )
+        .execute(&pool)
+        .await?;
+
+        normalize_codes(&pool).await?;
+
+        let rows: Vec<(String,)> =
+            sqlx::query_as("SELECT code FROM projects UNION ALL SELECT code FROM entries")
+                .fetch_all(&pool)
+                .await?;
+        assert_eq!(rows, vec![("AB-008".to_string(),), ("AB-008".to_string(),)]);
+
+        // The user's spelling of the code now matches the stored one.
+        delete_project(&pool, &"ab-008".parse()?).await?;
+        assert!(read_all_projects(&pool).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn test_query_timing() -> Result<()> {
+        let logs = CapturedLogs::default();
+        let _guard = tracing::subscriber::set_default(logs.subscriber());
+
+        let pool = setup_test_db().await?;
+        setup_entries_table(&pool).await?;
+        read_entries_between(&pool, "2021-02-01".to_string(), "2021-02-08".to_string()).await?;
+
+        // With no threshold every query is slow, however fast the machine.
+        timed_with(
+            "count_entries",
+            Duration::from_millis(0),
+            count_entries(&pool),
+        )
+        .await?;
+
+        let logs = logs.contents();
+        let closed = logs
+            .lines()
+            .find(|line| line.contains(r#"db_query{operation="read_entries_between" elapsed_ms="#))
+            .expect("no span for read_entries_between");
+        assert!(closed.contains("close"));
+
+        let warning = logs
+            .lines()
+            .find(|line| line.contains("Slow database query."))
+            .expect("no warning for the slow query");
+        assert!(warning.contains("WARN"));
+        assert!(warning.contains(r#"operation="count_entries""#));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,107 @@
+// Crates
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub type Result<T, E = TimecardError> = std::result::Result<T, E>;
+
+/// A problem with one field of a request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TimecardError {
+    #[error("{0} not found.")]
+    NotFound(String),
+    #[error("Invalid {}.", fields_list(.0))]
+    Validation(Vec<FieldError>),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    /// A conflict with an identical entry that's already stored, by its id.
+    #[error("Entry already logged (id {0}).")]
+    Duplicate(i32),
+    #[cfg(feature = "db")]
+    #[error("Database error: {0}")]
+    Database(#[source] sqlx::Error),
+    #[error("Failed to (de)serialize record: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[cfg(feature = "client")]
+    #[error("Request to the server failed: {0}")]
+    Request(#[from] reqwest::Error),
+    /// An error response from the server that doesn't map to another variant.
+    #[error("Server replied {status}: {message}")]
+    Server { status: u16, message: String },
+    #[error("The server didn't reply in time.")]
+    Timeout,
+    /// A successful response whose body isn't what the request returns.
+    #[error("The server's reply couldn't be read: {0}")]
+    MalformedReply(String),
+}
+
+impl TimecardError {
+    /// Shorthand for a validation error on a single field.
+    pub fn invalid(field: &str, message: impl Into<String>) -> Self {
+        TimecardError::Validation(vec![FieldError::new(field, message)])
+    }
+}
+
+/// Missing rows become `NotFound` and constraint violations `Conflict`, so
+/// callers can tell them apart from connection or query failures.
+#[cfg(feature = "db")]
+impl From<sqlx::Error> for TimecardError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => TimecardError::NotFound("Row".to_string()),
+            sqlx::Error::Database(ref db_err) if db_err.message().contains("constraint failed") => {
+                TimecardError::Conflict(db_err.message().to_string())
+            }
+            err => TimecardError::Database(err),
+        }
+    }
+}
+
+fn fields_list(fields: &[FieldError]) -> String {
+    fields
+        .iter()
+        .map(|f| format!("{}: {}", f.field, f.message))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_messages() {
+        let err = TimecardError::Validation(vec![
+            FieldError::new("start", "is required"),
+            FieldError::new("stop", "must be after start"),
+        ]);
+        assert_eq!(
+            err.to_string(),
+            "Invalid start: is required, stop: must be after start."
+        );
+    }
+
+    #[cfg(feature = "db")]
+    #[test]
+    fn test_database_errors() {
+        let err: TimecardError = sqlx::Error::RowNotFound.into();
+        assert!(matches!(err, TimecardError::NotFound(_)));
+
+        let err: TimecardError = sqlx::Error::PoolClosed.into();
+        assert!(matches!(err, TimecardError::Database(_)));
+    }
+}
@@ -0,0 +1,156 @@
+// Std
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+
+// Crates
+use anyhow::{Context as _, Result};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Starts reporting to Sentry if `SENTRY_DSN` is set. Panics are reported from
+/// here on, and `error!` events through `SentryLayer`, until the returned guard
+/// is dropped.
+pub fn init_sentry() -> Result<Option<sentry::ClientInitGuard>> {
+    let dsn = match env::var("SENTRY_DSN") {
+        Ok(dsn) if !dsn.trim().is_empty() => dsn,
+        _ => return Ok(None),
+    };
+    let dsn = dsn
+        .trim()
+        .parse::<sentry::types::Dsn>()
+        .context("SENTRY_DSN is not a valid DSN")?;
+
+    Ok(Some(sentry::init(sentry::ClientOptions {
+        dsn: Some(dsn),
+        release: sentry::release_name!(),
+        ..Default::default()
+    })))
+}
+
+/// Span and event fields copied to Sentry as tags.
+const SENTRY_TAGS: [&str; 2] = ["request_id", "correlation_id"];
+
+/// Sends `error!` events to Sentry, tagged with the route (the innermost span,
+/// i.e. the handler) and any request or correlation id. Does nothing unless
+/// `init_sentry` found a DSN.
+pub struct SentryLayer;
+
+impl<S> Layer<S> for SentryLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(fields) = span.extensions_mut().get_mut::<Fields>() {
+                values.record(fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let mut tags: BTreeMap<String, String> = fields
+            .0
+            .iter()
+            .filter(|(key, _)| SENTRY_TAGS.contains(&key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        let mut route = None;
+        let mut next = ctx.lookup_current();
+        while let Some(span) = next {
+            route.get_or_insert(span.name());
+            if let Some(span_fields) = span.extensions().get::<Fields>() {
+                for (key, value) in &span_fields.0 {
+                    if SENTRY_TAGS.contains(&key.as_str()) {
+                        tags.entry(key.clone()).or_insert_with(|| value.clone());
+                    }
+                }
+            }
+            next = span.parent();
+        }
+        if let Some(route) = route {
+            tags.insert("route".to_string(), route.to_string());
+        }
+
+        sentry::capture_event(sentry::protocol::Event {
+            message: fields.0.remove("message"),
+            level: sentry::Level::Error,
+            logger: Some(event.metadata().target().to_string()),
+            transaction: route.map(str::to_string),
+            tags,
+            extra: fields
+                .0
+                .into_iter()
+                .map(|(key, value)| (key, value.into()))
+                .collect(),
+            ..Default::default()
+        });
+    }
+}
+
+/// Field values as text, by name.
+#[derive(Default)]
+struct Fields(BTreeMap<String, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TimecardError;
+    use tracing::info;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    #[test]
+    fn test_sentry_layer() {
+        let subscriber = Registry::default().with(SentryLayer);
+        let events = sentry::test::with_captured_events(|| {
+            tracing::subscriber::with_default(subscriber, || {
+                let span = tracing::info_span!("read_entry", id = 7, request_id = "abc-123");
+                let _entered = span.enter();
+                info!("Reading entry #7");
+                crate::api::error_reply(&TimecardError::Database(sqlx::Error::PoolClosed));
+            });
+        });
+
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.message.as_deref(), Some("Request failed."));
+        assert_eq!(event.transaction.as_deref(), Some("read_entry"));
+        assert_eq!(event.tags["route"], "read_entry");
+        assert_eq!(event.tags["request_id"], "abc-123");
+        assert!(event.tags.contains_key("correlation_id"));
+        assert!(event.extra["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Database error"));
+    }
+}
@@ -0,0 +1,163 @@
+// Crates
+use anyhow::Result;
+use chrono::NaiveDateTime;
+
+// Modules
+use crate::Entry;
+
+/// Longest content line allowed by RFC 5545, in octets, before folding.
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Local times without a zone, which calendars show as they are, like the
+/// stored timestamps.
+const FLOATING_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// An iCalendar (RFC 5545) document with an event per entry. `stamp` is the
+/// UTC time the document is created, required on every event.
+pub fn calendar(entries: &[Entry], stamp: NaiveDateTime) -> Result<String> {
+    let mut ics = String::new();
+    ics.push_str(&fold("BEGIN:VCALENDAR"));
+    ics.push_str(&fold("VERSION:2.0"));
+    ics.push_str(&fold("PRODID:-//timecard//timecard//EN"));
+    let stamp = format!("{}Z", stamp.format(FLOATING_FORMAT));
+
+    for entry in entries {
+        let (start, stop) = entry.interval()?;
+        let start = start.format(FLOATING_FORMAT).to_string();
+        let uid = match entry.id {
+            Some(id) => format!("entry-{}@timecard", id),
+            None => format!("entry-{}-{}@timecard", start, entry.code),
+        };
+        let summary = if entry.memo.is_empty() {
+            entry.code.to_string()
+        } else {
+            format!("{} — {}", entry.code, entry.memo)
+        };
+
+        ics.push_str(&fold("BEGIN:VEVENT"));
+        ics.push_str(&fold(&format!("UID:{}", uid)));
+        ics.push_str(&fold(&format!("DTSTAMP:{}", stamp)));
+        ics.push_str(&fold(&format!("DTSTART:{}", start)));
+        ics.push_str(&fold(&format!("DTEND:{}", stop.format(FLOATING_FORMAT))));
+        ics.push_str(&fold(&format!("SUMMARY:{}", escape_text(&summary))));
+        ics.push_str(&fold("END:VEVENT"));
+    }
+
+    ics.push_str(&fold("END:VCALENDAR"));
+
+    Ok(ics)
+}
+
+/// Escapes a TEXT value: backslashes, semicolons, commas and newlines.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Ends a content line with CRLF, folding it onto continuation lines that
+/// start with a space so none is longer than 75 octets. Characters are never
+/// split.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    fn entry(memo: &str) -> Entry {
+        Entry {
+            id: Some(42),
+            start: "2021-02-03 09:00:00".to_string(),
+            stop: "2021-02-03 10:30:00".to_string(),
+            week_day: crate::Weekday::Wed,
+            code: "20-008".parse().unwrap(),
+            memo: memo.to_string(),
+        }
+    }
+
+    fn stamp() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2021-02-05 17:00:00", "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_fold_long_memo() -> Result<()> {
+        let memo =
+            "Réunion sur la facturation; notes, questions et suivi avec le client ".repeat(3);
+        let ics = calendar(&[entry(&memo)], stamp())?;
+
+        for line in ics.split_terminator("\r\n") {
+            assert!(line.len() <= MAX_LINE_OCTETS, "{:?}", line);
+        }
+        let unfolded = ics.replace("\r\n ", "");
+        let summary = format!("SUMMARY:20-008 — {}\r\n", escape_text(&memo));
+        assert!(unfolded.contains(&summary));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_escape_text() {
+        assert_eq!(
+            escape_text("a\\b; c, d\ne"),
+            "a\\\\b\\; c\\, d\\ne".to_string()
+        );
+    }
+
+    #[test]
+    fn test_calendar_parses() -> Result<()> {
+        let memo = "Planning, estimates; and a very long memo that needs folding onto more lines";
+        let ics = calendar(&[entry(memo), entry("")], stamp())?;
+
+        let mut parser = ical::IcalParser::new(BufReader::new(ics.as_bytes()));
+        let calendar = parser
+            .next()
+            .expect("no calendar")
+            .expect("unreadable calendar");
+        assert!(parser.next().is_none());
+        assert_eq!(calendar.events.len(), 2);
+
+        let value = |index: usize, name: &str| {
+            calendar.events[index]
+                .properties
+                .iter()
+                .find(|p| p.name == name)
+                .and_then(|p| p.value.clone())
+        };
+        assert_eq!(value(0, "UID").as_deref(), Some("entry-42@timecard"));
+        assert_eq!(value(0, "DTSTART").as_deref(), Some("20210203T090000"));
+        assert_eq!(value(0, "DTEND").as_deref(), Some("20210203T103000"));
+        assert_eq!(value(0, "DTSTAMP").as_deref(), Some("20210205T170000Z"));
+        assert_eq!(
+            value(0, "SUMMARY"),
+            Some(format!("20-008 — {}", escape_text(memo)))
+        );
+        assert_eq!(value(1, "SUMMARY").as_deref(), Some("20-008"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+// Std
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+// Crates
+use anyhow::{anyhow, Context, Result};
+use sqlx::sqlite::SqlitePool;
+
+// Modules
+use crate::config::Config;
+use crate::db;
+use crate::{Project, ProjectCode};
+
+pub static DEFAULT_BASE_URL: &str = "http://localhost:3333";
+
+/// Interactive first-run setup. Asks where entries live, writes the config file,
+/// creates and migrates a local database if one was chosen and optionally adds
+/// a first project. Returns the saved config.
+pub async fn run<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    config_path: &Path,
+    data_dir: &Path,
+) -> Result<Config> {
+    let existing = Config::load(config_path)?;
+    if let Some(config) = &existing {
+        let prompt = format!(
+            "Found existing config at {}. Edit it?",
+            config_path.display()
+        );
+        if !ask_yes_no(input, output, &prompt, false)? {
+            writeln!(output, "Keeping existing config.")?;
+            return Ok(config.clone());
+        }
+    }
+    let mut config = existing.unwrap_or_default();
+
+    let use_local = ask_yes_no(
+        input,
+        output,
+        "Use a local database?",
+        config.database.is_some() || config.base_url.is_none(),
+    )?;
+
+    let mut pool = None;
+    if use_local {
+        let default_path = match &config.database {
+            Some(url) => url.trim_start_matches("sqlite://").to_string(),
+            None => data_dir.join("timecard.db").display().to_string(),
+        };
+        let path = ask(input, output, "Database path", Some(&default_path))?;
+        if let Some(dir) = Path::new(&path).parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+
+        let url = format!("sqlite://{}", path);
+        let db_pool = SqlitePool::new(&url).await?;
+        db::setup_db(&db_pool).await?;
+        pool = Some(db_pool);
+
+        let default_url = config.base_url.as_deref().unwrap_or(DEFAULT_BASE_URL);
+        config.base_url = Some(ask(input, output, "Server URL", Some(default_url))?);
+        config.database = Some(url);
+    } else {
+        config.base_url = Some(ask(
+            input,
+            output,
+            "Server URL",
+            config.base_url.as_deref(),
+        )?);
+        config.database = None;
+    }
+
+    let mut project = None;
+    if let Some(pool) = &pool {
+        if ask_yes_no(input, output, "Add a first project?", false)? {
+            let name = ask(input, output, "Project name", None)?;
+            let code = loop {
+                match ask(input, output, "Project code", None)?.parse::<ProjectCode>() {
+                    Ok(code) => break code,
+                    Err(e) => writeln!(output, "{}", e)?,
+                }
+            };
+            let client = ask(input, output, "Client (blank for none)", Some(""))?;
+            let new_project = Project {
+                id: None,
+                name,
+                code,
+                client: Some(client).filter(|c| !c.is_empty()),
+            };
+            db::write_project(pool, &new_project).await?;
+            project = Some(new_project);
+        }
+    }
+
+    config.save(config_path)?;
+
+    writeln!(output)?;
+    writeln!(output, "Saved config to {}", config_path.display())?;
+    if let Some(url) = &config.database {
+        writeln!(output, "Database: {}", url)?;
+    }
+    if let Some(url) = &config.base_url {
+        writeln!(output, "Server: {}", url)?;
+    }
+    if let Some(project) = project {
+        writeln!(output, "Added project {} ({})", project.name, project.code)?;
+    }
+
+    Ok(config)
+}
+
+/// Prompts until a non-empty answer is given, or returns `default` on a blank line.
+fn ask<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    prompt: &str,
+    default: Option<&str>,
+) -> Result<String> {
+    loop {
+        match default {
+            Some(default) if !default.is_empty() => write!(output, "{} [{}]: ", prompt, default)?,
+            _ => write!(output, "{}: ", prompt)?,
+        }
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Err(anyhow!("Setup cancelled: no answer for '{}'.", prompt));
+        }
+
+        match (answer.trim(), default) {
+            ("", Some(default)) => return Ok(default.to_string()),
+            ("", None) => continue,
+            (answer, _) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+fn ask_yes_no<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    prompt: &str,
+    default: bool,
+) -> Result<bool> {
+    let options = if default { "Y/n" } else { "y/N" };
+    loop {
+        let answer = ask(
+            input,
+            output,
+            &format!("{} [{}]", prompt, options),
+            Some(""),
+        )?;
+        match answer.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => writeln!(output, "Please answer 'y' or 'n'.")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    fn temp_home(name: &str) -> PathBuf {
+        let home = env::temp_dir().join(format!("timecard_init_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        home
+    }
+
+    #[tokio::test]
+    async fn test_init_local_database() -> Result<()> {
+        let home = temp_home("local");
+        let config_path = Config::path_in_home(&home);
+        let data_dir = Config::data_dir_in_home(&home);
+
+        let mut input = Cursor::new("\n\n\ny\nAcme Website\n20 008\n20-008\nAcme\n");
+        let mut output = Vec::new();
+        let config = run(&mut input, &mut output, &config_path, &data_dir).await?;
+
+        let db_path = data_dir.join("timecard.db");
+        assert!(db_path.exists());
+        assert_eq!(
+            config.database,
+            Some(format!("sqlite://{}", db_path.display()))
+        );
+        assert_eq!(config.base_url.as_deref(), Some(DEFAULT_BASE_URL));
+        assert_eq!(Config::load(&config_path)?, Some(config.clone()));
+
+        let pool = SqlitePool::new(config.database.as_ref().unwrap()).await?;
+        let projects = db::read_all_projects(&pool).await?;
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].code, "20-008");
+        assert_eq!(projects[0].client.as_deref(), Some("Acme"));
+
+        let summary = String::from_utf8(output)?;
+        assert!(summary.contains("does not match the pattern"));
+        assert!(summary.contains("Added project Acme Website (20-008)"));
+
+        // Running again finds the config and leaves it alone unless asked.
+        let mut input = Cursor::new("\n");
+        let mut output = Vec::new();
+        let kept = run(&mut input, &mut output, &config_path, &data_dir).await?;
+        assert_eq!(kept, config);
+        assert!(String::from_utf8(output)?.contains("Found existing config"));
+
+        fs::remove_dir_all(&home)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_init_remote_server() -> Result<()> {
+        let home = temp_home("remote");
+        let config_path = Config::path_in_home(&home);
+        let data_dir = Config::data_dir_in_home(&home);
+
+        // A blank server URL is asked for again.
+        let mut input = Cursor::new("n\n\nhttp://timecard.example.com\n");
+        let mut output = Vec::new();
+        let config = run(&mut input, &mut output, &config_path, &data_dir).await?;
+
+        assert_eq!(
+            config.base_url.as_deref(),
+            Some("http://timecard.example.com")
+        );
+        assert_eq!(config.database, None);
+        assert!(!data_dir.exists());
+
+        // Running out of answers cancels rather than looping forever.
+        fs::remove_file(&config_path)?;
+        let mut input = Cursor::new("n\n");
+        assert!(run(&mut input, &mut Vec::new(), &config_path, &data_dir)
+            .await
+            .is_err());
+        assert!(!config_path.exists());
+
+        fs::remove_dir_all(&home)?;
+
+        Ok(())
+    }
+}
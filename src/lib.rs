@@ -1,22 +1,1319 @@
-use fake::{Dummy, Fake};
-use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
 
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+#[cfg(feature = "fake")]
+use fake::faker::company::en::CompanyName;
+#[cfg(feature = "fake")]
+use fake::faker::lorem::en::Sentence;
+#[cfg(feature = "fake")]
+use fake::{Dummy, Fake, Faker};
+use lazy_static::lazy_static;
+#[cfg(feature = "fake")]
+use rand::Rng;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::error::TimecardError;
+use crate::time::DATE_FORMAT;
+
+#[cfg(feature = "api")]
 pub mod api;
+pub mod breaks;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod config;
+#[cfg(feature = "db")]
 pub mod db;
+pub mod error;
+#[cfg(all(feature = "server", feature = "sentry"))]
+pub mod error_reporting;
+pub mod ics;
+#[cfg(feature = "db")]
+pub mod init;
+pub mod locale;
+pub mod remind;
+pub mod report;
+pub mod spec;
+#[cfg(feature = "db")]
+pub mod storage;
+#[cfg(feature = "server")]
+pub mod telemetry;
+pub mod time;
 
-#[derive(Debug, Dummy, Clone, PartialEq, Serialize, Deserialize)]
+/// Header carrying the id that ties a client's request to the server's logs.
+pub static REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Entry {
     pub id: Option<i32>,
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub start: String,
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub stop: String,
-    pub week_day: String,
-    pub code: String,
+    pub week_day: Weekday,
+    pub code: ProjectCode,
     pub memo: String,
 }
 
-#[derive(Debug, Dummy, Clone, PartialEq, Serialize, Deserialize)]
+impl Entry {
+    /// Parsed start and stop times.
+    pub fn interval(&self) -> Result<(NaiveDateTime, NaiveDateTime)> {
+        let start = NaiveDateTime::parse_from_str(&self.start, DATE_FORMAT)
+            .with_context(|| format!("Invalid start time: {}", self.start))?;
+        let stop = NaiveDateTime::parse_from_str(&self.stop, DATE_FORMAT)
+            .with_context(|| format!("Invalid stop time: {}", self.stop))?;
+
+        Ok((start, stop))
+    }
+
+    /// Whether the two entries share any time. Entries that only touch, one
+    /// stopping as the other starts, don't overlap.
+    pub fn overlaps(&self, other: &Entry) -> Result<bool> {
+        let (start, stop) = self.interval()?;
+        let (other_start, other_stop) = other.interval()?;
+
+        Ok(start < other_stop && other_start < stop)
+    }
+
+    /// Renders `start` and `stop` in the given wire format.
+    pub fn with_timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.start = format_timestamp(&self.start, format);
+        self.stop = format_timestamp(&self.stop, format);
+        self
+    }
+}
+
+/// An entry that hasn't been stored yet. The database assigns the id, so
+/// payloads that try to set one are rejected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewEntry {
+    #[serde(deserialize_with = "deserialize_timestamp")]
+    pub start: String,
+    #[serde(deserialize_with = "deserialize_timestamp")]
+    pub stop: String,
+    pub week_day: Weekday,
+    pub code: ProjectCode,
+    pub memo: String,
+}
+
+impl NewEntry {
+    pub fn builder() -> NewEntryBuilder {
+        NewEntryBuilder::default()
+    }
+}
+
+/// Builds a `NewEntry` from chrono types, formatting the timestamps and
+/// deriving `week_day` from the date.
+#[derive(Debug, Clone, Default)]
+pub struct NewEntryBuilder {
+    date: Option<NaiveDate>,
+    start_time: Option<NaiveTime>,
+    stop_time: Option<NaiveTime>,
+    code: Option<String>,
+    memo: Option<String>,
+}
+
+impl NewEntryBuilder {
+    pub fn date(mut self, date: NaiveDate) -> Self {
+        self.date = Some(date);
+        self
+    }
+
+    pub fn start_time(mut self, time: NaiveTime) -> Self {
+        self.start_time = Some(time);
+        self
+    }
+
+    pub fn stop_time(mut self, time: NaiveTime) -> Self {
+        self.stop_time = Some(time);
+        self
+    }
+
+    pub fn code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    pub fn memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = Some(memo.into());
+        self
+    }
+
+    /// Fails if any field is missing or the entry doesn't stop after it starts.
+    pub fn build(self) -> Result<NewEntry> {
+        let date = self.date.context("Entry is missing a date.")?;
+        let start = self.start_time.context("Entry is missing a start time.")?;
+        let stop = self.stop_time.context("Entry is missing a stop time.")?;
+        let code = self.code.context("Entry is missing a project code.")?;
+        let code = ProjectCode::new(&code)?;
+        let memo = self.memo.context("Entry is missing a memo.")?;
+
+        if stop <= start {
+            return Err(anyhow!(
+                "Stop time {} must be after start time {}.",
+                stop.format("%H:%M"),
+                start.format("%H:%M")
+            ));
+        }
+
+        Ok(NewEntry {
+            start: date.and_time(start).format(DATE_FORMAT).to_string(),
+            stop: date.and_time(stop).format(DATE_FORMAT).to_string(),
+            week_day: date.weekday().into(),
+            code,
+            memo,
+        })
+    }
+}
+
+impl From<NewEntry> for Entry {
+    fn from(entry: NewEntry) -> Self {
+        Entry {
+            id: None,
+            start: entry.start,
+            stop: entry.stop,
+            week_day: entry.week_day,
+            code: entry.code,
+            memo: entry.memo,
+        }
+    }
+}
+
+/// An entry that passes validation: up to four hours on a quarter hour
+/// between 6:00 and 20:00 on one of the last 90 days. Its code follows the
+/// default pattern, and isn't checked against a custom one.
+#[cfg(feature = "fake")]
+impl Dummy<Faker> for NewEntry {
+    fn dummy_with_rng<R: Rng + ?Sized>(_: &Faker, rng: &mut R) -> Self {
+        let date = Local::today().naive_local() - Duration::days(rng.gen_range(0, 90));
+        let start = NaiveTime::from_hms(6, 0, 0) + Duration::minutes(rng.gen_range(0, 40) * 15);
+        let stop = start + Duration::minutes(rng.gen_range(1, 17) * 15);
+
+        // Not built with `NewEntryBuilder`, which would reject the code under
+        // a `TIMECARD_CODE_PATTERN` it doesn't match.
+        NewEntry {
+            start: date.and_time(start).format(DATE_FORMAT).to_string(),
+            stop: date.and_time(stop).format(DATE_FORMAT).to_string(),
+            week_day: date.weekday().into(),
+            code: Faker.fake_with_rng(rng),
+            memo: Sentence(2..6).fake_with_rng(rng),
+        }
+    }
+}
+
+/// A valid entry with an id, as if read back from the database.
+#[cfg(feature = "fake")]
+impl Dummy<Faker> for Entry {
+    fn dummy_with_rng<R: Rng + ?Sized>(_: &Faker, rng: &mut R) -> Self {
+        Entry {
+            id: Some(rng.gen_range(1, 10_000)),
+            ..Faker.fake_with_rng::<NewEntry, _>(rng).into()
+        }
+    }
+}
+
+/// Untracked time between entries, which must be sorted by start time. Time
+/// covered by any earlier entry, including one that overlaps later ones, is
+/// not a gap.
+pub fn gaps(entries: &[Entry]) -> Result<Vec<(NaiveDateTime, NaiveDateTime)>> {
+    let mut gaps = Vec::new();
+    let mut previous: Option<(NaiveDateTime, NaiveDateTime)> = None;
+
+    for entry in entries {
+        let (start, stop) = entry.interval()?;
+        if let Some((previous_start, covered_until)) = previous {
+            if start < previous_start {
+                return Err(anyhow!("Entries must be sorted by start time."));
+            }
+            if start > covered_until {
+                gaps.push((covered_until, start));
+            }
+            previous = Some((start, covered_until.max(stop)));
+        } else {
+            previous = Some((start, stop));
+        }
+    }
+
+    Ok(gaps)
+}
+
+/// How entry timestamps are written on the wire. Both are accepted on input.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampFormat {
+    /// `2021-02-03 09:00:00`, as stored.
+    #[default]
+    Plain,
+    /// `2021-02-03T09:00:00-05:00`, using the local offset.
+    Rfc3339,
+}
+
+/// Renders a stored timestamp in `format`. Values that aren't valid stored
+/// timestamps are returned unchanged.
+pub fn format_timestamp(stored: &str, format: TimestampFormat) -> String {
+    match format {
+        TimestampFormat::Plain => stored.to_string(),
+        TimestampFormat::Rfc3339 => NaiveDateTime::parse_from_str(stored, DATE_FORMAT)
+            .ok()
+            .and_then(|time| Local.from_local_datetime(&time).earliest())
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_else(|| stored.to_string()),
+    }
+}
+
+/// Converts an incoming timestamp to the stored format. RFC 3339 values are
+/// shifted to local time; anything else is kept as is.
+pub fn normalize_timestamp(value: &str) -> String {
+    match DateTime::parse_from_rfc3339(value) {
+        Ok(time) => time
+            .with_timezone(&Local)
+            .naive_local()
+            .format(DATE_FORMAT)
+            .to_string(),
+        Err(_) => value.to_string(),
+    }
+}
+
+fn deserialize_timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    String::deserialize(deserializer).map(|value| normalize_timestamp(&value))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Project {
     pub id: Option<i32>,
     pub name: String,
-    pub code: String,
+    pub code: ProjectCode,
+    pub client: Option<String>,
+}
+
+/// A stored project named after a company, belonging to another one half the
+/// time.
+#[cfg(feature = "fake")]
+impl Dummy<Faker> for Project {
+    fn dummy_with_rng<R: Rng + ?Sized>(_: &Faker, rng: &mut R) -> Self {
+        Project {
+            id: Some(rng.gen_range(1, 10_000)),
+            name: CompanyName().fake_with_rng(rng),
+            code: Faker.fake_with_rng(rng),
+            client: if rng.gen() {
+                Some(CompanyName().fake_with_rng(rng))
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Pattern codes must match once normalized, unless overridden with
+/// `TIMECARD_CODE_PATTERN`.
+pub static DEFAULT_CODE_PATTERN: &str = "^[A-Z0-9]+(-[A-Z0-9]+)*$";
+
+lazy_static! {
+    static ref CODE_PATTERN: Regex = {
+        let pattern = env::var("TIMECARD_CODE_PATTERN");
+        Regex::new(pattern.as_deref().unwrap_or(DEFAULT_CODE_PATTERN))
+            .expect("TIMECARD_CODE_PATTERN must be a valid regular expression!")
+    };
+}
+
+/// A project code, trimmed and uppercased so `20-008 ` and `20-008` are the
+/// same project. Serialized as a plain string.
+///
+/// Deserializing only normalizes: codes stored before validation existed, or
+/// by a server with another `TIMECARD_CODE_PATTERN`, must still be readable.
+/// Input that is about to be stored is checked with `validate`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(into = "String")]
+pub struct ProjectCode(String);
+
+impl ProjectCode {
+    /// Normalizes `code` and checks it against the configured pattern.
+    pub fn new(code: &str) -> Result<Self, TimecardError> {
+        Self::with_pattern(code, &CODE_PATTERN)
+    }
+
+    pub fn with_pattern(code: &str, pattern: &Regex) -> Result<Self, TimecardError> {
+        let code = code.trim().to_uppercase();
+        if !pattern.is_match(&code) {
+            return Err(TimecardError::invalid(
+                "code",
+                format!("'{}' does not match the pattern {}", code, pattern),
+            ));
+        }
+
+        Ok(ProjectCode(code))
+    }
+
+    /// Normalizes a code that was already stored, without validating it, so
+    /// rows written before validation existed can still be read.
+    pub(crate) fn from_stored(code: &str) -> Self {
+        ProjectCode(code.trim().to_uppercase())
+    }
+
+    /// Checks the code against the configured pattern.
+    pub fn validate(&self) -> Result<(), TimecardError> {
+        Self::new(&self.0).map(drop)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for ProjectCode {
+    type Err = TimecardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ProjectCode::new(s)
+    }
+}
+
+impl TryFrom<String> for ProjectCode {
+    type Error = TimecardError;
+
+    fn try_from(code: String) -> Result<Self, Self::Error> {
+        ProjectCode::new(&code)
+    }
+}
+
+impl<'de> Deserialize<'de> for ProjectCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|code| ProjectCode::from_stored(&code))
+    }
+}
+
+impl From<ProjectCode> for String {
+    fn from(code: ProjectCode) -> Self {
+        code.0
+    }
+}
+
+impl fmt::Display for ProjectCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl PartialEq<&str> for ProjectCode {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+#[cfg(feature = "fake")]
+impl Dummy<Faker> for ProjectCode {
+    fn dummy_with_rng<R: Rng + ?Sized>(_: &Faker, rng: &mut R) -> Self {
+        ProjectCode(format!(
+            "{:02}-{:03}",
+            rng.gen_range(0, 100),
+            rng.gen_range(0, 1000)
+        ))
+    }
+}
+
+/// Day of the week an entry falls on, stored as its short English name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Weekday {
+    Sun,
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+}
+
+impl Weekday {
+    /// All days, Sunday first.
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Sun,
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+    ];
+
+    pub fn num_days_from_sunday(self) -> usize {
+        self as usize
+    }
+}
+
+impl FromStr for Weekday {
+    type Err = TimecardError;
+
+    /// Accepts short and full English names in any case, e.g. `Wed`, `WED`
+    /// or `wednesday`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "sun" | "sunday" => Ok(Weekday::Sun),
+            "mon" | "monday" => Ok(Weekday::Mon),
+            "tue" | "tues" | "tuesday" => Ok(Weekday::Tue),
+            "wed" | "weds" | "wednesday" => Ok(Weekday::Wed),
+            "thu" | "thur" | "thurs" | "thursday" => Ok(Weekday::Thu),
+            "fri" | "friday" => Ok(Weekday::Fri),
+            "sat" | "saturday" => Ok(Weekday::Sat),
+            _ => Err(TimecardError::invalid(
+                "week_day",
+                format!("'{}' is not a day of the week", s),
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Weekday {
+    type Error = TimecardError;
+
+    fn try_from(day: String) -> Result<Self, Self::Error> {
+        day.parse()
+    }
+}
+
+impl From<Weekday> for String {
+    fn from(day: Weekday) -> Self {
+        day.to_string()
+    }
+}
+
+impl fmt::Display for Weekday {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(crate::time::WEEKDAYS[self.num_days_from_sunday()])
+    }
+}
+
+impl From<chrono::Weekday> for Weekday {
+    fn from(day: chrono::Weekday) -> Self {
+        Weekday::ALL[day.num_days_from_sunday() as usize]
+    }
+}
+
+impl From<Weekday> for chrono::Weekday {
+    fn from(day: Weekday) -> Self {
+        match day {
+            Weekday::Sun => chrono::Weekday::Sun,
+            Weekday::Mon => chrono::Weekday::Mon,
+            Weekday::Tue => chrono::Weekday::Tue,
+            Weekday::Wed => chrono::Weekday::Wed,
+            Weekday::Thu => chrono::Weekday::Thu,
+            Weekday::Fri => chrono::Weekday::Fri,
+            Weekday::Sat => chrono::Weekday::Sat,
+        }
+    }
+}
+
+#[cfg(feature = "fake")]
+impl Dummy<Faker> for Weekday {
+    fn dummy_with_rng<R: Rng + ?Sized>(_: &Faker, rng: &mut R) -> Self {
+        Weekday::ALL[rng.gen_range(0, 7)]
+    }
+}
+
+/// The most recent change to the entries table, with enough information to
+/// reverse it. Only one record is kept.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum UndoRecord {
+    /// Newly written entries, undone by deleting them.
+    Created(Vec<Entry>),
+    /// Entries as they were before an update, undone by restoring them.
+    Updated(Vec<Entry>),
+    /// Deleted entries, undone by re-inserting them with their original ids.
+    Deleted(Vec<Entry>),
+}
+
+/// Version of the `Export` document format, bumped whenever its shape changes.
+/// Version 1 documents, written before `archived`, are still accepted.
+pub const EXPORT_VERSION: u32 = 2;
+
+/// Every project and entry in a database, for moving them to another one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Export {
+    pub version: u32,
+    pub projects: Vec<Project>,
+    pub entries: Vec<Entry>,
+    /// Entries moved to the archive, restored there by an import.
+    #[serde(default)]
+    pub archived: Vec<Entry>,
+}
+
+/// Entries newer than a polling client's cursor, oldest first, and the id to
+/// send as the next cursor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatestEntries {
+    pub entries: Vec<Entry>,
+    /// The highest id seen, or the cursor itself when nothing is new.
+    pub max_id: Option<i32>,
+}
+
+/// How many projects, entries and archived entries an import added.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub projects: usize,
+    pub entries: usize,
+    pub archived: usize,
+}
+
+/// A duration displayed the way people write it: `1h 40m`, `45m` or `2d 3h`.
+/// Spans of a day or more drop the minutes. Negative durations keep a leading
+/// minus; callers showing an entry's length should flag them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HumanDuration(pub Duration);
+
+impl HumanDuration {
+    pub fn is_negative(&self) -> bool {
+        self.0 < Duration::zero()
+    }
+}
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let minutes = self.0.num_minutes();
+        // Under a minute either way is shown as `0m`, never `-0m`.
+        let sign = if minutes < 0 { "-" } else { "" };
+        let minutes = minutes.abs();
+        let (days, hours, minutes) = (minutes / 1440, minutes % 1440 / 60, minutes % 60);
+
+        match (days, hours, minutes) {
+            (0, 0, m) => write!(f, "{}{}m", sign, m),
+            (0, h, 0) => write!(f, "{}{}h", sign, h),
+            (0, h, m) => write!(f, "{}{}h {}m", sign, h, m),
+            (d, 0, _) => write!(f, "{}{}d", sign, d),
+            (d, h, _) => write!(f, "{}{}d {}h", sign, d, h),
+        }
+    }
+}
+
+impl FromStr for HumanDuration {
+    type Err = TimecardError;
+
+    /// Accepts an optional sign followed by days, hours and minutes in that
+    /// order, e.g. `+1h30`, `1h 30m`, `45m`, `2d 3h` or `90`. A bare trailing
+    /// number counts as minutes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            TimecardError::invalid(
+                "duration",
+                format!("'{}' is not a duration like 1h30, 45m or 2d 3h", s),
+            )
+        };
+
+        let s = s.trim();
+        let (sign, rest) = match s.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, s.strip_prefix('+').unwrap_or(s)),
+        };
+
+        let mut minutes: i64 = 0;
+        let mut number = String::new();
+        let mut last_unit: Option<i64> = None;
+        for c in rest.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            if c.is_whitespace() && number.is_empty() {
+                continue;
+            }
+
+            let unit = match c.to_ascii_lowercase() {
+                'd' => 1440,
+                'h' => 60,
+                'm' => 1,
+                _ => return Err(invalid()),
+            };
+            if number.is_empty() || last_unit.map_or(false, |last| unit >= last) {
+                return Err(invalid());
+            }
+            let value: i64 = number.parse().map_err(|_| invalid())?;
+            minutes = value
+                .checked_mul(unit)
+                .and_then(|m| m.checked_add(minutes))
+                .ok_or_else(invalid)?;
+            last_unit = Some(unit);
+            number.clear();
+        }
+
+        if !number.is_empty() {
+            if last_unit.map_or(false, |last| last != 60) {
+                return Err(invalid());
+            }
+            let value: i64 = number.parse().map_err(|_| invalid())?;
+            minutes = minutes.checked_add(value).ok_or_else(invalid)?;
+        } else if last_unit.is_none() {
+            return Err(invalid());
+        }
+
+        // Duration::minutes panics past about 1.5e14 minutes.
+        let millis = minutes.checked_mul(60_000).ok_or_else(invalid)?;
+        Ok(HumanDuration(Duration::milliseconds(sign * millis)))
+    }
+}
+
+/// Seven consecutive days beginning on a configurable week day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Week {
+    begin: NaiveDate,
+}
+
+impl Week {
+    /// The week starting on `first_day` that is `weeks_ago` weeks before the
+    /// one containing `today`.
+    ///
+    /// Panics if the week falls outside the dates chrono supports.
+    pub fn new(today: NaiveDate, weeks_ago: i64, first_day: chrono::Weekday) -> Self {
+        Week::checked_new(today, weeks_ago, first_day).expect("week out of range")
+    }
+
+    /// Like `new`, but `None` if the week falls outside the dates chrono
+    /// supports.
+    pub fn checked_new(
+        today: NaiveDate,
+        weeks_ago: i64,
+        first_day: chrono::Weekday,
+    ) -> Option<Self> {
+        let offset = weeks_ago
+            .checked_mul(7)?
+            .checked_add(time::weekday_offset(today.weekday(), first_day))?;
+        let begin = i64::from(today.num_days_from_ce()).checked_sub(offset)?;
+        let begin = NaiveDate::from_num_days_from_ce_opt(i32::try_from(begin).ok()?)?;
+        Week::starting(begin)
+    }
+
+    /// ISO 8601 week `week` of `year`, which starts on Monday.
+    pub fn iso(year: i32, week: u32) -> Option<Self> {
+        NaiveDate::from_isoywd_opt(year, week, chrono::Weekday::Mon).and_then(Week::starting)
+    }
+
+    /// The week beginning on `begin`, if all of its days exist.
+    fn starting(begin: NaiveDate) -> Option<Self> {
+        begin.checked_add_signed(Duration::days(6))?;
+        Some(Week { begin })
+    }
+
+    /// Reads a number of weeks ago (`0` is the current week), a date in the
+    /// week, or an ISO week such as `2021-W05`. ISO weeks always start on
+    /// Monday.
+    pub fn resolve(
+        spec: &str,
+        today: NaiveDate,
+        first_day: chrono::Weekday,
+    ) -> Result<Self, TimecardError> {
+        let spec = spec.trim();
+        let week = if let Ok(weeks_ago) = spec.parse::<i64>() {
+            Week::checked_new(today, weeks_ago, first_day)
+        } else if let Ok(date) = NaiveDate::parse_from_str(spec, "%Y-%m-%d") {
+            Week::checked_new(date, 0, first_day)
+        } else {
+            return spec.parse();
+        };
+
+        week.ok_or_else(|| TimecardError::invalid("week", format!("'{}' is out of range", spec)))
+    }
+
+    pub fn begin(&self) -> NaiveDate {
+        self.begin
+    }
+
+    pub fn first_day(&self) -> chrono::Weekday {
+        self.begin.weekday()
+    }
+
+    pub fn end(&self) -> NaiveDate {
+        self.begin + Duration::days(6)
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.begin <= date && date <= self.end()
+    }
+
+    /// The week's days in order, starting with its first day.
+    pub fn days(&self) -> [Weekday; 7] {
+        let first = self.begin.weekday().num_days_from_sunday() as usize;
+        let mut days = Weekday::ALL;
+        days.rotate_left(first);
+        days
+    }
+
+    /// The ISO week most of the days fall in, e.g. `2021-W05`.
+    pub fn label(&self) -> String {
+        let iso = (self.begin + Duration::days(3)).iso_week();
+        format!("{}-W{:02}", iso.year(), iso.week())
+    }
+}
+
+impl fmt::Display for Week {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.label())
+    }
+}
+
+impl FromStr for Week {
+    type Err = TimecardError;
+
+    /// Accepts ISO weeks such as `2021-W05` or `2021-w5`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            TimecardError::invalid(
+                "week",
+                format!(
+                    "'{}' is not a number of weeks ago, a date or an ISO week like 2021-W05",
+                    s
+                ),
+            )
+        };
+
+        let (year, week) = s
+            .trim()
+            .split_once(|c| c == 'W' || c == 'w')
+            .ok_or_else(invalid)?;
+        let year = year
+            .strip_suffix('-')
+            .unwrap_or(year)
+            .parse()
+            .map_err(|_| invalid())?;
+        let week = week.parse().map_err(|_| invalid())?;
+
+        Week::iso(year, week).ok_or_else(invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn builder() -> NewEntryBuilder {
+        NewEntry::builder()
+            .date(NaiveDate::from_ymd(2021, 2, 3))
+            .start_time(NaiveTime::from_hms(9, 0, 0))
+            .stop_time(NaiveTime::from_hms(17, 30, 0))
+            .code("20-008")
+            .memo("work, work, work")
+    }
+
+    #[test]
+    fn test_build_entry() -> Result<()> {
+        let entry = builder().build()?;
+
+        assert_eq!(
+            entry,
+            NewEntry {
+                start: "2021-02-03 09:00:00".to_string(),
+                stop: "2021-02-03 17:30:00".to_string(),
+                week_day: Weekday::Wed,
+                code: ProjectCode::new("20-008")?,
+                memo: "work, work, work".to_string(),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_missing_fields() {
+        let missing = |builder: NewEntryBuilder| builder.build().unwrap_err().to_string();
+
+        assert_eq!(
+            missing(NewEntryBuilder {
+                date: None,
+                ..builder()
+            }),
+            "Entry is missing a date."
+        );
+        assert_eq!(
+            missing(NewEntryBuilder {
+                start_time: None,
+                ..builder()
+            }),
+            "Entry is missing a start time."
+        );
+        assert_eq!(
+            missing(NewEntryBuilder {
+                stop_time: None,
+                ..builder()
+            }),
+            "Entry is missing a stop time."
+        );
+        assert_eq!(
+            missing(NewEntryBuilder {
+                code: None,
+                ..builder()
+            }),
+            "Entry is missing a project code."
+        );
+        assert_eq!(
+            missing(NewEntryBuilder {
+                memo: None,
+                ..builder()
+            }),
+            "Entry is missing a memo."
+        );
+    }
+
+    #[test]
+    fn test_build_normalizes_code() -> Result<()> {
+        let entry = builder().code(" 20-008x ").build()?;
+        assert_eq!(entry.code, "20-008X");
+
+        assert!(builder().code("20 008").build().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_project_code_normalization() -> Result<()> {
+        assert_eq!(ProjectCode::new("20-008 ")?, ProjectCode::new("20-008")?);
+        assert_eq!(ProjectCode::new("  ab-1")?.as_str(), "AB-1");
+        assert_eq!("20-000-00".parse::<ProjectCode>()?.to_string(), "20-000-00");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_project_code_rejects_invalid() -> Result<()> {
+        for code in &["", "   ", "20 008", "20--008", "-20", "20-008-", "20/008"] {
+            assert!(ProjectCode::new(code).is_err(), "{:?}", code);
+        }
+
+        let digits_only = Regex::new(r"^\d{2}-\d{3}$")?;
+        assert!(ProjectCode::with_pattern("20-008", &digits_only).is_ok());
+        assert!(ProjectCode::with_pattern("AB-008", &digits_only).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_project_code_serde() -> Result<()> {
+        let code = ProjectCode::new("20-008")?;
+        assert_eq!(serde_json::to_string(&code)?, "\"20-008\"");
+        assert_eq!(serde_json::from_str::<ProjectCode>("\" 20-008\"")?, code);
+
+        // Legacy codes still deserialize, but don't validate.
+        let legacy = serde_json::from_str::<ProjectCode>("\"20 008\"")?;
+        assert_eq!(legacy.as_str(), "20 008");
+        assert!(legacy.validate().is_err());
+
+        #[cfg(feature = "fake")]
+        {
+            let project: Project = Faker.fake();
+            let json = serde_json::to_string(&project)?;
+            assert_eq!(serde_json::from_str::<Project>(&json)?, project);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "fake")]
+    #[test]
+    fn test_fake_data_is_valid() -> Result<()> {
+        let today = Local::today().naive_local();
+        for _ in 0..100 {
+            let entry: Entry = Faker.fake();
+            let (start, stop) = entry.interval()?;
+            assert!(start < stop);
+            assert_eq!(start.date(), stop.date());
+            assert!(start.date() <= today);
+            assert_eq!(entry.week_day, Weekday::from(start.weekday()));
+            assert_eq!(ProjectCode::new(entry.code.as_str())?, entry.code);
+            assert!(!entry.memo.is_empty());
+
+            let json = serde_json::to_string(&entry)?;
+            assert_eq!(serde_json::from_str::<Entry>(&json)?, entry);
+
+            let project: Project = Faker.fake();
+            assert_eq!(ProjectCode::new(project.code.as_str())?, project.code);
+            assert!(!project.name.is_empty());
+        }
+
+        Ok(())
+    }
+
+    fn stored_entry() -> Entry {
+        Entry {
+            id: Some(1),
+            start: "2021-02-03 09:00:00".to_string(),
+            stop: "2021-02-03 17:30:00".to_string(),
+            week_day: Weekday::Wed,
+            code: "20-008".parse().unwrap(),
+            memo: "work, work, work".to_string(),
+        }
+    }
+
+    fn timed(start: &str, stop: &str) -> Entry {
+        Entry {
+            start: format!("2021-02-03 {}:00", start),
+            stop: format!("2021-02-03 {}:00", stop),
+            ..stored_entry()
+        }
+    }
+
+    fn at(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("2021-02-03 {}:00", time), DATE_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn test_interval() -> Result<()> {
+        assert_eq!(
+            timed("09:00", "10:30").interval()?,
+            (at("09:00"), at("10:30"))
+        );
+
+        let mut malformed = timed("09:00", "10:00");
+        malformed.start = "0900".to_string();
+        assert!(malformed.interval().is_err());
+
+        let mut malformed = timed("09:00", "10:00");
+        malformed.stop = "2021-02-03T10:00:00".to_string();
+        assert!(malformed.interval().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_overlaps() -> Result<()> {
+        let morning = timed("09:00", "12:00");
+
+        // Partial overlap, either way round.
+        assert!(morning.overlaps(&timed("11:00", "13:00"))?);
+        assert!(timed("11:00", "13:00").overlaps(&morning)?);
+
+        // Containment and identical intervals.
+        assert!(morning.overlaps(&timed("10:00", "11:00"))?);
+        assert!(timed("10:00", "11:00").overlaps(&morning)?);
+        assert!(morning.overlaps(&morning)?);
+
+        // Touching endpoints and disjoint intervals.
+        assert!(!morning.overlaps(&timed("12:00", "13:00"))?);
+        assert!(!timed("08:00", "09:00").overlaps(&morning)?);
+        assert!(!morning.overlaps(&timed("14:00", "15:00"))?);
+
+        let mut malformed = timed("10:00", "11:00");
+        malformed.stop = "1100".to_string();
+        assert!(morning.overlaps(&malformed).is_err());
+        assert!(malformed.overlaps(&morning).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gaps() -> Result<()> {
+        assert!(gaps(&[])?.is_empty());
+        assert!(gaps(&[timed("09:00", "10:00")])?.is_empty());
+
+        let day = vec![
+            timed("09:00", "10:00"),
+            // Touching the previous entry leaves no gap.
+            timed("10:00", "12:00"),
+            timed("13:00", "17:00"),
+            // Contained in the previous entry.
+            timed("14:00", "15:00"),
+            timed("16:30", "18:00"),
+        ];
+        assert_eq!(gaps(&day)?, vec![(at("12:00"), at("13:00"))]);
+
+        let contained = vec![
+            timed("09:00", "17:00"),
+            timed("10:00", "11:00"),
+            timed("17:30", "18:00"),
+        ];
+        assert_eq!(gaps(&contained)?, vec![(at("17:00"), at("17:30"))]);
+
+        let unsorted = vec![timed("13:00", "14:00"), timed("09:00", "10:00")];
+        assert!(gaps(&unsorted).is_err());
+
+        let mut malformed = timed("13:00", "14:00");
+        malformed.start = "".to_string();
+        assert!(gaps(&[timed("09:00", "10:00"), malformed]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_weekday_spellings() -> Result<()> {
+        for spelling in &["Wed", "WED", "wed", "Wednesday", "WEDNESDAY", " weds "] {
+            assert_eq!(spelling.parse::<Weekday>()?, Weekday::Wed, "{}", spelling);
+        }
+        assert_eq!("Thurs".parse::<Weekday>()?, Weekday::Thu);
+        assert_eq!("tues".parse::<Weekday>()?, Weekday::Tue);
+
+        for day in &Weekday::ALL {
+            assert_eq!(day.to_string().parse::<Weekday>()?, *day);
+            assert_eq!(Weekday::from(chrono::Weekday::from(*day)), *day);
+        }
+        assert_eq!(Weekday::Sat.to_string(), "Sat");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_weekday_rejects_garbage() {
+        assert!("Wedn".parse::<Weekday>().is_err());
+        assert!("".parse::<Weekday>().is_err());
+        assert!(serde_json::from_str::<Weekday>("\"someday\"").is_err());
+        assert_eq!(serde_json::to_string(&Weekday::Mon).unwrap(), "\"Mon\"");
+        assert_eq!(
+            serde_json::from_str::<Weekday>("\"MONDAY\"").unwrap(),
+            Weekday::Mon
+        );
+    }
+
+    #[test]
+    fn test_plain_timestamps_round_trip() -> Result<()> {
+        let entry = stored_entry();
+        let json =
+            serde_json::to_string(&entry.clone().with_timestamp_format(TimestampFormat::Plain))?;
+
+        assert!(json.contains("\"2021-02-03 09:00:00\""));
+        assert_eq!(serde_json::from_str::<Entry>(&json)?, entry);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rfc3339_timestamps_round_trip() -> Result<()> {
+        let entry = stored_entry();
+        let rfc3339 = entry
+            .clone()
+            .with_timestamp_format(TimestampFormat::Rfc3339);
+
+        assert!(DateTime::parse_from_rfc3339(&rfc3339.start).is_ok());
+        assert!(rfc3339.stop.starts_with("2021-02-03T17:30:00"));
+
+        let json = serde_json::to_string(&rfc3339)?;
+        assert_eq!(serde_json::from_str::<Entry>(&json)?, entry);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rfc3339_input_is_shifted_to_local_time() -> Result<()> {
+        let utc = "2021-02-03T09:00:00Z";
+        let local = DateTime::parse_from_rfc3339(utc)?
+            .with_timezone(&Local)
+            .format(DATE_FORMAT)
+            .to_string();
+        assert_eq!(normalize_timestamp(utc), local);
+
+        let json = format!(
+            r#"{{"start":"{}","stop":"2021-02-03 17:30:00","week_day":"Wed","code":"20-008","memo":""}}"#,
+            utc
+        );
+        assert_eq!(serde_json::from_str::<NewEntry>(&json)?.start, local);
+
+        // Anything else is left for the caller to deal with.
+        assert_eq!(normalize_timestamp("0900"), "0900");
+        assert_eq!(format_timestamp("0900", TimestampFormat::Rfc3339), "0900");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_stop_before_start() {
+        let same = builder().stop_time(NaiveTime::from_hms(9, 0, 0)).build();
+        assert!(same.is_err());
+
+        let before = builder().stop_time(NaiveTime::from_hms(8, 0, 0)).build();
+        assert_eq!(
+            before.unwrap_err().to_string(),
+            "Stop time 08:00 must be after start time 09:00."
+        );
+    }
+
+    #[test]
+    fn test_week_bounds() {
+        // 2021-02-03 was a Wednesday.
+        let today = NaiveDate::from_ymd(2021, 2, 3);
+
+        let week = Week::new(today, 0, chrono::Weekday::Sun);
+        assert_eq!(week.begin(), NaiveDate::from_ymd(2021, 1, 31));
+        assert_eq!(week.end(), NaiveDate::from_ymd(2021, 2, 6));
+        assert_eq!(week.days()[0], Weekday::Sun);
+
+        let week = Week::new(today, 1, chrono::Weekday::Mon);
+        assert_eq!(week.begin(), NaiveDate::from_ymd(2021, 1, 25));
+        assert_eq!(week.days()[6], Weekday::Sun);
+        assert!(week.contains(NaiveDate::from_ymd(2021, 1, 31)));
+        assert!(!week.contains(today));
+    }
+
+    #[test]
+    fn test_week_resolve() -> Result<()> {
+        let today = NaiveDate::from_ymd(2021, 2, 3);
+
+        let week = Week::resolve("0", today, chrono::Weekday::Sun)?;
+        assert_eq!(week.first_day(), chrono::Weekday::Sun);
+        assert_eq!(week.label(), "2021-W05");
+
+        let week = Week::resolve("2021-W05", today, chrono::Weekday::Sun)?;
+        assert_eq!(week.begin(), NaiveDate::from_ymd(2021, 2, 1));
+        assert_eq!(week.first_day(), chrono::Weekday::Mon);
+        assert_eq!(week, Week::new(today, 0, chrono::Weekday::Mon));
+
+        let week = Week::resolve("2021-01-27", today, chrono::Weekday::Mon)?;
+        assert_eq!(week.begin(), NaiveDate::from_ymd(2021, 1, 25));
+
+        assert!(Week::resolve("last week", today, chrono::Weekday::Sun).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_iso_weeks() -> Result<()> {
+        // 2020 has 53 ISO weeks, the last of which ends in 2021.
+        let week: Week = "2020-W53".parse()?;
+        assert_eq!(week.begin(), NaiveDate::from_ymd(2020, 12, 28));
+        assert_eq!(week.end(), NaiveDate::from_ymd(2021, 1, 3));
+        assert_eq!(week.to_string(), "2020-W53");
+
+        let week: Week = "2021-w1".parse()?;
+        assert_eq!(week.begin(), NaiveDate::from_ymd(2021, 1, 4));
+        assert_eq!(week.label(), "2021-W01");
+
+        // Week 1 of 2015 starts in 2014.
+        assert_eq!(
+            "2015W01".parse::<Week>()?.begin(),
+            NaiveDate::from_ymd(2014, 12, 29)
+        );
+
+        assert!("2021-W53".parse::<Week>().is_err());
+        assert!("2021-W00".parse::<Week>().is_err());
+        assert!("2021-05".parse::<Week>().is_err());
+
+        // A Sunday week is labelled by the ISO week of its Monday to Saturday.
+        let week = Week::new(NaiveDate::from_ymd(2021, 1, 2), 0, chrono::Weekday::Sun);
+        assert_eq!(week.begin(), NaiveDate::from_ymd(2020, 12, 27));
+        assert_eq!(week.label(), "2020-W53");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_human_duration_display() {
+        let display = |minutes| HumanDuration(Duration::minutes(minutes)).to_string();
+
+        assert_eq!(display(0), "0m");
+        assert_eq!(display(45), "45m");
+        assert_eq!(display(60), "1h");
+        assert_eq!(display(100), "1h 40m");
+        assert_eq!(display(1440), "1d");
+        assert_eq!(display(2 * 1440 + 3 * 60 + 20), "2d 3h");
+        assert_eq!(display(-100), "-1h 40m");
+        assert_eq!(HumanDuration(Duration::seconds(-30)).to_string(), "0m");
+        assert!(HumanDuration(Duration::minutes(-1)).is_negative());
+    }
+
+    #[test]
+    fn test_human_duration_parse() -> Result<()> {
+        let minutes = |s: &str| -> Result<i64> { Ok(s.parse::<HumanDuration>()?.0.num_minutes()) };
+
+        assert_eq!(minutes("+1h30")?, 90);
+        assert_eq!(minutes("1h30m")?, 90);
+        assert_eq!(minutes("1H 30M")?, 90);
+        assert_eq!(minutes("45m")?, 45);
+        assert_eq!(minutes("90")?, 90);
+        assert_eq!(minutes("2d 3h")?, 2 * 1440 + 180);
+        assert_eq!(minutes("-15m")?, -15);
+
+        for bad in &["", "+", "h", "1x", "30m1h", "1h1h", "1d30", "1 30", "1.5h"] {
+            assert!(bad.parse::<HumanDuration>().is_err(), "{}", bad);
+        }
+
+        for text in &["45m", "1h", "1h 40m", "2d", "2d 3h", "-1h 40m"] {
+            assert_eq!(text.parse::<HumanDuration>()?.to_string(), *text);
+        }
+
+        Ok(())
+    }
+
+    /// Only compiled by `cargo test --no-default-features --features db`, so
+    /// that run fails to build if the library starts needing the server, CLI
+    /// or fake-data dependencies.
+    #[cfg(all(feature = "db", not(feature = "api"), not(feature = "fake")))]
+    #[test]
+    fn test_db_only_build() -> Result<()> {
+        let _setup = db::setup_db;
+        let entry: Entry = NewEntry::builder()
+            .date(NaiveDate::from_ymd(2021, 2, 3))
+            .start_time(NaiveTime::from_hms(9, 0, 0))
+            .stop_time(NaiveTime::from_hms(10, 0, 0))
+            .code("20-008")
+            .memo("")
+            .build()?
+            .into();
+        assert_eq!(entry.week_day, Weekday::Wed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        // Found by prop_week_resolve_never_panics and
+        // prop_human_duration_never_panics.
+        let today = NaiveDate::from_ymd(2021, 2, 3);
+        for spec in &["9223372036854775807", "-9223372036854775808", "100000000"] {
+            assert!(Week::resolve(spec, today, chrono::Weekday::Sun).is_err());
+        }
+        let last = chrono::naive::MAX_DATE;
+        assert!(Week::checked_new(last, 0, last.weekday()).is_none());
+        assert!("99999999999999d".parse::<HumanDuration>().is_err());
+    }
+
+    /// Every date from 0001-01-01 to 9999-12-31.
+    fn any_date() -> impl Strategy<Value = NaiveDate> {
+        (1i32..3_652_059).prop_map(NaiveDate::from_num_days_from_ce)
+    }
+
+    fn any_weekday() -> impl Strategy<Value = chrono::Weekday> {
+        (0usize..7).prop_map(|day| Weekday::ALL[day].into())
+    }
+
+    proptest! {
+        #[test]
+        fn prop_entry_week_day(date in any_date()) {
+            let entry = NewEntry::builder()
+                .date(date)
+                .start_time(NaiveTime::from_hms(9, 0, 0))
+                .stop_time(NaiveTime::from_hms(10, 0, 0))
+                .code("20-008")
+                .memo("work, work, work")
+                .build()
+                .unwrap();
+            prop_assert_eq!(entry.week_day, Weekday::from(date.weekday()));
+            prop_assert!(entry.start.starts_with(&date.format("%Y-%m-%d").to_string()));
+        }
+
+        #[test]
+        fn prop_week_contains_date(date in any_date(), first_day in any_weekday()) {
+            let spec = date.format("%Y-%m-%d").to_string();
+            let week = Week::resolve(&spec, date, first_day).unwrap();
+            prop_assert!(week.contains(date));
+            prop_assert_eq!(week.first_day(), first_day);
+            prop_assert_eq!(Week::resolve("0", date, first_day).unwrap(), week);
+        }
+
+        #[test]
+        fn prop_week_resolve_never_panics(spec in "\\PC*", weeks_ago in any::<i64>()) {
+            let today = NaiveDate::from_ymd(2021, 2, 3);
+            let _ = Week::resolve(&spec, today, chrono::Weekday::Sun);
+            let _ = Week::resolve(&weeks_ago.to_string(), today, chrono::Weekday::Sun);
+        }
+
+        #[test]
+        fn prop_human_duration_round_trip(minutes in -1439i64..1440) {
+            let duration = HumanDuration(Duration::minutes(minutes));
+            prop_assert_eq!(duration.to_string().parse::<HumanDuration>().unwrap(), duration);
+        }
+
+        #[test]
+        fn prop_human_duration_never_panics(input in "\\PC*", days in any::<u64>()) {
+            let _ = input.parse::<HumanDuration>();
+            let _ = format!("{}d", days).parse::<HumanDuration>();
+        }
+    }
 }
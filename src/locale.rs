@@ -0,0 +1,174 @@
+// Std
+use std::str::FromStr;
+
+// Crates
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, NaiveDate, Weekday};
+
+/// Languages reports can be rendered in. Only display text is translated;
+/// stored values such as `week_day` stay in English.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+    De,
+    Es,
+}
+
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+
+    /// Accepts a language code with an optional region, e.g. `fr`, `fr_CA` or `fr-FR`.
+    fn from_str(s: &str) -> Result<Self> {
+        let language = s.split(&['_', '-'][..]).next().unwrap_or("");
+        match language.to_lowercase().as_str() {
+            "en" => Ok(Locale::En),
+            "fr" => Ok(Locale::Fr),
+            "de" => Ok(Locale::De),
+            "es" => Ok(Locale::Es),
+            _ => Err(anyhow!("Unsupported locale '{}'.", s)),
+        }
+    }
+}
+
+impl Locale {
+    /// Abbreviated week day names, Sunday first.
+    fn weekday_names(self) -> [&'static str; 7] {
+        match self {
+            Locale::En => ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"],
+            Locale::Fr => ["dim.", "lun.", "mar.", "mer.", "jeu.", "ven.", "sam."],
+            Locale::De => ["So", "Mo", "Di", "Mi", "Do", "Fr", "Sa"],
+            Locale::Es => ["dom", "lun", "mar", "mié", "jue", "vie", "sáb"],
+        }
+    }
+
+    fn month_names(self) -> [&'static str; 12] {
+        match self {
+            Locale::En => [
+                "January",
+                "February",
+                "March",
+                "April",
+                "May",
+                "June",
+                "July",
+                "August",
+                "September",
+                "October",
+                "November",
+                "December",
+            ],
+            Locale::Fr => [
+                "janvier",
+                "février",
+                "mars",
+                "avril",
+                "mai",
+                "juin",
+                "juillet",
+                "août",
+                "septembre",
+                "octobre",
+                "novembre",
+                "décembre",
+            ],
+            Locale::De => [
+                "Januar",
+                "Februar",
+                "März",
+                "April",
+                "Mai",
+                "Juni",
+                "Juli",
+                "August",
+                "September",
+                "Oktober",
+                "November",
+                "Dezember",
+            ],
+            Locale::Es => [
+                "enero",
+                "febrero",
+                "marzo",
+                "abril",
+                "mayo",
+                "junio",
+                "julio",
+                "agosto",
+                "septiembre",
+                "octubre",
+                "noviembre",
+                "diciembre",
+            ],
+        }
+    }
+
+    pub fn weekday_name(self, day: Weekday) -> &'static str {
+        self.weekday_names()[day.num_days_from_sunday() as usize]
+    }
+
+    /// Month name for a 1-based month number.
+    pub fn month_name(self, month: u32) -> &'static str {
+        self.month_names()[month as usize - 1]
+    }
+
+    /// The day weeks start on by default in this locale.
+    pub fn first_weekday(self) -> Weekday {
+        match self {
+            Locale::En => Weekday::Sun,
+            Locale::Fr | Locale::De | Locale::Es => Weekday::Mon,
+        }
+    }
+
+    /// Report title for the week beginning on `date`.
+    pub fn week_title(self, date: NaiveDate) -> String {
+        let month = self.month_name(date.month());
+        match self {
+            Locale::En => format!("Week of {} {}, {}", month, date.day(), date.year()),
+            Locale::Fr => format!("Semaine du {} {} {}", date.day(), month, date.year()),
+            Locale::De => format!("Woche vom {}. {} {}", date.day(), month, date.year()),
+            Locale::Es => format!("Semana del {} de {} de {}", date.day(), month, date.year()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_from_str() -> Result<()> {
+        assert_eq!("en".parse::<Locale>()?, Locale::En);
+        assert_eq!("fr_CA".parse::<Locale>()?, Locale::Fr);
+        assert_eq!("DE-de".parse::<Locale>()?, Locale::De);
+        assert!("xx".parse::<Locale>().is_err());
+        assert!("".parse::<Locale>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_french_names() {
+        let fr = Locale::Fr;
+        assert_eq!(fr.weekday_name(Weekday::Mon), "lun.");
+        assert_eq!(fr.weekday_name(Weekday::Sun), "dim.");
+        assert_eq!(fr.month_name(2), "février");
+        assert_eq!(fr.first_weekday(), Weekday::Mon);
+        assert_eq!(
+            fr.week_title(NaiveDate::from_ymd(2021, 2, 1)),
+            "Semaine du 1 février 2021"
+        );
+    }
+
+    #[test]
+    fn test_english_is_unchanged() {
+        let en = Locale::default();
+        assert_eq!(en.weekday_name(Weekday::Wed), "Wed");
+        assert_eq!(en.first_weekday(), Weekday::Sun);
+        assert_eq!(
+            en.week_title(NaiveDate::from_ymd(2021, 1, 31)),
+            "Week of January 31, 2021"
+        );
+    }
+}
@@ -0,0 +1,61 @@
+// Crates
+use anyhow::Result;
+use chrono::{NaiveDateTime, NaiveTime};
+
+// Modules
+use crate::Entry;
+
+/// Time of day after which a day with no entries gets a reminder.
+pub const DEFAULT_REMIND_AFTER: &str = "17:00";
+
+/// Whether to remind at `now`: it's past `after` and none of `entries` starts
+/// on the same day.
+pub fn needs_reminder(now: NaiveDateTime, after: NaiveTime, entries: &[Entry]) -> Result<bool> {
+    if now.time() < after {
+        return Ok(false);
+    }
+
+    for entry in entries {
+        let (start, _) = entry.interval()?;
+        if start.date() == now.date() {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(start: &str, stop: &str) -> Entry {
+        Entry {
+            id: Some(1),
+            start: start.to_string(),
+            stop: stop.to_string(),
+            week_day: crate::Weekday::Wed,
+            code: "20-008".parse().unwrap(),
+            memo: "work, work, work".to_string(),
+        }
+    }
+
+    fn at(datetime: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_needs_reminder() -> Result<()> {
+        let after = NaiveTime::from_hms(17, 0, 0);
+        let today = entry("2021-02-03 09:00:00", "2021-02-03 10:00:00");
+        let yesterday = entry("2021-02-02 09:00:00", "2021-02-02 10:00:00");
+        let evening = at("2021-02-03 18:30:00");
+
+        assert!(!needs_reminder(at("2021-02-03 16:59:00"), after, &[])?);
+        assert!(needs_reminder(at("2021-02-03 17:00:00"), after, &[])?);
+        assert!(needs_reminder(evening, after, &[yesterday])?);
+        assert!(!needs_reminder(evening, after, &[today])?);
+
+        Ok(())
+    }
+}
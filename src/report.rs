@@ -0,0 +1,639 @@
+// Std
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Crates
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, Weekday};
+use serde::{Deserialize, Serialize};
+
+// Modules
+use crate::locale::Locale;
+use crate::time;
+use crate::{Entry, Project, Week};
+
+/// Translated week day column headers starting from `first`.
+pub fn weekday_headers(locale: Locale, first: Weekday) -> Vec<&'static str> {
+    let mut day = first;
+    let mut headers = Vec::new();
+    for _ in 0..7 {
+        headers.push(locale.weekday_name(day));
+        day = day.succ();
+    }
+
+    headers
+}
+
+/// Label for projects that have no client when grouping by client.
+pub const NO_CLIENT: &str = "(none)";
+
+/// How durations are shown in reports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DurationFormat {
+    /// Decimal hours rounded half up to the given number of places, e.g. `1.67`.
+    Decimal(usize),
+    /// Hours and minutes, e.g. `1:40`.
+    HoursMinutes,
+}
+
+impl Default for DurationFormat {
+    fn default() -> Self {
+        DurationFormat::Decimal(2)
+    }
+}
+
+impl FromStr for DurationFormat {
+    type Err = anyhow::Error;
+
+    /// Accepts `decimal`, `decimal:<precision>`, or `h:mm`.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_lowercase();
+        if s == "h:mm" {
+            return Ok(DurationFormat::HoursMinutes);
+        }
+
+        match s.strip_prefix("decimal") {
+            Some("") => Ok(DurationFormat::default()),
+            Some(precision) => {
+                let precision = precision
+                    .strip_prefix(':')
+                    .and_then(|p| p.parse().ok())
+                    .filter(|p| *p <= 6)
+                    .ok_or_else(|| anyhow!("Invalid decimal precision in '{}'.", s))?;
+                Ok(DurationFormat::Decimal(precision))
+            }
+            None => Err(anyhow!(
+                "Invalid duration format '{}': expected 'decimal', 'decimal:<precision>' or 'h:mm'.",
+                s
+            )),
+        }
+    }
+}
+
+impl DurationFormat {
+    pub fn display(self, minutes: i64) -> Hours {
+        Hours {
+            minutes,
+            format: self,
+        }
+    }
+}
+
+/// Displays a number of minutes according to a `DurationFormat`. Totals should
+/// be summed in minutes and only wrapped for display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hours {
+    pub minutes: i64,
+    pub format: DurationFormat,
+}
+
+impl fmt::Display for Hours {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.minutes < 0 { "-" } else { "" };
+        let minutes = self.minutes.abs();
+
+        match self.format {
+            DurationFormat::HoursMinutes => {
+                write!(f, "{}{}:{:02}", sign, minutes / 60, minutes % 60)
+            }
+            DurationFormat::Decimal(precision) => {
+                // Integer arithmetic keeps rounding exact: 0.05 is always 0.1.
+                let scale = 10_i64.pow(precision as u32);
+                let scaled = minutes * scale;
+                let mut units = scaled / 60;
+                if (scaled % 60) * 2 >= 60 {
+                    units += 1;
+                }
+                // A minus on a value that rounds to zero reads as a real debt.
+                let sign = if units == 0 { "" } else { sign };
+
+                if precision == 0 {
+                    write!(f, "{}{}", sign, units)
+                } else {
+                    write!(
+                        f,
+                        "{}{}.{:0width$}",
+                        sign,
+                        units / scale,
+                        units % scale,
+                        width = precision
+                    )
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComparisonRow {
+    pub project: String,
+    pub first_minutes: i64,
+    pub second_minutes: i64,
+}
+
+impl ComparisonRow {
+    pub fn delta_minutes(&self) -> i64 {
+        self.second_minutes - self.first_minutes
+    }
+}
+
+pub fn entry_minutes(entry: &Entry) -> Result<i64> {
+    let (start, stop) = entry.interval()?;
+
+    Ok(stop.signed_duration_since(start).num_minutes())
+}
+
+/// Total minutes logged per project code, ordered by code.
+pub fn project_minutes(entries: &[Entry]) -> Result<BTreeMap<String, i64>> {
+    let mut totals = BTreeMap::new();
+    for entry in entries {
+        *totals.entry(entry.code.to_string()).or_insert(0) += entry_minutes(entry)?;
+    }
+
+    Ok(totals)
+}
+
+/// One client's rows of a weekly report, with their minutes added up per day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientGroup {
+    pub client: String,
+    pub projects: Vec<WeeklyRow>,
+    pub subtotals: [i64; 7],
+}
+
+/// A `WeeklyReport` with its rows grouped by client. Columns follow `days`,
+/// as in the report it was built from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupedReport {
+    pub begin: String,
+    pub end: String,
+    pub label: String,
+    pub days: [crate::Weekday; 7],
+    pub groups: Vec<ClientGroup>,
+    pub totals: [i64; 7],
+}
+
+/// A client and how many projects belong to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientProjects {
+    pub client: String,
+    pub projects: i64,
+}
+
+/// Sums minutes per project code into minutes per client. Projects without a
+/// client, or missing from `projects`, are counted under `NO_CLIENT`.
+pub fn client_minutes(
+    minutes: &BTreeMap<String, i64>,
+    projects: &[Project],
+) -> BTreeMap<String, i64> {
+    let mut totals = BTreeMap::new();
+    for (code, minutes) in minutes {
+        let client = projects
+            .iter()
+            .find(|p| p.code.as_str() == code)
+            .and_then(|p| p.client.clone())
+            .unwrap_or_else(|| NO_CLIENT.to_string());
+        *totals.entry(client).or_insert(0) += minutes;
+    }
+
+    totals
+}
+
+/// Groups a weekly report's rows under the client each project belongs to,
+/// with a subtotal per client. Clients are ordered by name, and projects
+/// without a client (or missing from `projects`) come last under `NO_CLIENT`.
+pub fn group_by_client(report: WeeklyReport, projects: &[Project]) -> GroupedReport {
+    let mut named: BTreeMap<String, Vec<WeeklyRow>> = BTreeMap::new();
+    let mut unassigned: Vec<WeeklyRow> = Vec::new();
+
+    for row in report.rows {
+        let client = projects
+            .iter()
+            .find(|p| p.code.as_str() == row.project)
+            .and_then(|p| p.client.clone());
+
+        match client {
+            Some(client) => named.entry(client).or_default().push(row),
+            None => unassigned.push(row),
+        }
+    }
+
+    let mut groups: Vec<(String, Vec<WeeklyRow>)> = named.into_iter().collect();
+    if !unassigned.is_empty() {
+        groups.push((NO_CLIENT.to_string(), unassigned));
+    }
+
+    let groups = groups
+        .into_iter()
+        .map(|(client, projects)| {
+            let mut subtotals = [0; 7];
+            for row in &projects {
+                for (day, minutes) in row.minutes.iter().enumerate() {
+                    subtotals[day] += minutes;
+                }
+            }
+
+            ClientGroup {
+                client,
+                projects,
+                subtotals,
+            }
+        })
+        .collect();
+
+    GroupedReport {
+        begin: report.begin,
+        end: report.end,
+        label: report.label,
+        days: report.days,
+        groups,
+        totals: report.totals,
+    }
+}
+
+/// Compares two sets of entries project by project. Projects logged in only one
+/// of the sets still get a row, with zero minutes on the other side.
+pub fn compare_weeks(first: &[Entry], second: &[Entry]) -> Result<Vec<ComparisonRow>> {
+    let first_totals = project_minutes(first)?;
+    let second_totals = project_minutes(second)?;
+
+    let mut rows: BTreeMap<String, ComparisonRow> = BTreeMap::new();
+    for (code, minutes) in first_totals {
+        rows.insert(
+            code.clone(),
+            ComparisonRow {
+                project: code,
+                first_minutes: minutes,
+                second_minutes: 0,
+            },
+        );
+    }
+    for (code, minutes) in second_totals {
+        rows.entry(code.clone())
+            .or_insert(ComparisonRow {
+                project: code,
+                first_minutes: 0,
+                second_minutes: 0,
+            })
+            .second_minutes = minutes;
+    }
+
+    Ok(rows.into_values().collect())
+}
+
+/// What `WeeklyReport::build` collects besides minutes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReportOptions {
+    /// Keep each day's memos alongside its minutes.
+    #[serde(default)]
+    pub memos: bool,
+}
+
+/// One project's minutes and memos, in the report's day order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeeklyRow {
+    pub project: String,
+    pub minutes: [i64; 7],
+    pub memos: [Vec<String>; 7],
+}
+
+impl WeeklyRow {
+    fn new(project: String) -> Self {
+        WeeklyRow {
+            project,
+            minutes: [0; 7],
+            memos: Default::default(),
+        }
+    }
+
+    pub fn total(&self) -> i64 {
+        self.minutes.iter().sum()
+    }
+}
+
+/// Time logged per project and day over one week. Columns follow `days`, which
+/// starts on the week's first day rather than always on Sunday.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeeklyReport {
+    /// First and last dates of the week, `YYYY-MM-DD`.
+    pub begin: String,
+    pub end: String,
+    /// ISO week the report mostly covers, e.g. `2021-W05`.
+    pub label: String,
+    pub days: [crate::Weekday; 7],
+    /// One row per project code, ordered by code.
+    pub rows: Vec<WeeklyRow>,
+    pub totals: [i64; 7],
+}
+
+impl WeeklyReport {
+    /// Builds the report for `week` from `entries`. Entries starting outside
+    /// the week are ignored, and an entry is counted on the day it starts.
+    pub fn build(entries: &[Entry], week: &Week, options: ReportOptions) -> Result<WeeklyReport> {
+        let first_day = week.first_day();
+        let mut rows: BTreeMap<String, WeeklyRow> = BTreeMap::new();
+        let mut totals = [0; 7];
+
+        for entry in entries {
+            let (start, stop) = entry.interval()?;
+            if !week.contains(start.date()) {
+                continue;
+            }
+
+            let day = time::weekday_offset(start.weekday(), first_day) as usize;
+            let minutes = stop.signed_duration_since(start).num_minutes();
+            let code = entry.code.to_string();
+            let row = rows
+                .entry(code.clone())
+                .or_insert_with(|| WeeklyRow::new(code));
+
+            row.minutes[day] += minutes;
+            totals[day] += minutes;
+            if options.memos && !entry.memo.is_empty() {
+                row.memos[day].push(entry.memo.clone());
+            }
+        }
+
+        Ok(WeeklyReport {
+            begin: week.begin().to_string(),
+            end: week.end().to_string(),
+            label: week.label(),
+            days: week.days(),
+            rows: rows.into_values().collect(),
+            totals,
+        })
+    }
+
+    pub fn total(&self) -> i64 {
+        self.totals.iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn entry(start: &str, stop: &str, code: &str) -> Entry {
+        Entry {
+            id: None,
+            start: start.to_string(),
+            stop: stop.to_string(),
+            week_day: crate::Weekday::Mon,
+            code: code.parse().unwrap(),
+            memo: "work, work, work".to_string(),
+        }
+    }
+
+    fn project(code: &str, client: Option<&str>) -> Project {
+        Project {
+            id: None,
+            name: code.to_string(),
+            code: code.parse().unwrap(),
+            client: client.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_entry_minutes() -> Result<()> {
+        let e = entry("2021-02-01 09:00:00", "2021-02-01 10:40:00", "20-008");
+        assert_eq!(entry_minutes(&e)?, 100);
+
+        let bad = entry("0900", "1000", "20-008");
+        assert!(entry_minutes(&bad).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_weeks_deltas() -> Result<()> {
+        let first = vec![
+            entry("2021-02-01 09:00:00", "2021-02-01 11:00:00", "20-008"),
+            entry("2021-02-02 09:00:00", "2021-02-02 10:00:00", "20-008"),
+        ];
+        let second = vec![entry(
+            "2021-02-08 09:00:00",
+            "2021-02-08 10:30:00",
+            "20-008",
+        )];
+
+        let rows = compare_weeks(&first, &second)?;
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].first_minutes, 180);
+        assert_eq!(rows[0].second_minutes, 90);
+        assert_eq!(rows[0].delta_minutes(), -90);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_weeks_union_of_projects() -> Result<()> {
+        let first = vec![
+            entry("2021-02-01 09:00:00", "2021-02-01 10:00:00", "20-008"),
+            entry("2021-02-01 10:00:00", "2021-02-01 12:00:00", "20-000"),
+        ];
+        let second = vec![
+            entry("2021-02-08 09:00:00", "2021-02-08 10:00:00", "20-008"),
+            entry("2021-02-08 13:00:00", "2021-02-08 14:00:00", "21-001"),
+        ];
+
+        let rows = compare_weeks(&first, &second)?;
+        let projects: Vec<&str> = rows.iter().map(|r| r.project.as_str()).collect();
+
+        assert_eq!(projects, vec!["20-000", "20-008", "21-001"]);
+        assert_eq!(rows[0].delta_minutes(), -120);
+        assert_eq!(rows[1].delta_minutes(), 0);
+        assert_eq!(rows[2].delta_minutes(), 60);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by_client_subtotals() -> Result<()> {
+        let week = Week::new(NaiveDate::from_ymd(2021, 2, 3), 0, Weekday::Mon);
+        let entries = vec![
+            entry("2021-02-01 09:00:00", "2021-02-01 11:00:00", "20-001"),
+            entry("2021-02-02 09:00:00", "2021-02-02 10:00:00", "20-001"),
+            entry("2021-02-01 11:00:00", "2021-02-01 11:30:00", "20-002"),
+            entry("2021-02-01 13:00:00", "2021-02-01 16:00:00", "21-001"),
+            entry("2021-02-07 16:00:00", "2021-02-07 17:00:00", "99-999"),
+        ];
+        let projects = vec![
+            project("20-001", Some("Acme")),
+            project("20-002", Some("Acme")),
+            project("21-001", Some("Globex")),
+            project("99-999", None),
+        ];
+
+        let report = WeeklyReport::build(&entries, &week, ReportOptions::default())?;
+        let grouped = group_by_client(report.clone(), &projects);
+
+        let clients: Vec<&str> = grouped.groups.iter().map(|g| g.client.as_str()).collect();
+        assert_eq!(clients, vec!["Acme", "Globex", NO_CLIENT]);
+
+        // Columns start on Monday, like the report's.
+        assert_eq!(grouped.days, report.days);
+        let acme = &grouped.groups[0];
+        assert_eq!(acme.projects.len(), 2);
+        assert_eq!(acme.subtotals, [150, 60, 0, 0, 0, 0, 0]);
+
+        let globex = &grouped.groups[1];
+        assert_eq!(globex.subtotals, [180, 0, 0, 0, 0, 0, 0]);
+
+        assert_eq!(grouped.groups[2].subtotals, [0, 0, 0, 0, 0, 0, 60]);
+        assert_eq!(grouped.totals, [330, 60, 0, 0, 0, 0, 60]);
+        assert_eq!(grouped.label, "2021-W05");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_minutes() {
+        let minutes: BTreeMap<String, i64> = vec![
+            ("20-001".to_string(), 120),
+            ("20-002".to_string(), 30),
+            ("99-999".to_string(), 60),
+        ]
+        .into_iter()
+        .collect();
+        let projects = vec![
+            project("20-001", Some("Acme")),
+            project("20-002", Some("Acme")),
+            project("99-999", None),
+        ];
+
+        let totals = client_minutes(&minutes, &projects);
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals["Acme"], 150);
+        assert_eq!(totals[NO_CLIENT], 60);
+    }
+
+    #[test]
+    fn test_duration_format_from_str() -> Result<()> {
+        assert_eq!(
+            "decimal".parse::<DurationFormat>()?,
+            DurationFormat::Decimal(2)
+        );
+        assert_eq!(
+            "decimal:1".parse::<DurationFormat>()?,
+            DurationFormat::Decimal(1)
+        );
+        assert_eq!(
+            "H:MM".parse::<DurationFormat>()?,
+            DurationFormat::HoursMinutes
+        );
+
+        assert!("decimal:".parse::<DurationFormat>().is_err());
+        assert!("decimal:x".parse::<DurationFormat>().is_err());
+        assert!("minutes".parse::<DurationFormat>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decimal_rounding() {
+        let two = DurationFormat::Decimal(2);
+        assert_eq!(two.display(0).to_string(), "0.00");
+        assert_eq!(two.display(60).to_string(), "1.00");
+        assert_eq!(two.display(100).to_string(), "1.67");
+        // 0.01666.. and 0.08333.. sit either side of a .005 boundary.
+        assert_eq!(two.display(1).to_string(), "0.02");
+        assert_eq!(two.display(5).to_string(), "0.08");
+
+        // Exact halves round up: 3 minutes is 0.05 hours, 9 minutes 0.15.
+        let one = DurationFormat::Decimal(1);
+        assert_eq!(one.display(3).to_string(), "0.1");
+        assert_eq!(one.display(9).to_string(), "0.2");
+        assert_eq!(one.display(2).to_string(), "0.0");
+        assert_eq!(one.display(-3).to_string(), "-0.1");
+        assert_eq!(one.display(-2).to_string(), "0.0");
+
+        let zero = DurationFormat::Decimal(0);
+        assert_eq!(zero.display(30).to_string(), "1");
+        assert_eq!(zero.display(29).to_string(), "0");
+        assert_eq!(zero.display(-29).to_string(), "0");
+    }
+
+    #[test]
+    fn test_hours_minutes_format() {
+        let hm = DurationFormat::HoursMinutes;
+        assert_eq!(hm.display(0).to_string(), "0:00");
+        assert_eq!(hm.display(100).to_string(), "1:40");
+        assert_eq!(hm.display(45).to_string(), "0:45");
+        assert_eq!(hm.display(600).to_string(), "10:00");
+        assert_eq!(hm.display(-90).to_string(), "-1:30");
+    }
+
+    #[test]
+    fn test_weekday_headers_fr() {
+        let fr = Locale::Fr;
+        assert_eq!(
+            weekday_headers(fr, fr.first_weekday()),
+            vec!["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."]
+        );
+
+        // Stored keys stay English whatever the display language.
+        assert_eq!(crate::time::weekday_order(fr.first_weekday())[0], "Mon");
+    }
+
+    #[test]
+    fn test_weekly_report() -> Result<()> {
+        // Week of Sunday 2021-01-31.
+        let week = Week::new(NaiveDate::from_ymd(2021, 2, 3), 0, Weekday::Sun);
+        let mut entries = vec![
+            entry("2021-02-01 09:00:00", "2021-02-01 10:40:00", "20-008"),
+            entry("2021-02-01 13:00:00", "2021-02-01 14:00:00", "20-008"),
+            entry("2021-02-06 09:00:00", "2021-02-06 09:30:00", "20-000"),
+            // Outside the week.
+            entry("2021-02-07 09:00:00", "2021-02-07 17:00:00", "20-008"),
+        ];
+        entries[1].memo = "review".to_string();
+
+        let report = WeeklyReport::build(&entries, &week, ReportOptions::default())?;
+
+        assert_eq!(report.begin, "2021-01-31");
+        assert_eq!(report.end, "2021-02-06");
+        assert_eq!(report.label, "2021-W05");
+        assert_eq!(report.rows.len(), 2);
+        assert_eq!(report.rows[0].project, "20-000");
+        assert_eq!(report.rows[0].minutes, [0, 0, 0, 0, 0, 0, 30]);
+        assert_eq!(report.rows[1].project, "20-008");
+        assert_eq!(report.rows[1].minutes, [0, 160, 0, 0, 0, 0, 0]);
+        assert_eq!(report.rows[1].total(), 160);
+        assert_eq!(report.totals, [0, 160, 0, 0, 0, 0, 30]);
+        assert_eq!(report.total(), 190);
+        assert!(report.rows[1].memos[1].is_empty());
+
+        let report = WeeklyReport::build(&entries, &week, ReportOptions { memos: true })?;
+        assert_eq!(report.rows[1].memos[1], vec!["work, work, work", "review"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_weekly_report_monday_start() -> Result<()> {
+        let week = Week::new(NaiveDate::from_ymd(2021, 2, 3), 0, Weekday::Mon);
+        let entries = vec![
+            entry("2021-02-01 09:00:00", "2021-02-01 10:00:00", "20-008"),
+            entry("2021-02-07 09:00:00", "2021-02-07 11:00:00", "20-008"),
+            // The previous Sunday belongs to last week.
+            entry("2021-01-31 09:00:00", "2021-01-31 17:00:00", "20-008"),
+        ];
+
+        let report = WeeklyReport::build(&entries, &week, ReportOptions::default())?;
+
+        assert_eq!(report.days[0], crate::Weekday::Mon);
+        assert_eq!(report.label, "2021-W05");
+        assert_eq!(report.rows[0].minutes, [60, 0, 0, 0, 0, 0, 120]);
+
+        let json = serde_json::to_string(&report)?;
+        assert_eq!(serde_json::from_str::<WeeklyReport>(&json)?, report);
+
+        let bad = vec![entry("0900", "1000", "20-008")];
+        assert!(WeeklyReport::build(&bad, &week, ReportOptions::default()).is_err());
+
+        Ok(())
+    }
+}
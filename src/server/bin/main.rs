@@ -1,44 +1,284 @@
+// Std
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time;
+
 // Crates
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use chrono::{Duration, Local, NaiveDate};
+use dotenv::dotenv;
 use sqlx::sqlite::SqlitePool;
-use tracing::{info, Level};
-use warp::Filter;
+use tracing::{error, info};
 
 // Local
 use timecard::api;
+use timecard::config;
 use timecard::db;
+use timecard::storage::SqliteStorage;
+use timecard::telemetry::{self, LogFormat, LogSink};
+
+/// How often old entries are looked for when archiving is enabled.
+const ARCHIVE_INTERVAL: time::Duration = time::Duration::from_secs(24 * 60 * 60);
+
+/// The most days `TIMECARD_ARCHIVE_AFTER_DAYS` may be set to, about a century.
+const MAX_ARCHIVE_AFTER_DAYS: u32 = 36_500;
+
+/// What the server was started with.
+struct ServerConfig {
+    database_url: String,
+    listen_addr: SocketAddr,
+    /// Entries older than this many days are archived; unset turns it off.
+    archive_after_days: Option<u32>,
+}
+
+impl ServerConfig {
+    fn from_env() -> Result<Self> {
+        let archive_after_days = match env::var("TIMECARD_ARCHIVE_AFTER_DAYS") {
+            Ok(days) => Some(parse_archive_after_days(&days)?),
+            Err(_) => None,
+        };
+
+        Ok(ServerConfig {
+            database_url: db::database_url()?,
+            listen_addr: ([0, 0, 0, 0], 3333).into(),
+            archive_after_days,
+        })
+    }
+}
+
+fn parse_archive_after_days(days: &str) -> Result<u32> {
+    let days: u32 = days
+        .trim()
+        .parse()
+        .context("TIMECARD_ARCHIVE_AFTER_DAYS must be a number of days.")?;
+    if !(1..=MAX_ARCHIVE_AFTER_DAYS).contains(&days) {
+        return Err(anyhow!(
+            "TIMECARD_ARCHIVE_AFTER_DAYS must be from 1 to {}, got {}.",
+            MAX_ARCHIVE_AFTER_DAYS,
+            days
+        ));
+    }
+
+    Ok(days)
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let listen_port = 3333;
-    let pool = db::setup_pool().await?;
-    db::setup_db(&pool).await?;
+    dotenv().ok();
+    config::load_into_env()?;
 
-    let subscriber = tracing_subscriber::fmt()
-        .with_max_level(Level::TRACE)
-        .finish();
+    let config = ServerConfig::from_env()?;
+    let pool = db::setup_pool(&config.database_url).await?;
 
-    tracing::subscriber::set_global_default(subscriber).expect("no global subscriber has been set");
+    // Dropping the guard stops the file writer, so it lives until main returns.
+    let (dispatch, _log_guard) =
+        telemetry::get_subscriber(&LogSink::from_env()?, LogFormat::from_env()?)?;
+    tracing::dispatcher::set_global_default(dispatch).expect("no global subscriber has been set");
+    #[cfg(feature = "sentry")]
+    let _sentry_guard = timecard::error_reporting::init_sentry()?;
 
-    info!("Listening on port {}. . .", listen_port);
-    run(pool, listen_port).await;
+    diagnostics(&pool, &config).await;
+
+    if let Some(days) = config.archive_after_days {
+        info!("Archiving entries older than {} days.", days);
+        tokio::spawn(archive_periodically(pool.clone(), days));
+    }
+
+    info!("Listening on {}. . .", config.listen_addr);
+    run(pool, config.listen_addr).await;
 
     Ok(())
 }
 
-async fn run(pool: SqlitePool, listen_port: u16) {
-    let routes = api::post_entry(pool.clone())
-        .or(api::get_entry(pool.clone()))
-        .or(api::update_entry(pool.clone()))
-        .or(api::get_entries_between(pool.clone()))
-        .or(api::read_last_entry(pool.clone()))
-        .or(api::delete_entry(pool.clone()))
-        .or(api::delete_last_entry(pool.clone()))
-        .or(api::post_project(pool.clone()))
-        .or(api::get_project(pool.clone()))
-        .or(api::get_all_projects(pool.clone()))
-        .or(api::update_project(pool.clone()))
-        .or(api::delete_project(pool.clone()));
-
-    warp::serve(routes).run(([0, 0, 0, 0], listen_port)).await;
+async fn run(pool: SqlitePool, listen_addr: SocketAddr) {
+    let routes = api::routes(Arc::new(SqliteStorage::new(pool)));
+
+    warp::serve(routes).run(listen_addr).await;
+}
+
+/// Archives old entries now and then every `ARCHIVE_INTERVAL`. Each run is
+/// awaited before waiting for the next tick, so runs never overlap.
+async fn archive_periodically(pool: SqlitePool, after_days: u32) {
+    let mut interval = tokio::time::interval(ARCHIVE_INTERVAL);
+    loop {
+        interval.tick().await;
+        archive_old_entries(&pool, after_days, Local::today().naive_local()).await;
+    }
+}
+
+/// Moves entries starting more than `after_days` before `today` to the
+/// archive. A failure is logged and left for the next run.
+async fn archive_old_entries(pool: &SqlitePool, after_days: u32, today: NaiveDate) {
+    let cutoff = match today.checked_sub_signed(Duration::days(i64::from(after_days))) {
+        Some(cutoff) => cutoff,
+        None => {
+            error!(after_days, "Archive cutoff is out of range.");
+            return;
+        }
+    };
+    info!(%cutoff, "Archiving old entries.");
+    match db::archive_entries_before(pool, cutoff, db::ARCHIVE_BATCH_SIZE).await {
+        Ok(archived) => info!(archived, %cutoff, "Finished archiving entries."),
+        Err(e) => error!(error = %e, %cutoff, "Archiving entries failed."),
+    }
+}
+
+/// What the server found at startup. Anything that couldn't be read is `None`.
+#[derive(Debug)]
+struct Diagnostics {
+    database: Option<String>,
+    schema_version: Option<i64>,
+    entries: Option<i64>,
+    projects: Option<i64>,
+}
+
+/// Logs one event describing the environment the server is about to serve.
+/// Nothing here can stop the server from starting.
+async fn diagnostics(pool: &SqlitePool, config: &ServerConfig) -> Diagnostics {
+    let path = config.database_url.trim_start_matches("sqlite://");
+    let diagnostics = Diagnostics {
+        database: fs::canonicalize(path)
+            .ok()
+            .map(|path| path.display().to_string()),
+        schema_version: db::schema_version(pool).await.ok(),
+        entries: db::count_entries(pool).await.ok(),
+        projects: db::count_projects(pool).await.ok(),
+    };
+
+    // There's no authentication, CORS or TLS to enable yet.
+    info!(
+        version = env!("CARGO_PKG_VERSION"),
+        database = %or_unknown(&diagnostics.database),
+        schema_version = %or_unknown(&diagnostics.schema_version),
+        entries = %or_unknown(&diagnostics.entries),
+        projects = %or_unknown(&diagnostics.projects),
+        listen_addr = %config.listen_addr,
+        auth = false,
+        cors = false,
+        tls = false,
+        "Starting timecard-d."
+    );
+
+    diagnostics
+}
+
+fn or_unknown<T: Display>(value: &Option<T>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+    use timecard::telemetry::CapturedLogs;
+    use timecard::{NewEntry, Project};
+
+    #[tokio::test]
+    async fn test_diagnostics() -> Result<()> {
+        let path = env::temp_dir().join(format!("timecard_diagnostics_{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let config = ServerConfig {
+            database_url: format!("sqlite://{}", path.display()),
+            listen_addr: ([127, 0, 0, 1], 3333).into(),
+            archive_after_days: None,
+        };
+        let pool = db::setup_pool(&config.database_url).await?;
+
+        for hour in &[9, 13] {
+            let entry = NewEntry::builder()
+                .date(NaiveDate::from_ymd(2021, 2, 3))
+                .start_time(NaiveTime::from_hms(*hour, 0, 0))
+                .stop_time(NaiveTime::from_hms(*hour + 1, 0, 0))
+                .code("20-008")
+                .memo("work, work, work")
+                .build()?;
+            db::write_entry(&pool, &entry, false).await?;
+        }
+        db::write_project(
+            &pool,
+            &Project {
+                id: None,
+                name: "Acme Website".to_string(),
+                code: "20-008".parse()?,
+                client: None,
+            },
+        )
+        .await?;
+
+        let logs = CapturedLogs::default();
+        let _guard = tracing::subscriber::set_default(logs.subscriber());
+
+        let found = diagnostics(&pool, &config).await;
+        assert_eq!(found.entries, Some(2));
+        assert_eq!(found.projects, Some(1));
+        assert_eq!(found.schema_version, Some(db::SCHEMA_VERSION));
+        assert!(found.database.is_some());
+
+        // A missing table is reported as unknown rather than failing.
+        sqlx::query("DROP TABLE projects").execute(&pool).await?;
+        assert_eq!(diagnostics(&pool, &config).await.projects, None);
+
+        let logs = logs.contents();
+        let events: Vec<&str> = logs
+            .lines()
+            .filter(|line| line.contains("Starting timecard-d."))
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].contains("entries=2"));
+        assert!(events[0].contains("projects=1"));
+        assert!(events[0].contains("listen_addr=127.0.0.1:3333"));
+        assert!(events[1].contains("projects=unknown"));
+
+        fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_archive_old_entries() -> Result<()> {
+        let path = env::temp_dir().join(format!("timecard_archive_{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let pool = db::setup_pool(&format!("sqlite://{}", path.display())).await?;
+
+        let today = NaiveDate::from_ymd(2021, 3, 1);
+        for date in &[today - Duration::days(31), today - Duration::days(29)] {
+            let entry = NewEntry::builder()
+                .date(*date)
+                .start_time(NaiveTime::from_hms(9, 0, 0))
+                .stop_time(NaiveTime::from_hms(10, 0, 0))
+                .code("20-008")
+                .memo("work, work, work")
+                .build()?;
+            db::write_entry(&pool, &entry, false).await?;
+        }
+
+        archive_old_entries(&pool, 30, today).await;
+        assert_eq!(db::count_entries(&pool).await?, 1);
+        let start = "2021-01-01 00:00:00".to_string();
+        let end = "2021-03-01 23:59:59".to_string();
+        let archived = db::read_archived_entries_between(&pool, start, end).await?;
+        assert_eq!(archived.len(), 1);
+
+        // A cutoff before the earliest date is logged rather than panicking.
+        archive_old_entries(&pool, u32::MAX, today).await;
+        assert_eq!(db::count_entries(&pool).await?, 1);
+
+        fs::remove_file(&path)?;
+
+        Ok(())
+    }
+    #[test]
+    fn test_parse_archive_after_days() {
+        assert_eq!(parse_archive_after_days(" 730 ").unwrap(), 730);
+        assert_eq!(parse_archive_after_days("36500").unwrap(), 36_500);
+        for days in &["0", "36501", "4294967295", "-1", "two years"] {
+            assert!(parse_archive_after_days(days).is_err(), "{}", days);
+        }
+    }
 }
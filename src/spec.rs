@@ -0,0 +1,284 @@
+// Std
+use std::str::FromStr;
+
+// Crates
+use anyhow::Result;
+use chrono::{Duration, NaiveDate, NaiveTime};
+use thiserror::Error;
+
+// Modules
+use crate::breaks;
+use crate::time;
+use crate::{NewEntry, ProjectCode};
+
+/// The day an entry spec is for, relative days resolved when it's submitted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryDate {
+    Today,
+    Yesterday,
+    Tomorrow,
+    On(NaiveDate),
+}
+
+impl EntryDate {
+    pub fn resolve(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            EntryDate::Today => today,
+            EntryDate::Yesterday => today - Duration::days(1),
+            EntryDate::Tomorrow => today + Duration::days(1),
+            EntryDate::On(date) => date,
+        }
+    }
+}
+
+impl FromStr for EntryDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "today" => Ok(EntryDate::Today),
+            "yesterday" => Ok(EntryDate::Yesterday),
+            "tomorrow" => Ok(EntryDate::Tomorrow),
+            date => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map(EntryDate::On)
+                .map_err(|_| {
+                    format!(
+                        "'{}' is not a date: expected YYYY-MM-DD, today, yesterday or tomorrow",
+                        date
+                    )
+                }),
+        }
+    }
+}
+
+/// Why a pipe-delimited entry couldn't be read. Positions count fields from 1.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum EntrySpecError {
+    #[error("Expected 4 to 6 fields as [date|]start|stop|code|memo[|break=minutes], found {0}.")]
+    FieldCount(usize),
+    #[error("Field {position} ({name}): {message}")]
+    Field {
+        position: usize,
+        name: &'static str,
+        message: String,
+    },
+}
+
+/// An entry as typed on the command line: `start|stop|code|memo`, optionally
+/// preceded by a date and followed by a `break=minutes` field. The break can
+/// also be appended to the stop time, e.g. `1700-30m`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliEntrySpec {
+    /// `None` when no date was given; the entry is for today.
+    pub date: Option<EntryDate>,
+    pub start: NaiveTime,
+    pub stop: NaiveTime,
+    pub code: ProjectCode,
+    pub memo: String,
+    pub brk: Option<Duration>,
+}
+
+impl CliEntrySpec {
+    /// Reads already split fields, as given by a template or by clap.
+    pub fn from_fields(fields: &[&str]) -> Result<Self, EntrySpecError> {
+        let has_break = |field: &str| field.trim().starts_with("break=");
+        let (date, rest, extra) = match fields.len() {
+            4 => (None, fields, None),
+            5 if has_break(fields[4]) => (None, &fields[..4], Some(fields[4])),
+            5 => (Some(fields[0]), &fields[1..], None),
+            6 => (Some(fields[0]), &fields[1..5], Some(fields[5])),
+            n => return Err(EntrySpecError::FieldCount(n)),
+        };
+
+        // Position of the first field after the optional date.
+        let offset = if date.is_some() { 2 } else { 1 };
+        let field_error = |index: usize, name, message: String| EntrySpecError::Field {
+            position: offset + index,
+            name,
+            message,
+        };
+
+        let date = date
+            .map(|date| date.parse::<EntryDate>())
+            .transpose()
+            .map_err(|message| EntrySpecError::Field {
+                position: 1,
+                name: "date",
+                message,
+            })?;
+
+        let start =
+            time::parse_entry_time(rest[0]).map_err(|e| field_error(0, "start", e.to_string()))?;
+        let (stop, suffix) = breaks::extract_break(rest[1], None)
+            .map_err(|e| field_error(1, "stop", e.to_string()))?;
+        let stop =
+            time::parse_entry_time(stop).map_err(|e| field_error(1, "stop", e.to_string()))?;
+        let code = rest[2]
+            .parse::<ProjectCode>()
+            .map_err(|e| field_error(2, "code", e.to_string()))?;
+
+        let extra = match extra {
+            Some(field) => {
+                breaks::extract_break("", Some(field))
+                    .map_err(|e| field_error(4, "break", e.to_string()))?
+                    .1
+            }
+            None => None,
+        };
+        let brk = match (suffix, extra) {
+            (Some(_), Some(_)) => {
+                return Err(field_error(4, "break", "Break given twice.".to_string()))
+            }
+            (brk, None) | (None, brk) => brk,
+        };
+
+        Ok(CliEntrySpec {
+            date,
+            start,
+            stop,
+            code,
+            memo: rest[3].to_string(),
+            brk,
+        })
+    }
+
+    /// The entry on the spec's date, or on `today` if it has none. The break,
+    /// if any, is left for the caller to apply.
+    pub fn to_new_entry(&self, today: NaiveDate) -> Result<NewEntry> {
+        let date = self.date.map_or(today, |date| date.resolve(today));
+
+        NewEntry::builder()
+            .date(date)
+            .start_time(self.start)
+            .stop_time(self.stop)
+            .code(self.code.as_str())
+            .memo(self.memo.as_str())
+            .build()
+    }
+}
+
+impl FromStr for CliEntrySpec {
+    type Err = EntrySpecError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split('|').collect();
+        CliEntrySpec::from_fields(&fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd(2021, 2, 3)
+    }
+
+    #[test]
+    fn test_four_fields() -> Result<()> {
+        let spec: CliEntrySpec = "0900|1030|20-008|work, work, work".parse()?;
+        assert_eq!(spec.date, None);
+        assert_eq!(spec.brk, None);
+
+        let entry = spec.to_new_entry(today())?;
+        assert_eq!(entry.start, "2021-02-03 09:00:00");
+        assert_eq!(entry.stop, "2021-02-03 10:30:00");
+        assert_eq!(entry.code, "20-008");
+        assert_eq!(entry.memo, "work, work, work");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_backdated_fields() -> Result<()> {
+        let spec: CliEntrySpec = "2021-01-29|0900|1030|20-008|memo".parse()?;
+        assert_eq!(
+            spec.date,
+            Some(EntryDate::On(NaiveDate::from_ymd(2021, 1, 29)))
+        );
+        assert_eq!(spec.to_new_entry(today())?.start, "2021-01-29 09:00:00");
+
+        let spec: CliEntrySpec = "yesterday|0900|1030|20-008|memo|break=15".parse()?;
+        assert_eq!(spec.to_new_entry(today())?.start, "2021-02-02 09:00:00");
+        assert_eq!(spec.brk, Some(Duration::minutes(15)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_break_fields() -> Result<()> {
+        let spec: CliEntrySpec = "0900|1700|20-008|memo|break=30".parse()?;
+        assert_eq!(spec.date, None);
+        assert_eq!(spec.brk, Some(Duration::minutes(30)));
+
+        let spec: CliEntrySpec = "0900|1700-45m|20-008|memo".parse()?;
+        assert_eq!(spec.stop, NaiveTime::from_hms(17, 0, 0));
+        assert_eq!(spec.brk, Some(Duration::minutes(45)));
+
+        let err = "0900|1700-45m|20-008|memo|break=30"
+            .parse::<CliEntrySpec>()
+            .unwrap_err();
+        assert!(matches!(err, EntrySpecError::Field { position: 5, .. }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_count() {
+        let count = |s: &str| match s.parse::<CliEntrySpec>() {
+            Err(EntrySpecError::FieldCount(n)) => Some(n),
+            _ => None,
+        };
+
+        assert_eq!(count("0900|1030|20-008"), Some(3));
+        assert_eq!(count("0900"), Some(1));
+        assert_eq!(count("today|0900|1030|20-008|memo|break=5|extra"), Some(7));
+        assert_eq!(count("0900|1030|20-008|memo|with|pipes|inside"), Some(7));
+    }
+
+    #[test]
+    fn test_field_positions() {
+        let position = |s: &str| match s.parse::<CliEntrySpec>() {
+            Err(EntrySpecError::Field { position, name, .. }) => Some((position, name)),
+            _ => None,
+        };
+
+        assert_eq!(position("2575|1030|20-008|memo"), Some((1, "start")));
+        assert_eq!(position("0900|10:30|20-008|memo"), Some((2, "stop")));
+        assert_eq!(position("0900|1030|not a code|memo"), Some((3, "code")));
+        assert_eq!(
+            position("0900|1030|20-008|memo|break=x"),
+            Some((5, "break"))
+        );
+        assert_eq!(position("someday|0900|1030|20-008|memo"), Some((1, "date")));
+        assert_eq!(position("today|0900|2500|20-008|memo"), Some((3, "stop")));
+        assert_eq!(
+            position("today|0900|1030|20-008|memo|lunch=5"),
+            Some((6, "break"))
+        );
+
+        let err = "0900|1030|20-008|memo|break=x"
+            .parse::<CliEntrySpec>()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Field 5 (break): Invalid break length: 'x'"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_entry_spec_never_panics(spec in "\\PC*") {
+            let _ = spec.parse::<CliEntrySpec>();
+        }
+
+        #[test]
+        fn prop_entry_date_round_trip(days in 1i32..3_652_059) {
+            // Every date from 0001-01-01 to 9999-12-31.
+            let date = NaiveDate::from_num_days_from_ce(days);
+            let spec = date.format("%Y-%m-%d").to_string();
+            prop_assert_eq!(spec.parse::<EntryDate>(), Ok(EntryDate::On(date)));
+        }
+    }
+}
@@ -0,0 +1,312 @@
+// Std
+use std::collections::BTreeMap;
+
+// Crates
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use sqlx::sqlite::SqlitePool;
+
+// Modules
+use crate::db::{self, IntegrityIssue};
+use crate::error::Result;
+use crate::report::ClientProjects;
+use crate::{Entry, Export, ImportSummary, NewEntry, Project, ProjectCode, UndoRecord};
+
+/// The entry and project operations the API serves, so handlers can be
+/// tested against something other than a real database.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn read_entry(&self, id: i32) -> Result<Entry>;
+    async fn read_last_entry(&self) -> Result<Entry>;
+    async fn read_last_n_entries(&self, n: i32) -> Result<Vec<Entry>>;
+    async fn read_entries_since(&self, since_id: i32) -> Result<Vec<Entry>>;
+    async fn read_entries_between(
+        &self,
+        start_date: String,
+        end_date: String,
+    ) -> Result<Vec<Entry>>;
+    async fn read_archived_entries_between(
+        &self,
+        start_date: String,
+        end_date: String,
+    ) -> Result<Vec<Entry>>;
+    async fn read_client_entries_between(
+        &self,
+        start_date: String,
+        end_date: String,
+        client: &str,
+    ) -> Result<Vec<Entry>>;
+    async fn write_entry(&self, entry: &NewEntry, allow_duplicate: bool) -> Result<i32>;
+    async fn write_entries(&self, entries: &[NewEntry], allow_duplicate: bool) -> Result<Vec<i32>>;
+    async fn update_entry(&self, entry: &Entry) -> Result<()>;
+    async fn delete_entry(&self, id: i32) -> Result<()>;
+    async fn delete_last_entry(&self) -> Result<()>;
+    async fn delete_last_n(&self, ids: &[i32]) -> Result<()>;
+    async fn undo_last_action(&self) -> Result<Option<UndoRecord>>;
+    async fn integrity_report(&self) -> Result<Vec<IntegrityIssue>>;
+    async fn project_minutes_between(
+        &self,
+        begin: NaiveDate,
+        end: NaiveDate,
+        include_archive: bool,
+    ) -> Result<BTreeMap<String, i64>>;
+    async fn read_project(&self, id: i32) -> Result<Project>;
+    async fn read_all_projects(&self) -> Result<Vec<Project>>;
+    async fn read_clients(&self) -> Result<Vec<ClientProjects>>;
+    async fn write_project(&self, project: &Project) -> Result<i32>;
+    async fn update_project(&self, project: &Project) -> Result<()>;
+    async fn delete_project(&self, code: &ProjectCode) -> Result<()>;
+    async fn export(&self) -> Result<Export>;
+    async fn import(&self, export: &Export, merge: bool) -> Result<ImportSummary>;
+}
+
+/// Storage in the SQLite database, through the functions in `db`.
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub fn new(pool: SqlitePool) -> Self {
+        SqliteStorage { pool }
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn read_entry(&self, id: i32) -> Result<Entry> {
+        db::read_entry(&self.pool, id).await
+    }
+
+    async fn read_last_entry(&self) -> Result<Entry> {
+        db::read_last_entry(&self.pool).await
+    }
+
+    async fn read_last_n_entries(&self, n: i32) -> Result<Vec<Entry>> {
+        db::read_last_n_entries(&self.pool, n).await
+    }
+
+    async fn read_entries_since(&self, since_id: i32) -> Result<Vec<Entry>> {
+        db::read_entries_since(&self.pool, since_id).await
+    }
+
+    async fn read_entries_between(
+        &self,
+        start_date: String,
+        end_date: String,
+    ) -> Result<Vec<Entry>> {
+        db::read_entries_between(&self.pool, start_date, end_date).await
+    }
+
+    async fn read_archived_entries_between(
+        &self,
+        start_date: String,
+        end_date: String,
+    ) -> Result<Vec<Entry>> {
+        db::read_archived_entries_between(&self.pool, start_date, end_date).await
+    }
+
+    async fn read_client_entries_between(
+        &self,
+        start_date: String,
+        end_date: String,
+        client: &str,
+    ) -> Result<Vec<Entry>> {
+        db::read_client_entries_between(&self.pool, start_date, end_date, client).await
+    }
+
+    async fn write_entry(&self, entry: &NewEntry, allow_duplicate: bool) -> Result<i32> {
+        db::write_entry(&self.pool, entry, allow_duplicate).await
+    }
+
+    async fn write_entries(&self, entries: &[NewEntry], allow_duplicate: bool) -> Result<Vec<i32>> {
+        db::write_entries(&self.pool, entries, allow_duplicate).await
+    }
+
+    async fn update_entry(&self, entry: &Entry) -> Result<()> {
+        db::update_entry(&self.pool, entry).await
+    }
+
+    async fn delete_entry(&self, id: i32) -> Result<()> {
+        db::delete_entry(&self.pool, id).await
+    }
+
+    async fn delete_last_entry(&self) -> Result<()> {
+        db::delete_last_entry(&self.pool).await
+    }
+
+    async fn delete_last_n(&self, ids: &[i32]) -> Result<()> {
+        db::delete_last_n(&self.pool, ids).await
+    }
+
+    async fn undo_last_action(&self) -> Result<Option<UndoRecord>> {
+        db::undo_last_action(&self.pool).await
+    }
+
+    async fn integrity_report(&self) -> Result<Vec<IntegrityIssue>> {
+        db::integrity_report(&self.pool).await
+    }
+
+    async fn project_minutes_between(
+        &self,
+        begin: NaiveDate,
+        end: NaiveDate,
+        include_archive: bool,
+    ) -> Result<BTreeMap<String, i64>> {
+        db::project_minutes_between(&self.pool, begin, end, include_archive).await
+    }
+
+    async fn read_project(&self, id: i32) -> Result<Project> {
+        db::read_project(&self.pool, id).await
+    }
+
+    async fn read_all_projects(&self) -> Result<Vec<Project>> {
+        db::read_all_projects(&self.pool).await
+    }
+
+    async fn read_clients(&self) -> Result<Vec<ClientProjects>> {
+        db::read_clients(&self.pool).await
+    }
+
+    async fn write_project(&self, project: &Project) -> Result<i32> {
+        db::write_project(&self.pool, project).await
+    }
+
+    async fn update_project(&self, project: &Project) -> Result<()> {
+        db::update_project(&self.pool, project).await
+    }
+
+    async fn delete_project(&self, code: &ProjectCode) -> Result<()> {
+        db::delete_project(&self.pool, code).await
+    }
+
+    async fn export(&self) -> Result<Export> {
+        db::export(&self.pool).await
+    }
+
+    async fn import(&self, export: &Export, merge: bool) -> Result<ImportSummary> {
+        db::import(&self.pool, export, merge).await
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::error::TimecardError;
+
+    /// Storage whose every operation fails as if the database had gone away.
+    pub struct FailingStorage;
+
+    fn failure<T>() -> Result<T> {
+        Err(TimecardError::Database(sqlx::Error::PoolClosed))
+    }
+
+    #[async_trait]
+    impl Storage for FailingStorage {
+        async fn read_entry(&self, _: i32) -> Result<Entry> {
+            failure()
+        }
+
+        async fn read_last_entry(&self) -> Result<Entry> {
+            failure()
+        }
+
+        async fn read_last_n_entries(&self, _: i32) -> Result<Vec<Entry>> {
+            failure()
+        }
+
+        async fn read_entries_since(&self, _: i32) -> Result<Vec<Entry>> {
+            failure()
+        }
+
+        async fn read_entries_between(&self, _: String, _: String) -> Result<Vec<Entry>> {
+            failure()
+        }
+
+        async fn read_archived_entries_between(&self, _: String, _: String) -> Result<Vec<Entry>> {
+            failure()
+        }
+
+        async fn read_client_entries_between(
+            &self,
+            _: String,
+            _: String,
+            _: &str,
+        ) -> Result<Vec<Entry>> {
+            failure()
+        }
+
+        async fn write_entry(&self, _: &NewEntry, _: bool) -> Result<i32> {
+            failure()
+        }
+
+        async fn write_entries(&self, _: &[NewEntry], _: bool) -> Result<Vec<i32>> {
+            failure()
+        }
+
+        async fn update_entry(&self, _: &Entry) -> Result<()> {
+            failure()
+        }
+
+        async fn delete_entry(&self, _: i32) -> Result<()> {
+            failure()
+        }
+
+        async fn delete_last_entry(&self) -> Result<()> {
+            failure()
+        }
+
+        async fn delete_last_n(&self, _: &[i32]) -> Result<()> {
+            failure()
+        }
+
+        async fn undo_last_action(&self) -> Result<Option<UndoRecord>> {
+            failure()
+        }
+
+        async fn integrity_report(&self) -> Result<Vec<IntegrityIssue>> {
+            failure()
+        }
+
+        async fn project_minutes_between(
+            &self,
+            _: NaiveDate,
+            _: NaiveDate,
+            _: bool,
+        ) -> Result<BTreeMap<String, i64>> {
+            failure()
+        }
+
+        async fn read_project(&self, _: i32) -> Result<Project> {
+            failure()
+        }
+
+        async fn read_all_projects(&self) -> Result<Vec<Project>> {
+            failure()
+        }
+
+        async fn read_clients(&self) -> Result<Vec<ClientProjects>> {
+            failure()
+        }
+
+        async fn write_project(&self, _: &Project) -> Result<i32> {
+            failure()
+        }
+
+        async fn update_project(&self, _: &Project) -> Result<()> {
+            failure()
+        }
+
+        async fn delete_project(&self, _: &ProjectCode) -> Result<()> {
+            failure()
+        }
+
+        async fn export(&self) -> Result<Export> {
+            failure()
+        }
+
+        async fn import(&self, _: &Export, _: bool) -> Result<ImportSummary> {
+            failure()
+        }
+    }
+}
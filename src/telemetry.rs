@@ -0,0 +1,314 @@
+// Std
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+// Crates
+use anyhow::{anyhow, Context, Result};
+use tracing::Dispatch;
+use tracing_appender::non_blocking::{NonBlockingBuilder, WorkerGuard};
+use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
+use tracing_subscriber::filter::LevelFilter;
+#[cfg(not(feature = "sentry"))]
+use tracing_subscriber::layer::Identity;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{fmt, Registry};
+
+// Modules
+#[cfg(feature = "sentry")]
+use crate::error_reporting::SentryLayer;
+
+/// Name bunyan records are tagged with.
+pub static APP_NAME: &str = "timecard-d";
+
+/// Log files are named `timecard.log.YYYY-MM-DD`, one per UTC day.
+pub static LOG_FILE_PREFIX: &str = "timecard.log";
+
+/// Where the server writes its logs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogSink {
+    Stdout,
+    /// Daily rotated files in the given directory.
+    File(PathBuf),
+    Both(PathBuf),
+}
+
+impl LogSink {
+    /// Reads `TIMECARD_LOG_SINK` ("stdout", "file" or "both") and
+    /// `TIMECARD_LOG_DIR`.
+    pub fn from_env() -> Result<Self> {
+        let sink = env::var("TIMECARD_LOG_SINK").ok();
+        let dir = env::var("TIMECARD_LOG_DIR").ok().map(PathBuf::from);
+        LogSink::new(sink.as_deref(), dir)
+    }
+
+    /// Without an explicit sink, logs go to stdout and also to `dir` if given.
+    pub fn new(sink: Option<&str>, dir: Option<PathBuf>) -> Result<Self> {
+        let needs_dir = || anyhow!("TIMECARD_LOG_DIR must be set to log to a file!");
+        match (sink.map(|s| s.trim().to_lowercase()).as_deref(), dir) {
+            (None, None) | (Some("stdout"), _) => Ok(LogSink::Stdout),
+            (None, Some(dir)) | (Some("both"), Some(dir)) => Ok(LogSink::Both(dir)),
+            (Some("file"), Some(dir)) => Ok(LogSink::File(dir)),
+            (Some("file"), None) | (Some("both"), None) => Err(needs_dir()),
+            (Some(other), _) => Err(anyhow!(
+                "Unknown log sink '{}': expected stdout, file or both.",
+                other
+            )),
+        }
+    }
+}
+
+/// How log events are formatted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// tracing's default single line format.
+    Full,
+    Compact,
+    /// Multi-line and easiest to read while tailing.
+    Pretty,
+    /// Bunyan JSON, for log ingestion.
+    Bunyan,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Full
+    }
+}
+
+impl LogFormat {
+    /// Reads `TIMECARD_LOG_FORMAT`, defaulting to `full`.
+    pub fn from_env() -> Result<Self> {
+        match env::var("TIMECARD_LOG_FORMAT") {
+            Ok(format) => format.parse(),
+            Err(_) => Ok(LogFormat::default()),
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "full" => Ok(LogFormat::Full),
+            "compact" => Ok(LogFormat::Compact),
+            "pretty" => Ok(LogFormat::Pretty),
+            "bunyan" | "json" => Ok(LogFormat::Bunyan),
+            other => Err(anyhow!(
+                "Unknown log format '{}': expected full, compact, pretty or bunyan.",
+                other
+            )),
+        }
+    }
+}
+
+/// Stacks the layers on a registry that records every level and erases the
+/// resulting type, which differs for every format.
+macro_rules! dispatch {
+    ($($layer:expr),+ $(,)?) => {
+        Dispatch::new(
+            Registry::default()
+                .with(LevelFilter::TRACE)
+                .with(error_reporting())
+                $(.with($layer))+
+        )
+    };
+}
+
+/// Builds the server's subscriber. Events written to a file go through a
+/// background thread that stops when the returned guard is dropped, so keep
+/// it alive for as long as the process runs.
+pub fn get_subscriber(
+    sink: &LogSink,
+    format: LogFormat,
+) -> Result<(Dispatch, Option<WorkerGuard>)> {
+    let (stdout, dir) = match sink {
+        LogSink::Stdout => (true, None),
+        LogSink::File(dir) => (false, Some(dir)),
+        LogSink::Both(dir) => (true, Some(dir)),
+    };
+
+    let (file, guard) = match dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create log directory {}", dir.display()))?;
+            let appender = tracing_appender::rolling::daily(dir, LOG_FILE_PREFIX);
+            // Wait for the writer instead of dropping events when it falls
+            // behind, e.g. while switching to the next day's file.
+            let (writer, guard) = NonBlockingBuilder::default().lossy(false).finish(appender);
+            (Some(writer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    let dispatch = match format {
+        LogFormat::Full => dispatch!(
+            stdout.then(fmt::layer),
+            file.map(|w| fmt::layer().with_writer(w).with_ansi(false)),
+        ),
+        LogFormat::Compact => dispatch!(
+            stdout.then(|| fmt::layer().compact()),
+            file.map(|w| fmt::layer().compact().with_writer(w).with_ansi(false)),
+        ),
+        LogFormat::Pretty => dispatch!(
+            stdout.then(|| fmt::layer().pretty()),
+            file.map(|w| fmt::layer().pretty().with_writer(w).with_ansi(false)),
+        ),
+        LogFormat::Bunyan => dispatch!(
+            JsonStorageLayer,
+            stdout.then(|| BunyanFormattingLayer::new(APP_NAME.to_string(), io::stdout)),
+            file.map(|w| BunyanFormattingLayer::new(APP_NAME.to_string(), w)),
+        ),
+    };
+
+    Ok((dispatch, guard))
+}
+
+/// Formatted log output kept in memory, for asserting on what was logged.
+#[derive(Clone, Default)]
+pub struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    /// A subscriber writing into these logs, with a line for every span that
+    /// closes.
+    pub fn subscriber(&self) -> impl tracing::Subscriber + Send + Sync {
+        let writer = self.clone();
+        fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_span_events(fmt::format::FmtSpan::CLOSE)
+            .finish()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sentry")]
+fn error_reporting() -> SentryLayer {
+    SentryLayer
+}
+
+#[cfg(not(feature = "sentry"))]
+fn error_reporting() -> Identity {
+    Identity::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use tracing::info;
+
+    #[test]
+    fn test_log_sink() -> Result<()> {
+        let dir = PathBuf::from("/var/log/timecard");
+
+        assert_eq!(LogSink::new(None, None)?, LogSink::Stdout);
+        assert_eq!(
+            LogSink::new(None, Some(dir.clone()))?,
+            LogSink::Both(dir.clone())
+        );
+        assert_eq!(
+            LogSink::new(Some("stdout"), Some(dir.clone()))?,
+            LogSink::Stdout
+        );
+        assert_eq!(
+            LogSink::new(Some("File"), Some(dir.clone()))?,
+            LogSink::File(dir)
+        );
+        assert!(LogSink::new(Some("file"), None).is_err());
+        assert!(LogSink::new(Some("syslog"), None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_sink() -> Result<()> {
+        let dir = env::temp_dir().join(format!("timecard_logs_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let (dispatch, guard) = get_subscriber(&LogSink::File(dir.clone()), LogFormat::Full)?;
+        tracing::dispatcher::with_default(&dispatch, || {
+            info!(entries = 3, "Wrote some entries.");
+        });
+        // Flushes the background writer.
+        drop(guard);
+
+        let path = dir.join(format!(
+            "{}.{}",
+            LOG_FILE_PREFIX,
+            Utc::today().format("%Y-%m-%d")
+        ));
+        let logs = fs::read_to_string(&path)?;
+        assert!(logs.contains("Wrote some entries."));
+        assert!(logs.contains("entries=3"));
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_formats() -> Result<()> {
+        assert_eq!("Pretty".parse::<LogFormat>()?, LogFormat::Pretty);
+        assert_eq!("json".parse::<LogFormat>()?, LogFormat::Bunyan);
+        assert!("xml".parse::<LogFormat>().is_err());
+
+        let formats = [
+            (LogFormat::Full, "full"),
+            (LogFormat::Compact, "compact"),
+            (LogFormat::Pretty, "pretty"),
+            (LogFormat::Bunyan, "bunyan"),
+        ];
+        for (format, name) in formats.iter() {
+            let dir =
+                env::temp_dir().join(format!("timecard_logs_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+
+            let (dispatch, guard) = get_subscriber(&LogSink::File(dir.clone()), *format)?;
+            tracing::dispatcher::with_default(&dispatch, || {
+                let span = tracing::info_span!("report", weeks_ago = 0);
+                let _entered = span.enter();
+                info!("Built a report.");
+            });
+            drop(guard);
+
+            let logs = fs::read_to_string(dir.join(format!(
+                "{}.{}",
+                LOG_FILE_PREFIX,
+                Utc::today().format("%Y-%m-%d")
+            )))?;
+            assert!(logs.contains("Built a report."), "{}: {}", name, logs);
+            if *format == LogFormat::Bunyan {
+                let line = logs
+                    .lines()
+                    .find(|l| l.contains("Built a report."))
+                    .unwrap();
+                let record: serde_json::Value = serde_json::from_str(line)?;
+                assert_eq!(record["name"], APP_NAME);
+                assert_eq!(record["weeks_ago"], 0);
+            }
+
+            fs::remove_dir_all(&dir)?;
+        }
+
+        Ok(())
+    }
+}
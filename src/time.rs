@@ -0,0 +1,176 @@
+// Crates
+use chrono::{NaiveTime, Weekday};
+use thiserror::Error;
+
+/// Format of the `start` and `stop` timestamps stored on entries.
+pub static DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// English week day keys as stored in `week_day`, Sunday first.
+pub const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// Week day keys starting from `first`.
+pub fn weekday_order(first: Weekday) -> Vec<&'static str> {
+    let start = first.num_days_from_sunday() as usize;
+    (0..7).map(|i| WEEKDAYS[(start + i) % 7]).collect()
+}
+
+/// Number of days from the most recent `first` back to `day`, i.e. where
+/// `day` falls in a week starting on `first` (0 to 6).
+pub fn weekday_offset(day: Weekday, first: Weekday) -> i64 {
+    (day.num_days_from_sunday() as i64 - first.num_days_from_sunday() as i64).rem_euclid(7)
+}
+
+/// Why an entry time such as `0930` couldn't be read.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum TimeParseError {
+    #[error("Time is empty: expected HHMM, e.g. 0930.")]
+    Empty,
+    #[error("Invalid time '{0}': expected 3 or 4 digits as HHMM, e.g. 930 or 0930.")]
+    Format(String),
+    #[error("Invalid time '{input}': hour {hour} is not between 00 and 23.")]
+    Hour { input: String, hour: u32 },
+    #[error("Invalid time '{input}': minute {minute} is not between 00 and 59.")]
+    Minute { input: String, minute: u32 },
+}
+
+/// Parses an entry time given as `HHMM` (or `HMM`), e.g. `0930` or `930`.
+pub fn parse_entry_time(input: &str) -> Result<NaiveTime, TimeParseError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(TimeParseError::Empty);
+    }
+    if !(3..=4).contains(&input.len()) || !input.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(TimeParseError::Format(input.to_string()));
+    }
+
+    // Only ASCII digits remain, so this can't fail.
+    let value: u32 = input.parse().unwrap_or_default();
+    let (hour, minute) = (value / 100, value % 100);
+    if hour > 23 {
+        return Err(TimeParseError::Hour {
+            input: input.to_string(),
+            hour,
+        });
+    }
+    if minute > 59 {
+        return Err(TimeParseError::Minute {
+            input: input.to_string(),
+            minute,
+        });
+    }
+
+    Ok(NaiveTime::from_hms(hour, minute, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_weekday_order() {
+        assert_eq!(weekday_order(Weekday::Sun), WEEKDAYS.to_vec());
+        assert_eq!(
+            weekday_order(Weekday::Mon),
+            vec!["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+        );
+    }
+
+    #[test]
+    fn test_weekday_offset() {
+        assert_eq!(weekday_offset(Weekday::Sun, Weekday::Sun), 0);
+        assert_eq!(weekday_offset(Weekday::Sat, Weekday::Sun), 6);
+        assert_eq!(weekday_offset(Weekday::Wed, Weekday::Mon), 2);
+
+        // Days before `first` in the calendar wrap to the end of the week.
+        assert_eq!(weekday_offset(Weekday::Sun, Weekday::Mon), 6);
+        assert_eq!(weekday_offset(Weekday::Mon, Weekday::Tue), 6);
+
+        for day in &WEEKDAYS {
+            let day: Weekday = day.parse().unwrap();
+            assert_eq!(weekday_offset(day, day), 0);
+            assert_eq!(weekday_offset(day.succ(), day), 1);
+        }
+    }
+
+    #[test]
+    fn test_parse_entry_time() {
+        let valid = [
+            ("0930", (9, 30)),
+            ("930", (9, 30)),
+            ("0000", (0, 0)),
+            ("0005", (0, 5)),
+            ("000", (0, 0)),
+            ("1200", (12, 0)),
+            ("2359", (23, 59)),
+            (" 0800 ", (8, 0)),
+        ];
+        for (input, (hour, minute)) in valid.iter() {
+            assert_eq!(
+                parse_entry_time(input),
+                Ok(NaiveTime::from_hms(*hour, *minute, 0)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_entry_time_errors() {
+        let format = |s: &str| TimeParseError::Format(s.to_string());
+        let hour = |s: &str, hour| TimeParseError::Hour {
+            input: s.to_string(),
+            hour,
+        };
+        let minute = |s: &str, minute| TimeParseError::Minute {
+            input: s.to_string(),
+            minute,
+        };
+
+        let invalid = [
+            ("", TimeParseError::Empty),
+            ("   ", TimeParseError::Empty),
+            ("9", format("9")),
+            ("93", format("93")),
+            ("09300", format("09300")),
+            ("9:30", format("9:30")),
+            ("+930", format("+930")),
+            ("-930", format("-930")),
+            ("ab12", format("ab12")),
+            ("2400", hour("2400", 24)),
+            ("2575", hour("2575", 25)),
+            ("9999", hour("9999", 99)),
+            ("0960", minute("0960", 60)),
+            ("2399", minute("2399", 99)),
+            ("960", minute("960", 60)),
+        ];
+        for (input, err) in invalid.iter() {
+            assert_eq!(parse_entry_time(input).as_ref(), Err(err), "{}", input);
+        }
+
+        assert_eq!(
+            parse_entry_time("2575").unwrap_err().to_string(),
+            "Invalid time '2575': hour 25 is not between 00 and 23."
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_entry_time_round_trip(hour in 0u32..24, minute in 0u32..60) {
+            let time = NaiveTime::from_hms(hour, minute, 0);
+            prop_assert_eq!(parse_entry_time(&time.format("%H%M").to_string()), Ok(time));
+        }
+
+        #[test]
+        fn prop_entry_time_digits(input in "[0-9]{0,5}") {
+            let value: u32 = input.parse().unwrap_or_default();
+            let valid = (3..=4).contains(&input.len()) && value / 100 < 24 && value % 100 < 60;
+            prop_assert_eq!(parse_entry_time(&input).is_ok(), valid);
+        }
+
+        #[test]
+        fn prop_entry_time_never_panics(input in "\\PC*") {
+            let _ = parse_entry_time(&input);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+// Crates
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveTime};
+
+// Modules
+mod common;
+use common::TestApp;
+use timecard::{db, Entry, Export, NewEntry, Project, REQUEST_ID_HEADER};
+
+fn new_entry(hour: u32) -> Result<NewEntry> {
+    NewEntry::builder()
+        .date(NaiveDate::from_ymd(2021, 2, 3))
+        .start_time(NaiveTime::from_hms(hour, 0, 0))
+        .stop_time(NaiveTime::from_hms(hour + 1, 0, 0))
+        .code("20-008")
+        .memo("work, work, work")
+        .build()
+}
+
+#[tokio::test]
+async fn test_entry_crud() -> Result<()> {
+    let app = TestApp::spawn().await?;
+
+    let res = app
+        .http
+        .post(&app.url("/entry"))
+        .json(&new_entry(9)?)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let mut created: Entry = res.json().await?;
+    let id = created.id.expect("created entries have an id");
+
+    let res = app
+        .http
+        .get(&app.url(&format!("/entry/{}", id)))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.json::<Entry>().await?, created);
+
+    created.memo = "more work".to_string();
+    let res = app
+        .http
+        .post(&app.url("/update_entry"))
+        .json(&created)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // /last_entries is routed past /last_entry and /entry.
+    app.http
+        .post(&app.url("/entry"))
+        .json(&new_entry(13)?)
+        .send()
+        .await?;
+    let res = app.http.get(&app.url("/last_entries/5")).send().await?;
+    let entries: Vec<Entry> = res.json().await?;
+    assert_eq!(entries.len(), 2);
+    assert!(entries.contains(&created));
+
+    let res = app
+        .http
+        .post(&app.url(&format!("/delete_entry/{}", id)))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = app
+        .http
+        .get(&app.url(&format!("/entry/{}", id)))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_post_frontend_entry() -> Result<()> {
+    let app = TestApp::spawn().await?;
+
+    // The web form's body: plain strings, with the week day worked out from
+    // the entered date.
+    let body = serde_json::json!({
+        "start": "2021-02-03 09:00:00",
+        "stop": "2021-02-03 10:30:00",
+        "week_day": "Wed",
+        "code": "20-008",
+        "memo": "work, work, work",
+    });
+    let res = app.http.post(&app.url("/entry")).json(&body).send().await?;
+    assert_eq!(res.status(), 200);
+
+    let created: Entry = res.json().await?;
+    let id = created.id.expect("created entries have an id");
+    assert_eq!(db::read_entry(&app.pool, id).await?, created);
+    assert_eq!(created.start, "2021-02-03 09:00:00");
+    assert_eq!(created.week_day, timecard::Weekday::Wed);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_project_crud() -> Result<()> {
+    let app = TestApp::spawn().await?;
+
+    let project = Project {
+        id: None,
+        name: "Acme Website".to_string(),
+        code: "20-008".parse()?,
+        client: Some("Acme".to_string()),
+    };
+    let res = app
+        .http
+        .post(&app.url("/project"))
+        .json(&project)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let projects: Vec<Project> = app
+        .http
+        .get(&app.url("/all_projects"))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(projects.len(), 1);
+    let id = projects[0].id.expect("stored projects have an id");
+
+    let renamed = Project {
+        id: Some(id),
+        name: "Acme Shop".to_string(),
+        ..project
+    };
+    let res = app
+        .http
+        .post(&app.url("/update_project"))
+        .json(&renamed)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = app
+        .http
+        .get(&app.url(&format!("/project/{}", id)))
+        .send()
+        .await?;
+    assert_eq!(res.json::<Project>().await?.name, "Acme Shop");
+
+    let res = app
+        .http
+        .post(&app.url("/delete_project/20-008"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = app
+        .http
+        .post(&app.url("/delete_project/20-008"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_import_round_trip() -> Result<()> {
+    let source = TestApp::spawn().await?;
+    let project = Project {
+        id: None,
+        name: "Acme Website".to_string(),
+        code: "20-008".parse()?,
+        client: Some("Acme".to_string()),
+    };
+    source
+        .http
+        .post(&source.url("/project"))
+        .json(&project)
+        .send()
+        .await?;
+    for hour in &[9, 13] {
+        source
+            .http
+            .post(&source.url("/entry"))
+            .json(&new_entry(*hour)?)
+            .send()
+            .await?;
+    }
+
+    let export: Export = source
+        .http
+        .get(&source.url("/export"))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(export.projects.len(), 1);
+    assert_eq!(export.entries.len(), 2);
+
+    let target = TestApp::spawn().await?;
+    let res = target
+        .http
+        .post(&target.url("/import"))
+        .json(&export)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let imported: Export = target
+        .http
+        .get(&target.url("/export"))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(imported, export);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_routing() -> Result<()> {
+    let app = TestApp::spawn().await?;
+
+    let res = app.http.get(&app.url("/no_such_route")).send().await?;
+    assert_eq!(res.status(), 404);
+    let res = app.http.get(&app.url("/all_projects")).send().await?;
+    assert!(res.headers().contains_key(REQUEST_ID_HEADER));
+
+    Ok(())
+}
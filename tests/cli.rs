@@ -0,0 +1,149 @@
+// Std
+use std::env;
+
+// Crates
+use anyhow::Result;
+use assert_cmd::Command;
+use chrono::Local;
+
+// Modules
+mod common;
+use common::TestApp;
+use timecard::db;
+
+/// Runs the CLI against `app`, answering yes to any prompt, and returns what
+/// it printed to stdout and stderr. Only the given arguments and the server's
+/// URL reach it, never the developer's own config.
+fn timecard(app: &TestApp, args: &[&str]) -> Result<(String, String)> {
+    let output = Command::cargo_bin("timecard")?
+        .env_clear()
+        .env("BASE_URL", app.url("/"))
+        .env(
+            "TIMECARD_CONFIG",
+            env::temp_dir().join("timecard_e2e_missing_config.toml"),
+        )
+        .env("TIMECARD_WEEK_START", "Mon")
+        .current_dir(env::temp_dir())
+        .args(args)
+        .write_stdin("y\n")
+        .output()?;
+
+    Ok((
+        String::from_utf8(output.stdout)?,
+        String::from_utf8(output.stderr)?,
+    ))
+}
+
+// Running the CLI blocks the test's thread, so the server needs threads of
+// its own.
+#[tokio::test(threaded_scheduler)]
+async fn test_cli_entries() -> Result<()> {
+    let app = TestApp::spawn().await?;
+
+    let (stdout, stderr) = timecard(&app, &["-e", "0900|1030|20-008|work, work, work"])?;
+    assert!(stdout.contains("Entry submitted."), "{}", stderr);
+    let entry = db::read_last_entry(&app.pool).await?;
+    assert_eq!(entry.memo, "work, work, work");
+    assert_eq!(
+        entry.start,
+        format!("{} 09:00:00", Local::today().naive_local())
+    );
+
+    let (stdout, stderr) = timecard(&app, &["-b", "2021-02-03|1300|1500|20-008|backdated"])?;
+    assert!(stdout.contains("Entry submitted."), "{}", stderr);
+    let entries = db::read_entries_between(
+        &app.pool,
+        "2021-02-03 00:00:00".to_string(),
+        "2021-02-03 23:59:59".to_string(),
+    )
+    .await?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].stop, "2021-02-03 15:00:00");
+
+    let (stdout, stderr) = timecard(&app, &["-w", "2021-02-03"])?;
+    assert!(stdout.contains("2021-W05"), "{}", stderr);
+    assert!(stdout.contains("20-008"));
+
+    let (stdout, stderr) = timecard(&app, &["--compare", "2021-W05", "2021-W04"])?;
+    assert!(stdout.contains("20-008"), "{}", stderr);
+
+    let (stdout, stderr) = timecard(&app, &["-d"])?;
+    assert!(stdout.contains("Most recent entry deleted."), "{}", stderr);
+    assert_eq!(db::count_entries(&app.pool).await?, 1);
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_cli_projects() -> Result<()> {
+    let app = TestApp::spawn().await?;
+
+    let (stdout, stderr) = timecard(&app, &["-a", "Acme Website", "20-008", "--client", "Acme"])?;
+    assert!(stdout.contains("Project saved."), "{}", stderr);
+
+    let (stdout, _) = timecard(&app, &["-p"])?;
+    assert!(stdout.contains("Acme Website"));
+    assert!(stdout.contains("20-008"));
+
+    let (stdout, stderr) = timecard(&app, &["--delete-project", "20-008"])?;
+    assert!(stdout.contains("Project deleted."), "{}", stderr);
+    assert_eq!(db::count_projects(&app.pool).await?, 0);
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_cli_error_has_request_id() -> Result<()> {
+    let app = TestApp::spawn().await?;
+
+    // Nothing has been done yet, so there's nothing to undo.
+    let (_, stderr) = timecard(&app, &["undo"])?;
+    let request_id = stderr
+        .trim()
+        .rsplit("(request id ")
+        .next()
+        .and_then(|rest| rest.strip_suffix(')'))
+        .unwrap_or_else(|| panic!("no request id in {:?}", stderr));
+
+    let logs = app.logs.contents();
+    let line = logs
+        .lines()
+        .find(|line| line.contains("Request rejected."))
+        .expect("the server logged no rejection");
+    assert!(line.contains(request_id), "{}", line);
+
+    Ok(())
+}
+
+#[test]
+fn test_cli_template_exit_codes() -> Result<()> {
+    let config = env::temp_dir().join(format!(
+        "timecard_e2e_templates_{}.toml",
+        std::process::id()
+    ));
+    let template = |args: &[&str]| -> Result<std::process::Output> {
+        Ok(Command::cargo_bin("timecard")?
+            .env_clear()
+            .env("TIMECARD_CONFIG", &config)
+            .arg("template")
+            .args(args)
+            .output()?)
+    };
+
+    let output = template(&["add", "standup", "0830|0900|20-000|daily standup"])?;
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)?.contains("Template 'standup' saved."));
+
+    let output = template(&["list"])?;
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)?.contains("daily standup"));
+
+    assert!(template(&["remove", "standup"])?.status.success());
+    let output = template(&["remove", "standup"])?;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)?.contains("No template named 'standup'."));
+
+    std::fs::remove_file(&config)?;
+
+    Ok(())
+}
@@ -0,0 +1,118 @@
+// Crates
+use anyhow::Result;
+use chrono::{Local, NaiveTime};
+
+// Modules
+mod common;
+use common::TestApp;
+use timecard::client::TimecardClient;
+use timecard::error::TimecardError;
+use timecard::report::ReportOptions;
+use timecard::{NewEntry, Project, UndoRecord, Weekday};
+
+fn todays_entry() -> Result<NewEntry> {
+    NewEntry::builder()
+        .date(Local::today().naive_local())
+        .start_time(NaiveTime::from_hms(9, 0, 0))
+        .stop_time(NaiveTime::from_hms(10, 30, 0))
+        .code("20-008")
+        .memo("work, work, work")
+        .build()
+}
+
+#[tokio::test]
+async fn test_client_entries() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = app.client();
+
+    let created = client.create_entry(&todays_entry()?, false).await?;
+    assert!(created.id.is_some());
+    assert!(matches!(
+        client.create_entry(&todays_entry()?, false).await,
+        Err(TimecardError::Duplicate(id)) if Some(id) == created.id
+    ));
+    assert_eq!(client.last_entry().await?, created);
+    assert_eq!(client.last_entries(5).await?, vec![created.clone()]);
+
+    let latest = client.entries_since(0).await?;
+    assert_eq!(latest.entries, vec![created.clone()]);
+    assert_eq!(latest.max_id, created.id);
+
+    let today = Local::today().naive_local();
+    assert_eq!(
+        client.entries_between(today, today).await?,
+        vec![created.clone()]
+    );
+
+    let calendar = client.entries_ics(today, today).await?;
+    assert!(calendar.contains(&format!("UID:entry-{}@timecard", created.id.unwrap())));
+
+    let report = client
+        .weekly_report("0", Weekday::Mon, ReportOptions::default())
+        .await?;
+    assert_eq!(report.days[0], Weekday::Mon);
+    assert_eq!(report.total(), 90);
+
+    assert_eq!(
+        client.undo().await?,
+        Some(UndoRecord::Created(vec![created]))
+    );
+    assert_eq!(client.undo().await?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_client_projects() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = app.client();
+
+    let project = Project {
+        id: None,
+        name: "Acme Website".to_string(),
+        code: "20-008".parse()?,
+        client: Some("Acme".to_string()),
+    };
+    client.create_project(&project).await?;
+
+    let projects = client.projects().await?;
+    assert_eq!(projects.len(), 1);
+    assert_eq!(projects[0].code, "20-008");
+
+    let clients = client.clients().await?;
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].client, "Acme");
+    assert_eq!(clients[0].projects, 1);
+
+    client.create_entry(&todays_entry()?, false).await?;
+    let grouped = client.client_weekly_report("0", Weekday::Mon).await?;
+    assert_eq!(grouped.groups.len(), 1);
+    assert_eq!(grouped.groups[0].client, "Acme");
+    assert_eq!(grouped.totals.iter().sum::<i64>(), 90);
+
+    client.delete_project(&project.code).await?;
+    assert!(client.projects().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_client_errors() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = app.client();
+
+    let err = client.last_entry().await.unwrap_err();
+    assert!(matches!(err, TimecardError::NotFound(_)));
+    assert_eq!(err.to_string(), "Last entry not found.");
+
+    let err = client.last_entries(0).await.unwrap_err();
+    assert!(matches!(err, TimecardError::Validation(ref fields) if fields[0].field == "count"));
+
+    let unreachable = TimecardClient::new("http://127.0.0.1:9");
+    assert!(matches!(
+        unreachable.projects().await,
+        Err(TimecardError::Request(_))
+    ));
+
+    Ok(())
+}
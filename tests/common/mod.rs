@@ -0,0 +1,89 @@
+// Std
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+// Crates
+use anyhow::Result;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sqlx::sqlite::SqlitePool;
+
+// Modules
+use timecard::client::TimecardClient;
+use timecard::storage::SqliteStorage;
+use timecard::telemetry::CapturedLogs;
+use timecard::{api, db};
+
+/// The full API served from a fresh database on a free port. The database is
+/// removed when the app is dropped.
+pub struct TestApp {
+    pub addr: SocketAddr,
+    pub http: reqwest::Client,
+    /// The served database, for checking what requests left behind.
+    pub pool: SqlitePool,
+    /// Everything the server logged.
+    pub logs: CapturedLogs,
+    db_path: PathBuf,
+}
+
+impl TestApp {
+    pub async fn spawn() -> Result<TestApp> {
+        let name: String = thread_rng().sample_iter(&Alphanumeric).take(16).collect();
+        let db_path = env::temp_dir().join(format!("timecard_{}_test.db", name));
+
+        let pool = SqlitePool::new(&format!("sqlite://{}", db_path.display())).await?;
+        db::setup_db(&pool).await?;
+
+        // The server gets a thread of its own so its logs can be captured
+        // without a global subscriber.
+        let logs = CapturedLogs::default();
+        let subscriber = logs.subscriber();
+        let routes = api::routes(Arc::new(SqliteStorage::new(pool.clone())));
+        let (addr_tx, addr_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _guard = tracing::subscriber::set_default(subscriber);
+            let mut runtime = tokio::runtime::Builder::new()
+                .basic_scheduler()
+                .enable_all()
+                .build()
+                .expect("failed to start the test server's runtime");
+            runtime.block_on(async move {
+                let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+                addr_tx.send(addr).expect("test app went away");
+                server.await
+            });
+        });
+
+        Ok(TestApp {
+            addr: addr_rx.recv()?,
+            http: reqwest::Client::new(),
+            pool,
+            logs,
+            db_path,
+        })
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// A typed client for this app.
+    #[allow(dead_code)]
+    pub fn client(&self) -> TimecardClient {
+        TimecardClient::new(self.url("/"))
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        for suffix in &["", "-wal", "-shm", "-journal"] {
+            let mut path = self.db_path.clone().into_os_string();
+            path.push(suffix);
+            let _ = fs::remove_file(path);
+        }
+    }
+}